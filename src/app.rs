@@ -1,24 +1,51 @@
-use crate::config::Config;
+use crate::attachments::{self, Attachment};
+use crate::case::Case;
+use crate::diff::{self, DiffLine};
+use crate::merge::{self, Choice, Region};
+use crate::clipboard::Clipboard;
+use crate::config::{Config, FilenameStyle, LaunchPull, Symlinks, TabMode};
+use crate::crypt;
+use crate::datefmt;
+use crate::html;
+use crate::diagram;
+use crate::format;
 use crate::fs::{
-    build_notes_tree, ensure_notes_dir, flatten_tree_for_sidebar, read_note, rename_note,
-    write_note, FlatNode,
+    build_notes_tree, collect_dirs, collect_files, ensure_notes_dir, find_dir, flatten_tree_for_sidebar, is_text_file, read_note, rename_note,
+    rescan_subtree, write_note, FlatNode, NoteNode,
 };
-use crate::git::GitSection;
+use crate::git::{ChangedFile, CommitInfo, GitSection};
+use crate::logging::{self, log, Level};
+use crate::markdown;
+use crate::menu::{self, Menu, MenuAction};
+use crate::search::{find_in_lines, fuzzy_score, relative_to, search_vault, Matcher, SearchHit};
+use crate::links::{self, Anchor, LinkIndex, LinkIssue};
+use crate::replace::{self, ReplaceMatch};
+use crate::runbook;
+use crate::snapshots::{LocalHistory, Snapshot};
+use crate::search::SearchOptions;
+use crate::searches::Searches;
+use crate::session::Session;
+use crate::signals::{self, Signal, Signals};
+use crate::stats::{NoteStats, Stats};
+use crate::theme::{IconSet, Theme};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use crossterm::event::{
-    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+    self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
 };
 use crossterm::execute;
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
 };
 use ratatui::backend::CrosstermBackend;
+use ratatui::layout::Rect;
 use ratatui::widgets::ListState;
 use ratatui::Terminal;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
  
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -27,6 +54,7 @@ pub enum Focus {
     Title,
     Content,
     Commits,
+    ChangedFiles,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -39,14 +67,192 @@ pub enum RightFocus {
 pub enum Modal {
     ConfirmDelete { path: PathBuf },
     InputName { current: String, target_dir: PathBuf },
+    /// In-note find; `origin` is where the cursor goes back to on Esc.
+    Find { query: String, origin: (usize, usize) },
+    /// `history_pos` is set while Up at the top recalls earlier queries.
+    VaultSearch { query: String, hits: Vec<SearchHit>, selected: usize, history_pos: Option<usize> },
+    /// Naming the query of the vault search for the sidebar.
+    NameSearch { query: String, name: String },
+    /// `[text](url)` builder; Tab switches between the two fields.
+    InsertLink { text: String, url: String, editing_url: bool },
+    NotePicker { query: String, matches: Vec<PathBuf>, selected: usize, action: PickAction },
+    /// Headings of the open note; Enter jumps to the selected one.
+    Outline { selected: usize },
+    /// Mark list; a letter sets (`setting`) or jumps to that mark.
+    Marks { setting: bool, selected: usize },
+    /// Register prompt for macros; digits typed before the register set the
+    /// replay count.
+    MacroRegister { record: bool, count: String },
+    /// Leader-key menu shown as a bottom panel.
+    Menu { menu: &'static Menu },
+    /// `amend` rewrites HEAD with the staged changes instead of committing
+    /// everything anew.
+    CommitMessage { message: String, amend: bool },
+    /// Type, scope and summary for `commit_template`; `field` is the one
+    /// being typed in.
+    CommitForm { fields: [String; 3], field: usize },
+    /// Quit was requested (signal or Ctrl+C) with unsaved changes.
+    ConfirmQuit,
+    /// Tail of the debug log; `scroll` counts lines up from the newest.
+    Log { scroll: usize },
+    /// Details of the errors reported this session, newest first.
+    Errors,
+    /// Revisions of the open note; Content shows `lines` of the selected one
+    /// read-only until Esc, or Enter restores it into the buffer.
+    History { revisions: Vec<CommitInfo>, selected: usize, lines: Vec<String>, scroll: usize },
+    /// The open note changed on disk while it had unsaved edits; `selected`
+    /// indexes the conflicting hunks. `theirs`/`mtime` describe the file.
+    Merge { regions: Vec<Region>, selected: usize, theirs: Vec<String>, mtime: Option<SystemTime> },
+    /// Buffer vs. `base`, with unchanged runs folded.
+    Diff { base: DiffBase, rows: Vec<DiffLine>, scroll: usize },
+    /// What commit `hash` did to one file, picked in Changed Files.
+    CommitDiff { hash: String, file: ChangedFile, rows: Vec<DiffLine>, scroll: usize },
+    /// Same browser over the note's `.history/` snapshots.
+    LocalHistory { snapshots: Vec<Snapshot>, selected: usize, lines: Vec<String>, scroll: usize },
+    Stats(Stats),
+    NoteInfo(NoteStats),
+    /// Paste from the clipboard history.
+    Yanks { selected: usize },
+    /// Pick one of `date_formats` to insert.
+    InsertDate { selected: usize },
+    /// Line number or percentage typed in the footer.
+    GotoLine { input: String },
+    /// Name for a new note made from the text between `from` and `to`
+    /// (`(row, byte col)`, end exclusive); `open` switches to it afterwards.
+    ExtractNote { name: String, from: (usize, usize), to: (usize, usize), open: bool },
+    /// Output of the link report; Enter jumps to the entry.
+    LinkReport { issues: Vec<LinkIssue>, selected: usize },
+    /// `(name, URL)` of the repo's remotes. `form` is the remote being added
+    /// or re-pointed, with the same fields as `InsertLink`.
+    Remotes { remotes: Vec<(String, String)>, selected: usize, form: Option<(String, String, bool)> },
+    /// Attachments of the open note, or (`vault`) the unreferenced ones
+    /// across the vault; `renaming` holds the new file name being typed.
+    Attachments { items: Vec<Attachment>, selected: usize, vault: bool, renaming: Option<String> },
+    /// Path of a file to copy into the vault; `candidates` are the last
+    /// Tab completions.
+    ImportFile { input: String, candidates: Vec<String>, to_assets: bool },
+    /// Presentation mode: the buffer full-screen, one `slides` range at a
+    /// time.
+    Present { slides: Vec<(usize, usize)>, index: usize },
+    /// Files deleted this session, newest first, to restore one of.
+    Deleted { selected: usize },
+    /// An action on the files marked in the sidebar: `input` (folder or
+    /// tag) is asked for first when the action needs one.
+    Batch { action: BatchAction, input: String, confirming: bool },
+    /// Key of an encrypted note, to open `path` (at `at`) or (`sealing`)
+    /// to save it.
+    Passphrase { path: PathBuf, input: String, sealing: bool, at: Option<Anchor> },
+    /// `git stash list`; Enter pops the selected entry, `a` applies it.
+    Stashes { stashes: Vec<(String, String)>, selected: usize },
+    /// Vault-wide replace: edit the two fields, then review every match
+    /// (`previewing`) before applying.
+    Replace {
+        pattern: String,
+        with: String,
+        editing_with: bool,
+        matches: Vec<ReplaceMatch>,
+        selected: usize,
+        previewing: bool,
+        commit: bool,
+    },
+}
+
+/// A named position in the open note. The line text lets the mark find its
+/// line again after edits above it shift the rows.
+#[derive(Debug, Clone)]
+pub struct Mark {
+    pub row: usize,
+    pub col: usize,
+    line: String,
+}
+
+/// The editor state of whichever of the open note and the scratch buffer
+/// is not on screen; toggling swaps it with the live fields.
+#[derive(Debug, Default)]
+struct Buffer {
+    title: String,
+    title_cursor: usize,
+    lines: Vec<String>,
+    cursor_row: usize,
+    cursor_col: usize,
+    scroll_y: usize,
+    scroll_x: usize,
+    opened_path: Option<PathBuf>,
+    new_note_dir: Option<PathBuf>,
+    dirty: bool,
+    folded: HashSet<String>,
+    marks: BTreeMap<char, Mark>,
+}
+
+const SCRATCH_FILE: &str = "scratch.md";
+
+/// Errors kept for the details popup.
+const MAX_ERRORS: usize = 20;
+
+/// Lines shown by the sidebar preview popup.
+const PREVIEW_LINES: usize = 20;
+
+/// How often the open note is checked for edits made by other programs.
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest pause between keys typed to jump to a sidebar entry by name.
+const TYPEAHEAD_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// A failure shown in the status bar; `detail` holds the full cause chain.
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    pub summary: String,
+    pub detail: String,
+}
+
+/// A file deleted this session, kept in memory so it can be put back.
+#[derive(Debug, Clone)]
+pub struct DeletedFile {
+    pub path: PathBuf,
+    content: Vec<u8>,
+    at: time::OffsetDateTime,
+}
+
+/// What can be done to all files marked in the sidebar at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchAction {
+    Delete,
+    /// Into a folder given relative to the vault.
+    Move,
+    /// Adds a frontmatter tag to the marked notes.
+    Tag,
+    /// Into the `archive/` folder of the vault.
+    Archive,
+}
+
+/// Folder that sidebar batch archiving moves notes into.
+const ARCHIVE: &str = "archive";
+
+/// What the change view compares the buffer with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffBase {
+    Saved,
+    Committed,
+}
+
+/// What the fuzzy note picker does with the chosen note.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickAction {
+    InsertWikilink,
+    /// Finishes a `[[` the user just typed.
+    CompleteWikilink,
 }
 
 pub struct App {
     pub notes_dir: PathBuf,
 
+    /// In-memory copy of the vault tree; refreshes patch it per directory.
+    notes_tree: NoteNode,
     pub sidebar_items: Vec<FlatNode>,
     pub expanded_dirs: HashSet<PathBuf>,
     pub sidebar_state: ListState,
+    /// Last drawn area of the Changed Files pane, for mapping clicks to rows.
+    pub changed_files_area: Rect,
 
     pub title: String,
     pub title_cursor: usize,
@@ -54,18 +260,115 @@ pub struct App {
     pub cursor_row: usize,
     pub cursor_col: usize,
     pub scroll_y: usize,
+    pub scroll_x: usize,
+    pub wrap: bool,
+    pub tab_mode: TabMode,
+    pub tab_width: usize,
+    pub auto_pair: bool,
+    pub text_width: usize,
+    pub auto_wrap: bool,
+    pub date_formats: Vec<String>,
+    pub commit_template: Option<String>,
+    pub commit_types: Vec<String>,
+    /// Commit dates as `commit_date_format` timestamps instead of "2 days
+    /// ago"; `t` in the Commits pane toggles.
+    pub absolute_dates: bool,
+    commit_date_format: String,
+    date_language: String,
+    pub searches: Searches,
+    /// Rebuilt when the note picker opens or a link is followed, so
+    /// aliases edited since are picked up.
+    pub link_index: LinkIndex,
+    /// The open note as last read from or written to disk, the merge base
+    /// when it is changed behind our back.
+    disk_lines: Vec<String>,
+    disk_mtime: Option<SystemTime>,
+    last_disk_check: Instant,
+    /// `None` when `local_history` is off.
+    local_history: Option<LocalHistory>,
+    /// Where `lazynotes capture` appends; Alt+I opens it.
+    inbox: PathBuf,
+    /// Keys of the encrypted notes opened this session; never written out.
+    keys: HashMap<PathBuf, String>,
+    /// Notes marked `encrypted: true`, shown with a lock in the sidebar.
+    pub encrypted: HashSet<PathBuf>,
+    /// Files deleted this session with what they held, oldest first; `u` in
+    /// the sidebar puts back the last one.
+    pub deleted: Vec<DeletedFile>,
+    /// Files marked with Space in the sidebar for a batch action.
+    pub marked: HashSet<PathBuf>,
+    /// `(notes, words)` under each folder shown in the sidebar, filled in
+    /// as folders come into view when `folder_counts` is on and dropped
+    /// when their contents are rescanned.
+    folder_counts: Option<HashMap<PathBuf, (usize, usize)>>,
+    symlinks: Symlinks,
+    /// Count typed before a sidebar motion.
+    sidebar_count: Option<usize>,
+    /// `g` was pressed in the sidebar; another `g` jumps to the top.
+    sidebar_pending_g: bool,
+    /// Name prefix typed in the sidebar and when its last key came.
+    typeahead: Option<(String, Instant)>,
+    /// Peek at the selected sidebar note in a popup (`p` in the sidebar).
+    pub preview: bool,
+    /// Start of the previewed file, read again when it changes on disk.
+    preview_cache: Option<(PathBuf, Option<SystemTime>, Vec<String>)>,
+    /// Inner size of the Content pane at the last draw, for scrolling.
+    content_width: usize,
+    content_height: usize,
+    /// Other end of the Content selection; the cursor is the moving end.
+    pub selection_anchor: Option<(usize, usize)>,
+    /// Fixed corner of the block selection (Alt+B) as row and char column.
+    pub block_anchor: Option<(usize, usize)>,
+    /// Live matches of the find prompt as `(row, start, end)`.
+    pub find_matches: Vec<(usize, usize, usize)>,
+    /// Folded sections of the open note, keyed by the heading line so edits
+    /// above them do not shift the fold.
+    folded: HashSet<String>,
+    /// Fold state of notes opened earlier in the session.
+    fold_memory: HashMap<PathBuf, HashSet<String>>,
+    pub marks: BTreeMap<char, Mark>,
+    /// Recorded key sequences by register.
+    macros: HashMap<char, Vec<KeyEvent>>,
+    /// Register being recorded into and the keys so far.
+    pub recording: Option<(char, Vec<KeyEvent>)>,
+    replaying: bool,
+    /// Marks of notes opened earlier in the session.
+    mark_memory: HashMap<PathBuf, BTreeMap<char, Mark>>,
     pub opened_path: Option<PathBuf>,
     pub dirty: bool,
+    /// The scratch buffer is on screen; `parked` holds the note.
+    pub scratch_active: bool,
+    parked: Buffer,
+    persist_scratch: bool,
+    format_on_save: bool,
+    /// `exit_commit_message` when `commit_on_exit` is on.
+    exit_commit: Option<String>,
+    title_sync: bool,
+    filename_style: FilenameStyle,
+    formatter: Option<String>,
+    open_with: BTreeMap<String, String>,
+    run_blocks: BTreeMap<String, String>,
+    /// The code block running in the background, if any.
+    running_block: Option<runbook::Job>,
 
     pub focus: Focus,
     pub last_right_focus: RightFocus,
 
-    terminal: Terminal<CrosstermBackend<io::Stdout>>,
-
     pub git_section: GitSection,
+    pub theme: Theme,
+    pub icons: IconSet,
+    pub math_unicode: bool,
+    pub clipboard: Clipboard,
     pub status_message: Option<String>,
+    pub errors: Vec<ErrorReport>,
+    /// Errors reported since the details popup was last opened.
+    pub unseen_errors: usize,
     pub new_note_dir: Option<PathBuf>,
     pub modal: Option<Modal>,
+    /// Set by modal actions that end the session, checked by the event loop.
+    quitting: bool,
+    /// Another instance holds the vault lock: nothing is written to disk.
+    pub read_only: bool,
 }
 
 impl App {
@@ -73,80 +376,230 @@ impl App {
         let notes_dir = config.notes_path();
         ensure_notes_dir(&notes_dir)?;
 
-        let mut expanded_dirs = HashSet::new();
-        expanded_dirs.insert(notes_dir.clone());
+        let expanded_dirs = Session::load().expanded(&notes_dir);
 
-        let sidebar_items = Self::build_sidebar(&notes_dir, &expanded_dirs)?;
+        let notes_tree = logging::timed("fs", format_args!("build tree dir={:?}", notes_dir), || build_notes_tree(&notes_dir, config.symlinks))?;
+        let sidebar_items = flatten_tree_for_sidebar(&notes_tree, &expanded_dirs);
+        let encrypted = logging::timed("fs", format_args!("scan encrypted"), || {
+            collect_files(&notes_tree).into_iter().filter(|p| links::is_note(p) && crypt::file_is_marked(p)).collect()
+        });
 
-        let git_section = GitSection::new_for(Some(notes_dir.clone()));
+        let mut git_section = GitSection::new_for(Some(notes_dir.clone()));
+        git_section.user_name = config.git_name.clone();
+        git_section.user_email = config.git_email.clone();
 
         let mut sidebar_state = ListState::default();
         if !sidebar_items.is_empty() {
             sidebar_state.select(Some(0));
         }
 
-        enable_raw_mode()?;
-        let mut stdout = io::stdout();
-        execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-        let backend = CrosstermBackend::new(stdout);
-        let terminal = Terminal::new(backend)?;
-
         let mut app = Self {
             notes_dir,
+            notes_tree,
             sidebar_items,
             expanded_dirs,
             sidebar_state,
+            changed_files_area: Rect::default(),
             title: String::new(),
             title_cursor: 0,
             lines: vec![String::new()],
             cursor_row: 0,
             cursor_col: 0,
             scroll_y: 0,
+            scroll_x: 0,
+            wrap: config.wrap,
+            tab_mode: config.tab_mode,
+            tab_width: config.tab_width.max(1),
+            auto_pair: config.auto_pair,
+            text_width: config.text_width.max(1),
+            auto_wrap: config.auto_wrap,
+            date_formats: config.date_formats.clone(),
+            commit_template: config.commit_template.clone(),
+            commit_types: config.commit_types.clone(),
+            absolute_dates: false,
+            commit_date_format: config.commit_date_format.clone(),
+            date_language: config.date_locale.clone().unwrap_or_else(datefmt::env_language),
+            searches: Searches::load(),
+            link_index: LinkIndex::default(),
+            inbox: crate::capture::inbox_path(&config),
+            keys: HashMap::new(),
+            encrypted,
+            deleted: Vec::new(),
+            marked: HashSet::new(),
+            folder_counts: config.folder_counts.then(HashMap::new),
+            symlinks: config.symlinks,
+            sidebar_count: None,
+            sidebar_pending_g: false,
+            typeahead: None,
+            preview: false,
+            preview_cache: None,
+            disk_lines: Vec::new(),
+            disk_mtime: None,
+            last_disk_check: Instant::now(),
+            local_history: config
+                .local_history
+                .unwrap_or(git_section.repo_root.is_none())
+                .then(|| LocalHistory::new(&config.notes_path(), config.snapshot_interval, config.history_max_mb)),
+            content_width: 80,
+            content_height: 20,
+            selection_anchor: None,
+            block_anchor: None,
+            find_matches: Vec::new(),
+            folded: HashSet::new(),
+            fold_memory: HashMap::new(),
+            marks: BTreeMap::new(),
+            mark_memory: HashMap::new(),
+            macros: HashMap::new(),
+            recording: None,
+            replaying: false,
             opened_path: None,
             dirty: false,
+            scratch_active: false,
+            parked: Buffer { lines: load_scratch(config.persist_scratch), ..Buffer::default() },
+            persist_scratch: config.persist_scratch,
+            format_on_save: config.format_on_save,
+            exit_commit: config.commit_on_exit.then(|| config.exit_commit_message.clone()),
+            title_sync: config.title_sync,
+            filename_style: config.filename_style,
+            formatter: config.formatter.clone(),
+            run_blocks: config.run_blocks.clone(),
+            running_block: None,
+            open_with: config.open_with.iter().map(|(ext, cmd)| (ext.trim_start_matches('.').to_lowercase(), cmd.clone())).collect(),
             focus: Focus::Sidebar,
             last_right_focus: RightFocus::Title,
-            terminal,
             git_section,
+            theme: Theme::named(&config.theme, config.background),
+            icons: config.icons,
+            math_unicode: config.math_unicode,
+            clipboard: Clipboard::new(config.clipboard),
             status_message: None,
+            errors: Vec::new(),
+            unseen_errors: 0,
             new_note_dir: None,
             modal: None,
+            quitting: false,
+            read_only: false,
         };
 
-        if app.git_section.commits.is_empty() {
-            app.status_message = Some("No commits found in notes folder or git not initialized".to_string());
+        if !Theme::NAMES.contains(&config.theme.as_str()) {
+            app.status_message = Some(format!("Unknown theme '{}', using default", config.theme));
         }
 
         Ok(app)
     }
 
+    /// Starts the `pull_on_launch` update; its outcome lands in the status
+    /// bar like a pull from the menu.
+    pub fn pull_on_launch(&mut self, mode: LaunchPull) {
+        if self.git_section.repo_root.is_none() {
+            return;
+        }
+        match mode {
+            LaunchPull::Off => return,
+            LaunchPull::Rebase => self.git_section.pull_rebase(),
+            LaunchPull::Ff => self.git_section.pull(),
+        }
+        self.status_message = Some("Pulling on launch...".into());
+    }
+
     pub fn run(&mut self) -> Result<()> {
-        let res = self.event_loop();
+        enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
+        let backend = CrosstermBackend::new(stdout);
+        let mut terminal = Terminal::new(backend)?;
+
+        let res = Signals::install().and_then(|signals| self.event_loop(&mut terminal, &signals));
 
         disable_raw_mode()?;
         execute!(
-            self.terminal.backend_mut(),
+            terminal.backend_mut(),
             LeaveAlternateScreen,
-            DisableMouseCapture
+            DisableMouseCapture,
+            DisableBracketedPaste
         )?;
-        self.terminal.show_cursor()?;
+        terminal.show_cursor()?;
+
+        let mut session = Session::load();
+        session.set_expanded(&self.notes_dir, &self.expanded_dirs);
+        if let Err(e) = session.save() {
+            eprintln!("lazynotes: {:#}", e);
+        }
 
+        // After the terminal is back so a signing prompt can use it.
+        if let Some(format) = &self.exit_commit
+            && !self.read_only
+        {
+            match self.git_section.commit_session(&datefmt::strftime(datefmt::now(), format)) {
+                Ok(true) => println!("lazynotes: committed the session's changes"),
+                Ok(false) => {}
+                Err(e) => eprintln!("lazynotes: session commit failed: {:#}", e),
+            }
+        }
         res
     }
 
-    fn event_loop(&mut self) -> Result<()> {
+    fn event_loop(&mut self, terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, signals: &Signals) -> Result<()> {
         loop {
-            let self_ptr: *mut App = self;
-            self.terminal.draw(|f| {
-                let app: &mut App = unsafe { &mut *self_ptr };
-                crate::ui::draw(f, app);
-            })?;
+            terminal.draw(|f| crate::ui::draw(f, self))?;
+
+            if let Some(signal) = signals.take()
+                && self.handle_signal(signal)?
+            {
+                break;
+            }
+
+            if self.git_section.tick() {
+                if let Some(e) = self.git_section.load_error.take() {
+                    self.report_error(e.context("Loading commits"));
+                } else if let Some(outcome) = self.git_section.last_action.take() {
+                    match outcome.error {
+                        Some(e) => self.report_error(anyhow::anyhow!("{} failed: {}", outcome.label, e)),
+                        None => self.status_message = Some(format!("{} done, commits refreshed", outcome.label)),
+                    }
+                    // Pulls and stashes add and remove notes.
+                    if matches!(outcome.label, "Pull" | "Stash" | "Unstash") {
+                        let root = self.notes_dir.clone();
+                        self.rescan_sidebar_dir(&root);
+                    }
+                } else if self.git_section.commits.is_empty() {
+                    self.status_message = Some(self.git_section.empty_reason());
+                }
+            }
+
+            self.finish_code_block();
+
+            if let Some(job) = self.git_section.foreground.take() {
+                Self::release_terminal(terminal, &format!("{}: signing; answer any passphrase prompt below.", job.label))?;
+                self.git_section.run_foreground(job);
+                Self::reclaim_terminal(terminal)?;
+            }
+
+            if self.last_disk_check.elapsed() >= DISK_CHECK_INTERVAL && self.modal.is_none() {
+                self.last_disk_check = Instant::now();
+                if let Err(e) = self.check_disk_changes() {
+                    self.report_error(e);
+                }
+            }
 
             if event::poll(std::time::Duration::from_millis(200))? {
                 match event::read()? {
-                    Event::Key(k) => {
-                        if self.handle_key(k)? {
-                            break;
+                    Event::Key(k)
+                        if cfg!(unix) && k.code == KeyCode::Char('z') && k.modifiers == KeyModifiers::CONTROL =>
+                    {
+                        Self::suspend(terminal)?;
+                    }
+                    Event::Key(k) => match self.handle_key(k) {
+                        Ok(true) => break,
+                        Ok(false) => {}
+                        Err(e) => self.report_error(e),
+                    },
+                    Event::Paste(text) => {
+                        self.handle_paste(&text);
+                    }
+                    Event::Mouse(m) => {
+                        if let Err(e) = self.handle_mouse(m) {
+                            self.report_error(e);
                         }
                     }
                     Event::Resize(_, _) => {
@@ -155,54 +608,187 @@ impl App {
                     _ => {}
                 }
             }
+            if self.quitting {
+                break;
+            }
+            }
+
+        self.save_scratch();
+        Ok(())
+    }
+
+    /// Shows `err` in the status bar and keeps its cause chain for the
+    /// details popup (Alt+E).
+    pub fn report_error(&mut self, err: anyhow::Error) {
+        log!(Level::Error, "error", "{:#}", err);
+        let summary = format!("{:#}", err);
+        self.status_message = Some(summary.clone());
+        if self.errors.len() == MAX_ERRORS {
+            self.errors.remove(0);
+        }
+        self.errors.push(ErrorReport { summary, detail: format!("{:?}", err) });
+        self.unseen_errors += 1;
+    }
+
+    /// Returns true when the app should exit right away.
+    fn handle_signal(&mut self, signal: Signal) -> Result<bool> {
+        self.leave_scratch();
+        match signal {
+            Signal::Hangup => {
+                if self.dirty {
+                    self.save_current()?;
+                }
+                Ok(true)
+            }
+            // A second SIGTERM while the prompt is up means "now": save what
+            // can be saved rather than dropping it.
+            Signal::Terminate if matches!(self.modal, Some(Modal::ConfirmQuit)) => {
+                self.save_current()?;
+                Ok(true)
             }
+            Signal::Terminate => Ok(self.request_quit()),
+        }
+    }
+
+    /// Quits at once when there is nothing to lose, otherwise asks whether
+    /// to save first.
+    fn request_quit(&mut self) -> bool {
+        self.leave_scratch();
+        if !self.dirty || self.read_only {
+            return true;
+        }
+        self.modal = Some(Modal::ConfirmQuit);
+        false
+    }
+
+    /// Hands the terminal back to the shell and stops until `fg`.
+    fn suspend(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
+        Self::release_terminal(terminal, "")?;
+        signals::suspend()?;
+        Self::reclaim_terminal(terminal)
+    }
+
+    /// Leaves the TUI so another program can use the terminal, printing
+    /// `note` (if any) on the restored screen.
+    fn release_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, note: &str) -> Result<()> {
+        disable_raw_mode()?;
+        execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture, DisableBracketedPaste)?;
+        terminal.show_cursor()?;
+        if !note.is_empty() {
+            println!("{}", note);
+        }
+        Ok(())
+    }
 
+    fn reclaim_terminal(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
+        enable_raw_mode()?;
+        execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
+        terminal.clear()?;
         Ok(())
     }
     fn handle_key(&mut self, key: KeyEvent) -> Result<bool> {
+        // Keys typed into a passphrase prompt stay out of the log and macros.
+        let secret = matches!(self.modal, Some(Modal::Passphrase { .. }));
+        if secret {
+            log!(Level::Debug, "key", "(passphrase input) focus={:?}", self.focus);
+        } else {
+            log!(Level::Debug, "key", "code={:?} mods={:?} focus={:?} modal={}", key.code, key.modifiers, self.focus, self.modal.is_some());
+        }
+        let macro_toggle = self.modal.is_none() && key.modifiers == KeyModifiers::ALT && key.code == KeyCode::Char('q');
+        if !self.replaying
+            && !macro_toggle
+            && !secret
+            && let Some((_, keys)) = &mut self.recording
+        {
+            keys.push(key);
+        }
         if self.modal.is_some() {
             self.handle_modal_key(key)?;
             return Ok(false);
         }
 
-        if key.modifiers.is_empty() {
+        // Bare letters and digits are text while Title or Content has focus
+        // or a name is being typed in the sidebar, and digits are motion
+        // counts there; Alt+digit and Ctrl+Q keep pane switching and quitting
+        // reachable.
+        let typing = matches!(self.focus, Focus::Title | Focus::Content) || self.typing_ahead();
+        let counting = matches!(self.focus, Focus::Sidebar) && matches!(key.code, KeyCode::Char('0'..='9'));
+        let shortcut = key.modifiers.is_empty() && !typing && !counting;
+        let alt = key.modifiers == KeyModifiers::ALT;
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        if shortcut || alt {
             match key.code {
                 KeyCode::Char('1') => { self.focus = Focus::Sidebar; return Ok(false); }
                 KeyCode::Char('2') => { self.focus = Focus::Title; return Ok(false); }
                 KeyCode::Char('3') => { self.focus = Focus::Content; return Ok(false); }
                 KeyCode::Char('4') => { self.focus = Focus::Commits; return Ok(false); }
+                KeyCode::Char('5') => { self.focus = Focus::ChangedFiles; return Ok(false); }
                 _ => {}
             }
         }
+        if alt && key.code == KeyCode::Char('r') {
+            self.reveal_in_sidebar();
+            return Ok(false);
+        }
+        if macro_toggle {
+            self.toggle_macro_recording();
+            return Ok(false);
+        }
+        if alt && key.code == KeyCode::Char('h') {
+            self.open_history()?;
+            return Ok(false);
+        }
+        if alt && key.code == KeyCode::Char('e') {
+            self.show_errors();
+            return Ok(false);
+        }
+        if alt && key.code == KeyCode::Char('s') {
+            self.toggle_scratch();
+            return Ok(false);
+        }
+        if alt && key.code == KeyCode::Char('i') {
+            self.open_inbox()?;
+            return Ok(false);
+        }
+        if key.code == KeyCode::Char('@') && key.modifiers.contains(KeyModifiers::ALT) {
+            self.modal = Some(Modal::MacroRegister { record: false, count: String::new() });
+            return Ok(false);
+        }
+        if key.code == KeyCode::Char('g') && ctrl {
+            self.modal = Some(Modal::Menu { menu: &menu::ROOT });
+            return Ok(false);
+        }
 
-        if key.code == KeyCode::Char('q') && key.modifiers.is_empty() {
+        if key.code == KeyCode::Char('q') && (shortcut || ctrl) {
             return Ok(true);
         }
+        // Raw mode turns Ctrl+C into a key; outside Content (where it copies)
+        // it behaves like SIGINT.
+        if key.code == KeyCode::Char('c') && ctrl && self.focus != Focus::Content {
+            return Ok(self.request_quit());
+        }
 
-        if key.code == KeyCode::Char('s') && key.modifiers.contains(KeyModifiers::CONTROL) {
+        if key.code == KeyCode::Char('s') && ctrl {
             self.save_current()?;
             return Ok(false);
         }
-        if key.code == KeyCode::Char('n') && key.modifiers.is_empty() {
-            let mut target = self.notes_dir.clone();
-            if matches!(self.focus, Focus::Sidebar) {
-                if let Some(sel) = self.sidebar_state.selected() {
-                    if sel < self.sidebar_items.len() {
-                        let it = &self.sidebar_items[sel];
-                        if it.is_dir {
-                            target = it.path.clone();
-                        } else if let Some(parent) = it.path.parent() {
-                            target = parent.to_path_buf();
-                        }
-                    }
-                }
-            }
-            self.modal = Some(Modal::InputName { current: String::new(), target_dir: target });
+        if key.code == KeyCode::Char('n') && shortcut {
+            self.prompt_new_note();
             return Ok(false);
         }
 
-        if key.modifiers.is_empty() {
+        // The sidebar uses `g` for `gg`; Ctrl+G reaches both menus from there.
+        if shortcut {
             match key.code {
+                KeyCode::Char('g') if self.focus != Focus::Sidebar => {
+                    self.modal = Some(Modal::Menu { menu: &menu::GIT });
+                    return Ok(false);
+                }
+                KeyCode::Char('f') if self.focus != Focus::Sidebar => {
+                    self.modal = Some(Modal::Menu { menu: &menu::FILE });
+                    return Ok(false);
+                }
                 KeyCode::Char('h') => {
                     self.focus = Focus::Sidebar;
                 }
@@ -215,29 +801,23 @@ impl App {
                 _ => {}
             }
         }
-        
-        if key.code == KeyCode::Tab {
-            self.focus = match self.focus {
-                Focus::Sidebar => {
-                    self.last_right_focus = RightFocus::Title;
-                    Focus::Title
-                }
-                Focus::Title => {
-                    self.last_right_focus = RightFocus::Content;
-                    Focus::Content
-                }
-                Focus::Content => Focus::Commits,
-                Focus::Commits => Focus::Sidebar,
-            };
+
+        // Ctrl+Space always cycles; plain Tab does too unless Content wants it
+        // for indentation.
+        let tab_cycles = key.code == KeyCode::Tab
+            && !(self.focus == Focus::Content && self.tab_mode != TabMode::Focus);
+        if tab_cycles || (key.code == KeyCode::Char(' ') && ctrl) {
+            self.cycle_focus();
             return Ok(false);
         }
-        
+
         if !matches!(self.focus, Focus::Content) {
             match key.code {
                 KeyCode::Up => {
                     match self.focus {
                         Focus::Sidebar => { self.handle_sidebar_key(key)?; return Ok(false); }
                         Focus::Commits => { self.git_section.select_prev(); return Ok(false); }
+                        Focus::ChangedFiles => { self.git_section.file_selected = self.git_section.file_selected.saturating_sub(1); return Ok(false); }
                         _ => {}
                     }
                 }
@@ -245,15 +825,17 @@ impl App {
                     match self.focus {
                         Focus::Sidebar => { self.handle_sidebar_key(key)?; return Ok(false); }
                         Focus::Commits => { self.git_section.select_next(); return Ok(false); }
+                        Focus::ChangedFiles => {
+                            let last = self.git_section.selected_changed_files().len().saturating_sub(1);
+                            self.git_section.file_selected = (self.git_section.file_selected + 1).min(last);
+                            return Ok(false);
+                        }
                         _ => {}
                     }
                 }
-                KeyCode::Left => {
-                    
-                    if matches!(self.focus, Focus::Commits) || matches!(self.focus, Focus::Title) {
-                        self.focus = Focus::Sidebar;
-                        return Ok(false);
-                    }
+                KeyCode::Left if matches!(self.focus, Focus::Commits | Focus::ChangedFiles | Focus::Title) => {
+                    self.focus = Focus::Sidebar;
+                    return Ok(false);
                 }
                 KeyCode::Right => {
                     
@@ -262,7 +844,7 @@ impl App {
                         self.sidebar_enter_action(sel)?;
                         return Ok(false);
                     }
-                    if matches!(self.focus, Focus::Commits) {
+                    if matches!(self.focus, Focus::Commits | Focus::ChangedFiles) {
                         self.focus = match self.last_right_focus {
                             RightFocus::Title => Focus::Title,
                             RightFocus::Content => Focus::Content,
@@ -276,66 +858,549 @@ impl App {
 
         match self.focus {
             Focus::Sidebar => self.handle_sidebar_key(key)?,
-            Focus::Title => self.handle_title_key(key)?,
-            Focus::Content => self.handle_content_key(key)?,
+            Focus::Title => {
+                let before = self.title.clone();
+                self.handle_title_key(key)?;
+                if self.title != before {
+                    self.title_to_heading();
+                }
+            }
+            Focus::Content => {
+                self.handle_content_key(key)?;
+                self.heading_to_title();
+            }
             Focus::Commits => self.handle_commits_key(key)?,
+            Focus::ChangedFiles => self.handle_changed_files_key(key)?,
         }
         Ok(false)
     }
 
+    /// Opens the new-note prompt targeting the folder selected in the
+    /// sidebar, or the vault root.
+    fn prompt_new_note(&mut self) {
+        let mut target = self.notes_dir.clone();
+        if matches!(self.focus, Focus::Sidebar)
+            && let Some(sel) = self.sidebar_state.selected()
+            && sel < self.sidebar_items.len()
+        {
+            let it = &self.sidebar_items[sel];
+            if it.is_dir {
+                target = it.path.clone();
+            } else if let Some(parent) = it.path.parent() {
+                target = parent.to_path_buf();
+            }
+        }
+        self.leave_scratch();
+        self.modal = Some(Modal::InputName { current: String::new(), target_dir: target });
+    }
+
+    fn run_menu_action(&mut self, action: MenuAction) -> Result<()> {
+        match action {
+            MenuAction::Submenu(menu) => self.modal = Some(Modal::Menu { menu }),
+            MenuAction::Commit => self.start_commit(),
+            MenuAction::Amend => self.start_amend()?,
+            MenuAction::Push => {
+                self.git_section.push();
+                self.status_message = Some("Pushing...".into());
+            }
+            MenuAction::Pull => {
+                self.git_section.pull();
+                self.status_message = Some("Pulling...".into());
+            }
+            MenuAction::Fetch => {
+                self.git_section.fetch_and_refresh();
+                self.status_message = Some("Fetching...".into());
+            }
+            MenuAction::NewNote => self.prompt_new_note(),
+            MenuAction::Save => self.save_current()?,
+            MenuAction::Rename => {
+                self.focus = Focus::Title;
+                self.last_right_focus = RightFocus::Title;
+                self.status_message = Some("Edit the title and save to rename".into());
+            }
+            MenuAction::Delete => {
+                let path = match self.focus {
+                    Focus::Sidebar => self
+                        .sidebar_state
+                        .selected()
+                        .and_then(|i| self.sidebar_items.get(i))
+                        .filter(|it| !it.is_dir)
+                        .map(|it| it.path.clone()),
+                    _ => self.opened_path.clone(),
+                };
+                match path {
+                    Some(path) => self.modal = Some(Modal::ConfirmDelete { path }),
+                    None => self.status_message = Some("No note to delete".into()),
+                }
+            }
+            MenuAction::Reveal => self.reveal_in_sidebar(),
+            MenuAction::SearchVault => {
+                self.open_vault_search(String::new());
+            }
+            MenuAction::ShowLog => self.modal = Some(Modal::Log { scroll: 0 }),
+            MenuAction::ShowErrors => self.show_errors(),
+            MenuAction::History => self.open_history()?,
+            MenuAction::LocalHistory => self.open_local_history(),
+            MenuAction::DiffSaved => self.open_diff(DiffBase::Saved),
+            MenuAction::DiffCommitted => self.open_diff(DiffBase::Committed),
+            MenuAction::Scratch => self.toggle_scratch(),
+            MenuAction::SortLines => self.transform_lines("Sorted", |lines| lines.sort_by_key(|l| l.to_lowercase())),
+            MenuAction::UniqueLines => self.transform_lines("Deduplicated", |lines| {
+                let mut seen = HashSet::new();
+                lines.retain(|l| seen.insert(l.clone()));
+            }),
+            MenuAction::ReverseLines => self.transform_lines("Reversed", |lines| lines.reverse()),
+            MenuAction::Reflow => self.reflow(),
+            MenuAction::Case(case) => self.convert_case(case),
+            MenuAction::InsertDate => self.prompt_insert_date(),
+            MenuAction::ReplaceInVault => self.open_replace()?,
+            MenuAction::Format => {
+                self.format_note()?;
+                self.status_message = Some("Formatted".into());
+            }
+            MenuAction::LinkReport => self.open_link_report(),
+            MenuAction::Remotes => self.open_remotes()?,
+            MenuAction::Stash => {
+                if self.dirty && !self.read_only {
+                    self.save_current()?;
+                }
+                self.git_section.stash();
+                self.status_message = Some("Stashing...".into());
+            }
+            MenuAction::Stashes => {
+                let stashes = self.git_section.stashes()?;
+                if stashes.is_empty() {
+                    self.status_message = Some("No stashed changes".into());
+                } else {
+                    self.modal = Some(Modal::Stashes { stashes, selected: 0 });
+                }
+            }
+            MenuAction::ExtractFromCursor => self.prompt_extract_from_cursor(),
+            MenuAction::ExtractSelection => self.prompt_extract_selection(),
+            MenuAction::Attachments => self.open_attachments(false),
+            MenuAction::OrphanAttachments => self.open_attachments(true),
+            MenuAction::ImportFile => {
+                self.modal = Some(Modal::ImportFile { input: String::new(), candidates: Vec::new(), to_assets: true });
+            }
+            MenuAction::NoteInfo => self.modal = Some(Modal::NoteInfo(NoteStats::of(&self.lines))),
+            MenuAction::CopyCodeBlock => match markdown::code_block_at(&self.lines, self.cursor_row) {
+                Some(block) => {
+                    let code = self.lines[block.body()].join("\n");
+                    self.copy_to_clipboard(&code);
+                }
+                None => self.status_message = Some("The cursor is not in a code block".into()),
+            },
+            MenuAction::RunCodeBlock => self.run_code_block()?,
+            MenuAction::OpenDiagram => match markdown::code_block_at(&self.lines, self.cursor_row) {
+                Some(block) if block.lang == diagram::MERMAID => {
+                    let image = diagram::render(&self.lines[block.body()])?;
+                    attachments::open_externally(&image, None)?;
+                    self.status_message = Some("Opened the rendered diagram".into());
+                }
+                _ => self.status_message = Some("The cursor is not in a mermaid block".into()),
+            },
+            MenuAction::CopyHtml => {
+                let markdown = self.selected_text().unwrap_or_else(|| self.lines.join("\n"));
+                match self.clipboard.copy_html(&html::to_html(&markdown)) {
+                    Ok(target) => self.status_message = Some(format!("Copied as HTML to {}", target)),
+                    Err(e) => self.report_error(e.context("Copy failed")),
+                }
+            }
+            MenuAction::RecentlyDeleted => {
+                if self.deleted.is_empty() {
+                    self.status_message = Some("Nothing deleted this session".into());
+                } else {
+                    self.modal = Some(Modal::Deleted { selected: 0 });
+                }
+            }
+            MenuAction::Present => {
+                let slides = markdown::slides(&self.lines);
+                if slides.is_empty() {
+                    self.status_message = Some("Nothing to present".into());
+                } else {
+                    // Start at the slide the cursor is on.
+                    let index = slides.iter().rposition(|&(start, _)| start <= self.cursor_row).unwrap_or(0);
+                    self.modal = Some(Modal::Present { slides, index });
+                }
+            }
+            MenuAction::Stats => {
+                self.modal = Some(Modal::Stats(Stats::collect(&self.git_section, &collect_files(&self.notes_tree))));
+            }
+        }
+        Ok(())
+    }
+
+    fn open_history(&mut self) -> Result<()> {
+        let Some(path) = self.opened_path.clone() else {
+            self.status_message = Some("Open a saved note to browse its history".into());
+            return Ok(());
+        };
+        let has_local = self.local_history.as_ref().is_some_and(|h| !h.list(&path).is_empty());
+        let revisions = match self.git_section.file_history(&path) {
+            Ok(revisions) if !revisions.is_empty() => revisions,
+            // Without git (or commits), fall back to the local snapshots.
+            _ if has_local => {
+                self.open_local_history();
+                return Ok(());
+            }
+            result => result?,
+        };
+        if revisions.is_empty() {
+            self.status_message = Some("This note has no committed history".into());
+            return Ok(());
+        }
+        let lines = Self::revision_lines(&self.git_section, &revisions[0])?;
+        self.modal = Some(Modal::History { revisions, selected: 0, lines, scroll: 0 });
+        Ok(())
+    }
+
+    fn open_diff(&mut self, base: DiffBase) {
+        let Some(path) = self.opened_path.clone() else {
+            self.status_message = Some("Not saved yet; everything is new".into());
+            return;
+        };
+        let old = match base {
+            DiffBase::Saved => read_note(&path),
+            DiffBase::Committed => {
+                let rel = relative_to(&path, &self.notes_dir).to_string_lossy().replace('\\', "/");
+                self.git_section.show_file("HEAD", &format!("./{}", rel))
+            }
+        };
+        let old = match old {
+            Ok(content) => split_lines_preserve(&content),
+            Err(e) => {
+                self.status_message = Some(format!("Nothing to compare with: {:#}", e));
+                return;
+            }
+        };
+        let old = if old.is_empty() { vec![String::new()] } else { old };
+        let rows = diff::diff_lines(&old, &self.lines);
+        if rows.iter().all(|r| matches!(r, DiffLine::Same(_))) {
+            self.status_message = Some(match base {
+                DiffBase::Saved => "No unsaved changes".into(),
+                DiffBase::Committed => "No changes since the last commit".into(),
+            });
+            return;
+        }
+        self.modal = Some(Modal::Diff { base, rows: diff::with_context(rows, 3), scroll: 0 });
+    }
+
+    fn open_local_history(&mut self) {
+        let Some(path) = self.opened_path.clone() else {
+            self.status_message = Some("Open a saved note to browse its history".into());
+            return;
+        };
+        let Some(history) = &self.local_history else {
+            self.status_message = Some("Local history is off (see local_history)".into());
+            return;
+        };
+        let snapshots = history.list(&path);
+        let Some(newest) = snapshots.first() else {
+            self.status_message = Some("No local snapshots of this note yet".into());
+            return;
+        };
+        let lines = Self::snapshot_lines(newest);
+        self.modal = Some(Modal::LocalHistory { snapshots, selected: 0, lines, scroll: 0 });
+    }
+
+    fn snapshot_lines(snapshot: &Snapshot) -> Vec<String> {
+        match snapshot.read() {
+            Ok(content) => split_lines_preserve(&content),
+            Err(e) => vec![format!("({:#})", e)],
+        }
+    }
+
+    fn revision_lines(git: &GitSection, rev: &CommitInfo) -> Result<Vec<String>> {
+        let path = rev.changed_files.first().map(|f| f.path.as_str()).unwrap_or_default();
+        Ok(split_lines_preserve(&git.show_file(&rev.hash, path)?))
+    }
+
+    /// When `commit` was authored, relative or absolute per `absolute_dates`.
+    pub fn commit_date(&self, commit: &CommitInfo) -> String {
+        let now = datefmt::now();
+        let Ok(time) = time::OffsetDateTime::from_unix_timestamp(commit.time) else { return String::new() };
+        if self.absolute_dates {
+            datefmt::strftime(time.to_offset(now.offset()), &self.commit_date_format)
+        } else {
+            datefmt::relative(time, now, &self.date_language)
+        }
+    }
+
+    /// How long ago `file` was deleted.
+    pub fn deleted_ago(&self, file: &DeletedFile) -> String {
+        datefmt::relative(file.at, datefmt::now(), &self.date_language)
+    }
+
+    /// Asks for the commit message, as parts of `commit_template` when one
+    /// is configured.
+    fn start_commit(&mut self) {
+        self.modal = Some(match &self.commit_template {
+            Some(_) => {
+                let kind = self.commit_types.first().cloned().unwrap_or_default();
+                Modal::CommitForm { fields: [kind, String::new(), String::new()], field: 2 }
+            }
+            None => Modal::CommitMessage { message: String::new(), amend: false },
+        });
+    }
+
+    fn start_amend(&mut self) -> Result<()> {
+        let message = self.git_section.head_message()?;
+        self.modal = Some(Modal::CommitMessage { message, amend: true });
+        Ok(())
+    }
+
+    fn show_errors(&mut self) {
+        if self.errors.is_empty() {
+            self.status_message = Some("No errors this session".into());
+        } else {
+            self.unseen_errors = 0;
+            self.modal = Some(Modal::Errors);
+        }
+    }
+
+    fn cycle_focus(&mut self) {
+        self.focus = match self.focus {
+            Focus::Sidebar => {
+                self.last_right_focus = RightFocus::Title;
+                Focus::Title
+            }
+            Focus::Title => {
+                self.last_right_focus = RightFocus::Content;
+                Focus::Content
+            }
+            Focus::Content => Focus::Commits,
+            Focus::Commits => Focus::ChangedFiles,
+            Focus::ChangedFiles => Focus::Sidebar,
+        };
+    }
+
     fn handle_sidebar_key(&mut self, key: KeyEvent) -> Result<()> {
-        let len = self.sidebar_items.len();
+        // Saved searches are listed after the tree.
+        let len = self.sidebar_items.len() + self.searches.saved.len();
         let selected = self.sidebar_state.selected().unwrap_or(0);
 
+        // After `s`, every key that could be part of a name extends it until
+        // the pause runs out; the first one may take as long as it likes.
+        let typing = self.typeahead.take().filter(|(prefix, at)| prefix.is_empty() || at.elapsed() < TYPEAHEAD_TIMEOUT);
+        if let Some((mut prefix, _)) = typing
+            && let KeyCode::Char(c) = key.code
+            && !c.is_whitespace()
+            && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            // A fresh name starts looking below the selection.
+            let from = if prefix.is_empty() { (selected + 1) % self.sidebar_items.len().max(1) } else { selected };
+            prefix.push(c);
+            self.type_ahead(prefix, from);
+            return Ok(());
+        }
+
+        // A count prefix (`5` then Down) repeats the motion after it.
+        if let KeyCode::Char(c @ '0'..='9') = key.code
+            && (c != '0' || self.sidebar_count.is_some())
+            && key.modifiers.is_empty()
+        {
+            let digit = c as usize - '0' as usize;
+            self.sidebar_count = Some(self.sidebar_count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            return Ok(());
+        }
+        let count = self.sidebar_count.take();
+        let pending_g = std::mem::take(&mut self.sidebar_pending_g);
+        let times = count.unwrap_or(1);
+
         match key.code {
-            KeyCode::Up => {
-                if len > 0 {
-                    let new = selected.saturating_sub(1);
-                    self.sidebar_state.select(Some(new));
-                }
+            KeyCode::Up if len > 0 => {
+                let new = selected.saturating_sub(times);
+                self.sidebar_state.select(Some(new));
             }
-            KeyCode::Down => {
-                if len > 0 {
-                    let new = (selected + 1).min(len - 1);
-                    self.sidebar_state.select(Some(new));
+            KeyCode::Down if len > 0 => {
+                let new = (selected + times).min(len - 1);
+                self.sidebar_state.select(Some(new));
+            }
+            KeyCode::Char('g') if pending_g && len > 0 => self.sidebar_state.select(Some(0)),
+            KeyCode::Char('g') => self.sidebar_pending_g = true,
+            // `G` goes to the bottom, `5G` to the fifth entry.
+            KeyCode::Char('G') if len > 0 => {
+                self.sidebar_state.select(Some(count.map_or(len - 1, |n| n.saturating_sub(1).min(len - 1))));
+            }
+            KeyCode::Char(c @ ('{' | '}')) if selected < self.sidebar_items.len() => {
+                let mut at = selected;
+                for _ in 0..times {
+                    match self.sibling_dir(at, c == '}') {
+                        Some(next) => at = next,
+                        None => break,
+                    }
                 }
+                self.sidebar_state.select(Some(at));
             }
             KeyCode::Enter => {
                 self.sidebar_enter_action(selected)?;
             }
-            KeyCode::Char(' ') => {
-                self.sidebar_toggle_dir(selected)?;
+            // `-` folds everything up to the top level, `+` unfolds the
+            // whole vault and `*` the folder under the selection.
+            KeyCode::Char('-') => {
+                // Keep the selection on the top-level entry it was under.
+                let top = self.sidebar_items.iter().take(selected + 1).rev().find(|it| it.depth == 0).map(|it| it.path.clone());
+                self.expanded_dirs.retain(|p| *p == self.notes_dir);
+                self.reflatten_sidebar();
+                if let Some(i) = top.and_then(|top| self.sidebar_items.iter().position(|it| it.path == top)) {
+                    self.sidebar_state.select(Some(i));
+                }
+            }
+            KeyCode::Char('+') => {
+                self.expanded_dirs.extend(collect_dirs(&self.notes_tree));
+                self.reflatten_sidebar();
+            }
+            KeyCode::Char('*') if selected < self.sidebar_items.len() && self.sidebar_items[selected].is_dir => {
+                let dirs = find_dir(&self.notes_tree, &self.sidebar_items[selected].path).map(collect_dirs).unwrap_or_default();
+                self.expanded_dirs.extend(dirs);
+                self.reflatten_sidebar();
+            }
+            KeyCode::Char(' ') => match self.sidebar_items.get(selected) {
+                Some(it) if !it.is_dir => {
+                    if !self.marked.remove(&it.path) {
+                        self.marked.insert(it.path.clone());
+                    }
+                    self.sidebar_state.select(Some((selected + 1).min(len - 1)));
+                }
+                _ => self.sidebar_toggle_dir(selected)?,
+            },
+            KeyCode::Esc if !self.marked.is_empty() => self.marked.clear(),
+            KeyCode::Char(c @ ('d' | 'm' | 't' | 'a')) if !self.marked.is_empty() => {
+                let action = match c {
+                    'd' => BatchAction::Delete,
+                    'm' => BatchAction::Move,
+                    't' => BatchAction::Tag,
+                    _ => BatchAction::Archive,
+                };
+                let confirming = matches!(action, BatchAction::Delete | BatchAction::Archive);
+                self.modal = Some(Modal::Batch { action, input: String::new(), confirming });
             }
             KeyCode::Right => {
                 self.sidebar_enter_action(selected)?;
             }
-            KeyCode::Char('d') => {
-                if selected < self.sidebar_items.len() {
-                    let it = &self.sidebar_items[selected];
-                    if !it.is_dir {
-                        self.modal = Some(Modal::ConfirmDelete { path: it.path.clone() });
+            KeyCode::Char('.') => self.reveal_in_sidebar(),
+            KeyCode::Char('p') => {
+                self.preview = !self.preview;
+                self.preview_cache = None;
+            }
+            KeyCode::Char(c @ ('y' | 'Y')) if selected < self.sidebar_items.len() && !self.sidebar_items[selected].is_dir => {
+                let path = self.sidebar_items[selected].path.clone();
+                // Wikilinks only resolve to notes; anything else gets a
+                // Markdown link relative to the open note.
+                let link = if c == 'y' && links::is_note(&path) {
+                    format!("[[{}]]", path.file_stem().and_then(|s| s.to_str()).unwrap_or_default())
+                } else {
+                    let dir = self.opened_path.as_deref().and_then(Path::parent).unwrap_or(&self.notes_dir);
+                    attachments::link_to(&path, dir)
+                };
+                self.copy_to_clipboard(&link);
+                self.status_message = Some(format!("Copied {} (Ctrl+V pastes it)", link));
+            }
+            KeyCode::Char('u') => match self.deleted.len() {
+                0 => self.status_message = Some("Nothing to undo".into()),
+                n => self.restore_deleted(n - 1)?,
+            },
+            KeyCode::Char('/') => {
+                self.open_vault_search(String::new());
+            }
+            KeyCode::Char('d') if selected < self.sidebar_items.len() => {
+                let it = &self.sidebar_items[selected];
+                if !it.is_dir {
+                    self.modal = Some(Modal::ConfirmDelete { path: it.path.clone() });
+                }
+            }
+            KeyCode::Char('d') if selected < len => {
+                let name = self.searches.saved.keys().nth(selected - self.sidebar_items.len()).cloned();
+                if let Some(name) = name {
+                    self.searches.saved.remove(&name);
+                    if let Err(e) = self.searches.save() {
+                        self.report_error(e);
                     }
+                    self.sidebar_state.select(len.checked_sub(2).map(|last| selected.min(last)));
+                    self.status_message = Some(format!("Removed saved search '{}'", name));
                 }
             }
+            // `s` (seek) jumps to an entry by typing its name. It gets its
+            // own key because bare letters are already sidebar and global
+            // shortcuts (`q` quits, `n` makes a note, `gg` goes to the top).
+            KeyCode::Char('s') => {
+                self.status_message = Some("Jump: ".into());
+                self.typeahead = Some((String::new(), Instant::now()));
+            }
             _ => {}
         }
 
         Ok(())
     }
 
+    fn typing_ahead(&self) -> bool {
+        self.focus == Focus::Sidebar
+            && self.typeahead.as_ref().is_some_and(|(prefix, at)| prefix.is_empty() || at.elapsed() < TYPEAHEAD_TIMEOUT)
+    }
+
+    /// Selects the first sidebar entry from `from` on, wrapping around,
+    /// whose name starts with `prefix` (ignoring case), and keeps `prefix`
+    /// for the next key.
+    fn type_ahead(&mut self, prefix: String, from: usize) {
+        let needle = prefix.to_lowercase();
+        let n = self.sidebar_items.len();
+        if let Some(i) = (0..n).map(|k| (from + k) % n).find(|&i| self.sidebar_items[i].name.to_lowercase().starts_with(&needle)) {
+            self.sidebar_state.select(Some(i));
+        }
+        self.status_message = Some(format!("Jump: {}", prefix));
+        self.typeahead = Some((prefix, Instant::now()));
+    }
+
+    /// The next (or previous) folder at the depth of sidebar entry `from`
+    /// within the same parent.
+    fn sibling_dir(&self, from: usize, forward: bool) -> Option<usize> {
+        let depth = self.sidebar_items[from].depth;
+        let candidates: Box<dyn Iterator<Item = usize>> =
+            if forward { Box::new(from + 1..self.sidebar_items.len()) } else { Box::new((0..from).rev()) };
+        for i in candidates {
+            let it = &self.sidebar_items[i];
+            if it.depth < depth {
+                return None;
+            }
+            if it.depth == depth && it.is_dir {
+                return Some(i);
+            }
+        }
+        None
+    }
+
     fn sidebar_enter_action(&mut self, idx: usize) -> Result<()> {
         if idx >= self.sidebar_items.len() {
+            let query = self.searches.saved.values().nth(idx - self.sidebar_items.len()).cloned();
+            if let Some(query) = query {
+                self.open_vault_search(query);
+            }
             return Ok(());
         }
         if self.sidebar_items[idx].is_dir {
             self.sidebar_toggle_dir(idx)?;
         } else {
             let path = self.sidebar_items[idx].path.clone();
-            self.open_file(&path)?;
+            if self.opener(&path).is_some() || !is_text_file(&path) {
+                self.open_externally(&path)?;
+                self.status_message = Some(format!("Opened {} externally", path.file_name().unwrap_or_default().to_string_lossy()));
+            } else {
+                self.open_file(&path, None)?;
+            }
         }
         Ok(())
     }
 
+    /// The `open_with` command configured for the extension of `path`.
+    fn opener(&self, path: &Path) -> Option<&str> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        self.open_with.get(&ext).map(String::as_str)
+    }
+
+    fn open_externally(&self, path: &Path) -> Result<()> {
+        attachments::open_externally(path, self.opener(path))
+    }
+
 
     fn sidebar_toggle_dir(&mut self, idx: usize) -> Result<()> {
         if idx >= self.sidebar_items.len() {
@@ -348,26 +1413,21 @@ impl App {
         let was_expanded = self.expanded_dirs.contains(&item.path);
         if was_expanded {
             self.expanded_dirs.remove(&item.path);
-            self.refresh_sidebar_preserve_selection(Some(idx));
         } else {
             self.expanded_dirs.insert(item.path.clone());
-            self.refresh_sidebar_preserve_selection(Some(idx));
         }
+        self.reflatten_sidebar();
         Ok(())
     }
 
     fn handle_title_key(&mut self, key: KeyEvent) -> Result<()> {
         self.last_right_focus = RightFocus::Title;
         match key.code {
-            KeyCode::Left => {
-                if self.title_cursor > 0 {
-                    self.title_cursor -= 1;
-                }
+            KeyCode::Left if self.title_cursor > 0 => {
+                self.title_cursor = prev_char_boundary(&self.title, self.title_cursor);
             }
-            KeyCode::Right => {
-                if self.title_cursor < self.title.len() {
-                    self.title_cursor += 1;
-                }
+            KeyCode::Right if self.title_cursor < self.title.len() => {
+                self.title_cursor = next_char_boundary(&self.title, self.title_cursor);
             }
             KeyCode::Home => {
                 self.title_cursor = 0;
@@ -375,25 +1435,23 @@ impl App {
             KeyCode::End => {
                 self.title_cursor = self.title.len();
             }
-            KeyCode::Backspace => {
-                if self.title_cursor > 0 {
-                    self.title.remove(self.title_cursor - 1);
-                    self.title_cursor -= 1;
-                    self.dirty = true;
-                }
+            KeyCode::Backspace if self.title_cursor > 0 => {
+                self.title_cursor = prev_char_boundary(&self.title, self.title_cursor);
+                self.title.remove(self.title_cursor);
+                self.dirty = true;
             }
-            KeyCode::Delete => {
-                if self.title_cursor < self.title.len() {
-                    self.title.remove(self.title_cursor);
-                    self.dirty = true;
-                }
+            KeyCode::Delete if self.title_cursor < self.title.len() => {
+                self.title.remove(self.title_cursor);
+                self.dirty = true;
             }
-            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
-                if c != '/' && c != '\\' && c != '.' && c != '\n' && c != '\r' {
-                    self.title.insert(self.title_cursor, c);
-                    self.title_cursor += 1;
-                    self.dirty = true;
-                }
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => self.reveal_in_sidebar(),
+            KeyCode::Char(c)
+                if !key.modifiers.contains(KeyModifiers::CONTROL)
+                    && c != '/' && c != '\\' && c != '.' && c != '\n' && c != '\r' =>
+            {
+                self.title.insert(self.title_cursor, c);
+                self.title_cursor += c.len_utf8();
+                self.dirty = true;
             }
             _ => {}
         }
@@ -402,34 +1460,81 @@ impl App {
 
     fn handle_content_key(&mut self, key: KeyEvent) -> Result<()> {
         self.last_right_focus = RightFocus::Content;
+        if self.block_anchor.is_some() && self.handle_block_key(key) {
+            self.ensure_cursor_visible();
+            return Ok(());
+        }
+        if key.code == KeyCode::Enter && key.modifiers == KeyModifiers::ALT {
+            return self.follow_link();
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let is_motion = matches!(
+            key.code,
+            KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down | KeyCode::Home | KeyCode::End
+        );
+        if is_motion {
+            if key.modifiers.contains(KeyModifiers::SHIFT) {
+                self.selection_anchor.get_or_insert((self.cursor_row, self.cursor_col));
+            } else {
+                self.selection_anchor = None;
+            }
+        }
+        let is_edit = matches!(key.code, KeyCode::Enter | KeyCode::Backspace | KeyCode::Delete)
+            || matches!(key.code, KeyCode::Char(_) if !ctrl);
+        if is_edit || key.code == KeyCode::Esc {
+            self.find_matches.clear();
+        }
+        if self.auto_pair
+            && let KeyCode::Char(c) = key.code
+            && !ctrl
+            && let Some(close) = closing_pair(c)
+            && let Some(selected) = self.selected_text()
+        {
+            self.delete_selection();
+            self.insert_text(&format!("{}{}{}", c, selected, close));
+            self.ensure_cursor_visible();
+            return Ok(());
+        }
+        if is_edit
+            && self.delete_selection()
+            && matches!(key.code, KeyCode::Backspace | KeyCode::Delete)
+        {
+            self.ensure_cursor_visible();
+            return Ok(());
+        }
         match key.code {
             KeyCode::Left => {
                 if self.cursor_col > 0 {
-                    self.cursor_col -= 1;
+                    self.cursor_col = prev_char_boundary(&self.lines[self.cursor_row], self.cursor_col);
                 } else if self.cursor_row > 0 {
                     self.cursor_row -= 1;
                     self.cursor_col = self.lines[self.cursor_row].len();
+                    self.skip_folded(false);
                 }
             }
             KeyCode::Right => {
                 if self.cursor_col < self.lines[self.cursor_row].len() {
-                    self.cursor_col += 1;
+                    self.cursor_col = next_char_boundary(&self.lines[self.cursor_row], self.cursor_col);
                 } else if self.cursor_row + 1 < self.lines.len() {
                     self.cursor_row += 1;
                     self.cursor_col = 0;
+                    self.skip_folded(true);
                 }
             }
-            KeyCode::Up => {
-                if self.cursor_row > 0 {
-                    self.cursor_row -= 1;
-                    self.cursor_col = self.cursor_col.min(self.lines[self.cursor_row].len());
-                }
+            KeyCode::Up if self.cursor_row > 0 => {
+                self.cursor_row -= 1;
+                self.cursor_col = self.cursor_col.min(self.lines[self.cursor_row].len());
+                self.skip_folded(false);
             }
-            KeyCode::Down => {
-                if self.cursor_row + 1 < self.lines.len() {
-                    self.cursor_row += 1;
-                    self.cursor_col = self.cursor_col.min(self.lines[self.cursor_row].len());
+            KeyCode::Down if self.cursor_row + 1 < self.lines.len() => {
+                let from = self.cursor_row;
+                self.cursor_row += 1;
+                self.skip_folded(true);
+                if self.cursor_row < from {
+                    // Folded section at the end of the note: nowhere to go.
+                    self.cursor_row = from;
                 }
+                self.cursor_col = self.cursor_col.min(self.lines[self.cursor_row].len());
             }
             KeyCode::Home => {
                 self.cursor_col = 0;
@@ -437,10 +1542,16 @@ impl App {
             KeyCode::End => {
                 self.cursor_col = self.lines[self.cursor_row].len();
             }
+            KeyCode::Backspace if self.auto_pair && self.cursor_in_empty_pair() => {
+                let col = self.cursor_col;
+                self.lines[self.cursor_row].replace_range(col - 1..col + 1, "");
+                self.cursor_col -= 1;
+                self.dirty = true;
+            }
             KeyCode::Backspace => {
                 if self.cursor_col > 0 {
-                    self.lines[self.cursor_row].remove(self.cursor_col - 1);
-                    self.cursor_col -= 1;
+                    self.cursor_col = prev_char_boundary(&self.lines[self.cursor_row], self.cursor_col);
+                    self.lines[self.cursor_row].remove(self.cursor_col);
                 } else if self.cursor_row > 0 {
                     let prev_len = self.lines[self.cursor_row - 1].len();
                     let curr = self.lines.remove(self.cursor_row);
@@ -452,7 +1563,8 @@ impl App {
             }
             KeyCode::Delete => {
                 if self.cursor_col < self.lines[self.cursor_row].len() {
-                    self.lines[self.cursor_row].remove(self.cursor_col);
+                    let col = clamp_to_char_boundary(&self.lines[self.cursor_row], self.cursor_col);
+                    self.lines[self.cursor_row].remove(col);
                 } else if self.cursor_row + 1 < self.lines.len() {
                     let next = self.lines.remove(self.cursor_row + 1);
                     self.lines[self.cursor_row].push_str(&next);
@@ -461,15 +1573,111 @@ impl App {
             }
             KeyCode::Enter => {
                 let rest = self.lines[self.cursor_row].split_off(self.cursor_col);
+                let prefix = continuation_prefix(&self.lines[self.cursor_row]).to_string();
                 self.cursor_row += 1;
-                self.cursor_col = 0;
-                self.lines.insert(self.cursor_row, rest);
+                self.cursor_col = prefix.len();
+                self.lines.insert(self.cursor_row, prefix + &rest);
                 self.dirty = true;
             }
-            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
-                self.lines[self.cursor_row].insert(self.cursor_col, c);
-                self.cursor_col += 1;
-                self.dirty = true;
+            KeyCode::Char('c') if ctrl => {
+                let text = self.selected_text().unwrap_or_else(|| self.lines[self.cursor_row].clone());
+                self.copy_to_clipboard(&text);
+            }
+            KeyCode::Char('x') if ctrl => {
+                if let Some(text) = self.selected_text() {
+                    self.copy_to_clipboard(&text);
+                    self.delete_selection();
+                } else {
+                    let line = self.lines[self.cursor_row].clone();
+                    self.copy_to_clipboard(&line);
+                    if self.lines.len() > 1 {
+                        self.lines.remove(self.cursor_row);
+                        self.cursor_row = self.cursor_row.min(self.lines.len() - 1);
+                    } else {
+                        self.lines[0].clear();
+                    }
+                    self.cursor_col = 0;
+                    self.dirty = true;
+                }
+            }
+            KeyCode::Tab => self.indent(),
+            KeyCode::BackTab => self.dedent(),
+            KeyCode::Char('z') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.wrap = !self.wrap;
+                self.scroll_x = 0;
+                self.status_message = Some(format!("Wrap {}", if self.wrap { "on" } else { "off" }));
+            }
+            KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::ALT) => self.toggle_fold(),
+            KeyCode::Char('F') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.folded.clear();
+                self.status_message = Some("Unfolded all sections".into());
+            }
+            KeyCode::Char('k') if ctrl => {
+                let text = self.selected_text().filter(|t| !t.contains('\n')).unwrap_or_default();
+                let url = Some(self.clipboard.register.trim())
+                    .filter(|r| looks_like_url(r))
+                    .unwrap_or_default()
+                    .to_string();
+                let editing_url = !text.is_empty() && url.is_empty();
+                self.modal = Some(Modal::InsertLink { text, url, editing_url });
+            }
+            KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.open_note_picker(PickAction::InsertWikilink);
+            }
+            KeyCode::Char('m') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.modal = Some(Modal::Marks { setting: true, selected: 0 });
+            }
+            KeyCode::Char('\'') if key.modifiers.contains(KeyModifiers::ALT) => {
+                if self.marks.is_empty() {
+                    self.status_message = Some("No marks in this note (Alt+M sets one)".into());
+                } else {
+                    self.modal = Some(Modal::Marks { setting: false, selected: 0 });
+                }
+            }
+            KeyCode::Char('o') if ctrl => {
+                let headings = markdown::headings(&self.lines);
+                if headings.is_empty() {
+                    self.status_message = Some("No headings in this note".into());
+                } else {
+                    let selected = markdown::current_heading(&headings, self.cursor_row).unwrap_or(0);
+                    self.modal = Some(Modal::Outline { selected });
+                }
+            }
+            KeyCode::Char('f') if ctrl => {
+                self.selection_anchor = None;
+                self.modal = Some(Modal::Find {
+                    query: String::new(),
+                    origin: (self.cursor_row, self.cursor_col),
+                });
+            }
+            KeyCode::Char('v') if ctrl && self.clipboard.block => {
+                let text = self.clipboard.register.clone();
+                self.paste_block(&text);
+            }
+            KeyCode::Char('v') if ctrl => {
+                let text = self.clipboard.register.clone();
+                self.paste_text(&text);
+            }
+            KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::ALT) => self.reflow(),
+            KeyCode::Char('l') if ctrl => self.modal = Some(Modal::GotoLine { input: String::new() }),
+            KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::ALT) => self.prompt_insert_date(),
+            KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::ALT) => {
+                self.selection_anchor = None;
+                self.block_anchor = Some((self.cursor_row, char_col(&self.lines[self.cursor_row], self.cursor_col)));
+                self.status_message = Some("Block selection: type to insert on every line, Ctrl+C/X yank, Esc ends".into());
+            }
+            KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::ALT) => {
+                if self.clipboard.history.is_empty() {
+                    self.status_message = Some("Nothing copied yet".into());
+                } else {
+                    self.modal = Some(Modal::Yanks { selected: 0 });
+                }
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.type_char(c);
+                if c == '[' && !self.replaying && self.lines[self.cursor_row][..self.cursor_col].ends_with("[[") {
+                    self.open_note_picker(PickAction::CompleteWikilink);
+                }
             }
             _ => {}
         }
@@ -485,15 +1693,18 @@ impl App {
             KeyCode::Down => {
                 self.git_section.select_next();
             }
-            KeyCode::Home => {
-                if !self.git_section.commits.is_empty() {
-                    self.git_section.selected = 0;
-                }
+            KeyCode::Home if !self.git_section.commits.is_empty() => {
+                self.git_section.select(0);
             }
-            KeyCode::End => {
-                if !self.git_section.commits.is_empty() {
-                    self.git_section.selected = self.git_section.commits.len() - 1;
-                }
+            KeyCode::End if !self.git_section.commits.is_empty() => {
+                self.git_section.select(self.git_section.commits.len() - 1);
+            }
+            KeyCode::PageDown if !self.git_section.commits.is_empty() => {
+                self.git_section.select((self.git_section.selected + 10).min(self.git_section.commits.len() - 1));
+            }
+            KeyCode::PageUp => self.git_section.select(self.git_section.selected.saturating_sub(10)),
+            KeyCode::Enter if !self.git_section.selected_changed_files().is_empty() => {
+                self.focus = Focus::ChangedFiles;
             }
             KeyCode::Left => {
                 self.focus = Focus::Sidebar;
@@ -506,26 +1717,121 @@ impl App {
             }
             KeyCode::Char('r') if key.modifiers.is_empty() => {
                 self.git_section.fetch_and_refresh();
-                self.status_message = Some("Fetched and refreshed commits".to_string());
+                self.status_message = Some("Fetching...".to_string());
+            }
+            KeyCode::Char('a') if key.modifiers.is_empty() => self.start_amend()?,
+            KeyCode::Char('p') if key.modifiers.is_empty() => {
+                self.git_section.pull();
+                self.status_message = Some("Pulling...".into());
+            }
+            KeyCode::Char('t') if key.modifiers.is_empty() => self.absolute_dates = !self.absolute_dates,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_changed_files_key(&mut self, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Enter => self.open_changed_file(false)?,
+            KeyCode::Char('d') if key.modifiers.is_empty() => self.open_changed_file(true)?,
+            KeyCode::Home => self.git_section.file_selected = 0,
+            KeyCode::End => self.git_section.file_selected = self.git_section.selected_changed_files().len().saturating_sub(1),
+            KeyCode::PageDown => {
+                let last = self.git_section.selected_changed_files().len().saturating_sub(1);
+                self.git_section.file_selected = (self.git_section.file_selected + 10).min(last);
             }
+            KeyCode::PageUp => self.git_section.file_selected = self.git_section.file_selected.saturating_sub(10),
+            KeyCode::Esc => self.focus = Focus::Commits,
             _ => {}
         }
         Ok(())
     }
 
+    /// A left click on a Changed Files row opens it like Enter does; other
+    /// mouse input is ignored.
+    fn handle_mouse(&mut self, event: MouseEvent) -> Result<()> {
+        if self.modal.is_some() || event.kind != MouseEventKind::Down(MouseButton::Left) {
+            return Ok(());
+        }
+        let area = self.changed_files_area;
+        let inside = event.column > area.x
+            && event.column + 1 < area.right()
+            && event.row > area.y
+            && event.row + 1 < area.bottom();
+        if !inside {
+            return Ok(());
+        }
+        let index = self.git_section.files_state.offset() + (event.row - area.y - 1) as usize;
+        if index < self.git_section.selected_changed_files().len() {
+            self.focus = Focus::ChangedFiles;
+            self.git_section.file_selected = index;
+            self.open_changed_file(false)?;
+        }
+        Ok(())
+    }
+
+    /// Opens the selected Changed Files entry: the note itself, or with
+    /// `diff` (and for files no longer in the vault) what the commit did to
+    /// it.
+    fn open_changed_file(&mut self, diff: bool) -> Result<()> {
+        let files = self.git_section.selected_changed_files();
+        let Some(file) = files.get(self.git_section.file_selected).cloned() else { return Ok(()) };
+        let path = self.notes_dir.join(&file.path);
+        if !diff && links::is_note(&path) && path.is_file() {
+            if self.dirty && !self.read_only {
+                self.save_current()?;
+            }
+            self.open_file(&path, None)?;
+            return Ok(());
+        }
+        let hash = self.git_section.commits[self.git_section.selected].hash.clone();
+        // Paths are relative to the notes folder, which `./` tells git.
+        let show = |rev: &str, path: &str| {
+            self.git_section.show_file(rev, &format!("./{}", path)).map(|c| split_lines_preserve(&c)).unwrap_or_default()
+        };
+        let old = if file.status == 'A' { Vec::new() } else { show(&format!("{}^", hash), file.from.as_deref().unwrap_or(&file.path)) };
+        let new = if file.status == 'D' { Vec::new() } else { show(&hash, &file.path) };
+        let rows = diff::with_context(diff::diff_lines(&old, &new), 3);
+        self.modal = Some(Modal::CommitDiff { hash, file, rows, scroll: 0 });
+        Ok(())
+    }
+
     fn handle_modal_key(&mut self, key: KeyEvent) -> Result<()> {
+        if matches!(self.modal, Some(Modal::Find { .. } | Modal::VaultSearch { .. } | Modal::Replace { previewing: false, .. }))
+            && let Some(options) = self.toggle_search_option(key)
+        {
+            if let Some(Modal::VaultSearch { query, hits, selected, .. }) = &mut self.modal {
+                *hits = Self::run_vault_search(&self.notes_tree, query, options);
+                *selected = 0;
+            }
+            self.update_find();
+            return Ok(());
+        }
         if let Some(modal) = &mut self.modal {
             match modal {
                 Modal::ConfirmDelete { path } => {
                     match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') if self.read_only => {
+                            self.modal = None;
+                            self.status_message = Some("Read-only: not deleted".into());
+                        }
                         KeyCode::Char('y') | KeyCode::Char('Y') => {
-                            if let Err(e) = std::fs::remove_file(path) {
-                                self.status_message = Some(format!("Delete failed: {}", e));
-                            } else {
-                                self.status_message = Some("Deleted".to_string());
-                                self.refresh_sidebar_preserve_selection(None);
-                            }
+                            let path = path.clone();
                             self.modal = None;
+                            let deleted = std::fs::read(&path)
+                                .and_then(|content| std::fs::remove_file(&path).map(|()| content))
+                                .with_context(|| format!("Delete {}", path.display()));
+                            match deleted {
+                                Err(e) => self.report_error(e),
+                                Ok(content) => {
+                                    self.deleted.push(DeletedFile { path: path.clone(), content, at: datefmt::now() });
+                                    self.status_message = Some("Deleted (u in the sidebar undoes)".to_string());
+                                    if let Some(parent) = path.parent() {
+                                        self.rescan_sidebar_dir(parent);
+                                    }
+                                    self.git_section.request_refresh();
+                                }
+                            }
                         }
                         KeyCode::Char('n') | KeyCode::Esc | KeyCode::Char('N') => {
                             self.modal = None;
@@ -547,12 +1853,16 @@ impl App {
                                 self.cursor_row = 0;
                                 self.cursor_col = 0;
                                 self.scroll_y = 0;
-                                self.new_note_dir = Some(target_dir.clone());
+                                self.selection_anchor = None;
+                                let target_dir = target_dir.clone();
+                                self.status_message = Some(format!("New note will be created in {}", target_dir.display()));
+                                self.new_note_dir = Some(target_dir);
+                                self.stash_note_state();
                                 self.opened_path = None;
                                 self.dirty = true;
                                 self.focus = Focus::Title;
                                 self.last_right_focus = RightFocus::Title;
-                                self.status_message = Some(format!("New note will be created in {}", target_dir.display()));
+                                self.title_to_heading();
                             }
                             self.modal = None;
                         }
@@ -560,124 +1870,2848 @@ impl App {
                         _ => {}
                     }
                 }
-            }
-        }
-        Ok(())
-    }
-
-    fn ensure_cursor_visible(&mut self) {
-        let window = 20usize;
-        if self.cursor_row < self.scroll_y {
-            self.scroll_y = self.cursor_row;
-        } else if self.cursor_row >= self.scroll_y + window {
-            self.scroll_y = self.cursor_row + 1 - window;
-        }
-    }
-
-    fn open_file(&mut self, path: &Path) -> Result<()> {
-        let content = read_note(path).unwrap_or_default();
-        let title = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or_default()
-            .to_string();
-
-        self.title = title;
-        self.title_cursor = self.title.len();
-        self.lines = split_lines_preserve(&content);
-        if self.lines.is_empty() {
-            self.lines.push(String::new());
-        }
-        self.cursor_row = 0;
-        self.cursor_col = 0;
-        self.scroll_y = 0;
-        self.opened_path = Some(path.to_path_buf());
-        self.dirty = false;
-        self.focus = self.last_right_focus.into();
-        Ok(())
-    }
-
-    
-
-    fn save_current(&mut self) -> Result<()> {
-        if self.title.trim().is_empty() {
-            return Ok(());
-        }
-    let target_dir = self.new_note_dir.as_ref().unwrap_or(&self.notes_dir);
-    let new_path = target_dir.join(format!("{}.md", self.title.trim()));
-        let content = self.lines.join("\n");
-
-        if let Some(old) = &self.opened_path {
-            if *old != new_path {
-                write_note(&new_path, &content)?;
-                rename_note(old, &new_path).ok();
-            } else {
-                write_note(&new_path, &content)?;
-            }
-        } else {
-            write_note(&new_path, &content)?;
-        }
-
-        self.opened_path = Some(new_path.clone());
-        self.dirty = false;
-    self.new_note_dir = None;
-
-        
-        self.refresh_sidebar_select_path(&new_path);
-
-        Ok(())
-    }
-
-    fn refresh_sidebar_select_path(&mut self, path: &Path) {
-        self.refresh_sidebar_preserve_selection(None);
-        if let Some(idx) = self
-            .sidebar_items
-            .iter()
-            .position(|n| !n.is_dir && n.path == path)
-        {
-            self.sidebar_state.select(Some(idx));
-        }
-    }
-
-    fn refresh_sidebar_preserve_selection(&mut self, prefer_idx: Option<usize>) {
-        let old_idx = prefer_idx.or(self.sidebar_state.selected());
-        self.sidebar_items = Self::build_sidebar(&self.notes_dir, &self.expanded_dirs).unwrap_or_default();
-        if !self.sidebar_items.is_empty() {
-            let idx = old_idx.unwrap_or(0).min(self.sidebar_items.len() - 1);
-            self.sidebar_state.select(Some(idx));
-        } else {
-            self.sidebar_state.select(None);
-        }
-    }
-
-    fn build_sidebar(notes_dir: &Path, expanded: &HashSet<PathBuf>) -> Result<Vec<FlatNode>> {
-        let tree = build_notes_tree(notes_dir)?;
-        Ok(flatten_tree_for_sidebar(&tree, expanded))
-    }
-}
-
-impl From<RightFocus> for Focus {
-    fn from(value: RightFocus) -> Self {
-        match value {
-            RightFocus::Title => Focus::Title,
-            RightFocus::Content => Focus::Content,
-        }
-    }
-}
-
-fn split_lines_preserve(s: &str) -> Vec<String> {
-    let mut out = Vec::new();
-    for (_i, line) in s.split_inclusive('\n').enumerate() {
-        if line.ends_with('\n') {
-            let mut ln = line.to_string();
-            ln.pop();
-            out.push(ln);
-        } else {
-            out.push(line.to_string());
-        }
-    }
-    if out.is_empty() {
-        out.push(String::new());
-    }
-    out
+                Modal::Outline { selected } => {
+                    let headings = markdown::headings(&self.lines);
+                    match key.code {
+                        KeyCode::Down | KeyCode::Char('j') => {
+                            *selected = (*selected + 1).min(headings.len().saturating_sub(1))
+                        }
+                        KeyCode::Up | KeyCode::Char('k') => *selected = selected.saturating_sub(1),
+                        KeyCode::Enter => {
+                            if let Some(h) = headings.get(*selected) {
+                                self.selection_anchor = None;
+                                self.cursor_row = h.row;
+                                self.cursor_col = 0;
+                                self.scroll_y = h.row;
+                                self.ensure_cursor_visible();
+                            }
+                            self.modal = None;
+                        }
+                        KeyCode::Esc => self.modal = None,
+                        _ => {}
+                    }
+                }
+                Modal::Marks { setting, selected } => {
+                    let setting = *setting;
+                    match key.code {
+                        KeyCode::Char(c) if c.is_ascii_alphabetic() && !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            self.modal = None;
+                            if setting { self.set_mark(c) } else { self.jump_to_mark(c) }
+                        }
+                        KeyCode::Down => *selected = (*selected + 1).min(self.marks.len().saturating_sub(1)),
+                        KeyCode::Up => *selected = selected.saturating_sub(1),
+                        KeyCode::Enter => {
+                            let name = self.marks.keys().nth(*selected).copied();
+                            self.modal = None;
+                            if let Some(c) = name.filter(|_| !setting) {
+                                self.jump_to_mark(c);
+                            }
+                        }
+                        KeyCode::Delete => {
+                            if let Some(c) = self.marks.keys().nth(*selected).copied() {
+                                self.marks.remove(&c);
+                                *selected = (*selected).min(self.marks.len().saturating_sub(1));
+                            }
+                        }
+                        KeyCode::Esc => self.modal = None,
+                        _ => {}
+                    }
+                }
+                Modal::ExtractNote { name, from, to, open } => match key.code {
+                    KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) && !matches!(c, '/' | '\\' | '.') => name.push(c),
+                    KeyCode::Backspace => {
+                        name.pop();
+                    }
+                    KeyCode::Enter if !name.trim().is_empty() => {
+                        let (name, from, to, open) = (name.trim().to_string(), *from, *to, *open);
+                        self.modal = None;
+                        self.extract_to_note(&name, from, to, open)?;
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::LinkReport { issues, selected } => match key.code {
+                    KeyCode::Down => *selected = (*selected + 1).min(issues.len() - 1),
+                    KeyCode::Up => *selected = selected.saturating_sub(1),
+                    KeyCode::PageDown => *selected = (*selected + 10).min(issues.len() - 1),
+                    KeyCode::PageUp => *selected = selected.saturating_sub(10),
+                    KeyCode::Enter => {
+                        let issue = issues[*selected].clone();
+                        self.modal = None;
+                        self.jump_to_issue(&issue)?;
+                    }
+                    KeyCode::Char('r') => self.open_link_report(),
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::Remotes { remotes, selected, form } => match form {
+                    Some((name, url, editing_url)) => match key.code {
+                        KeyCode::Tab | KeyCode::BackTab => *editing_url = !*editing_url,
+                        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            if *editing_url { url.push(c) } else { name.push(c) }
+                        }
+                        KeyCode::Backspace => {
+                            if *editing_url { url.pop() } else { name.pop() };
+                        }
+                        KeyCode::Enter if !name.trim().is_empty() && !url.trim().is_empty() => {
+                            let (name, url) = (name.trim().to_string(), url.trim().to_string());
+                            let verb = if remotes.iter().any(|(n, _)| *n == name) { "now points at" } else { "added for" };
+                            self.git_section.set_remote(&name, &url)?;
+                            self.open_remotes()?;
+                            if let Some(Modal::Remotes { remotes, selected, .. }) = &mut self.modal {
+                                *selected = remotes.iter().position(|(n, _)| *n == name).unwrap_or(0);
+                            }
+                            self.status_message = Some(format!("Remote {} {} {}", name, verb, url));
+                        }
+                        KeyCode::Esc if remotes.is_empty() => self.modal = None,
+                        KeyCode::Esc => *form = None,
+                        _ => {}
+                    },
+                    None => match key.code {
+                        KeyCode::Down => *selected = (*selected + 1).min(remotes.len().saturating_sub(1)),
+                        KeyCode::Up => *selected = selected.saturating_sub(1),
+                        KeyCode::Char('a') => *form = Some((String::new(), String::new(), false)),
+                        KeyCode::Char('e') | KeyCode::Enter => {
+                            if let Some((name, url)) = remotes.get(*selected) {
+                                *form = Some((name.clone(), url.clone(), true));
+                            }
+                        }
+                        KeyCode::Esc => self.modal = None,
+                        _ => {}
+                    },
+                },
+                Modal::Attachments { items, selected, renaming: Some(name), .. } => match key.code {
+                    KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) && !matches!(c, '/' | '\\') => name.push(c),
+                    KeyCode::Backspace => {
+                        name.pop();
+                    }
+                    KeyCode::Enter if !name.trim().is_empty() => {
+                        let (old, name) = (items[*selected].path.clone(), name.trim().to_string());
+                        self.rename_attachment(&old, &name)?;
+                    }
+                    KeyCode::Esc => {
+                        if let Some(Modal::Attachments { renaming, .. }) = &mut self.modal {
+                            *renaming = None;
+                        }
+                    }
+                    _ => {}
+                },
+                Modal::Attachments { items, selected, vault, renaming } => match key.code {
+                    KeyCode::Down => *selected = (*selected + 1).min(items.len() - 1),
+                    KeyCode::Up => *selected = selected.saturating_sub(1),
+                    KeyCode::Enter | KeyCode::Char('o') if items[*selected].state != attachments::State::Missing => {
+                        let path = items[*selected].path.clone();
+                        self.open_externally(&path)?;
+                    }
+                    KeyCode::Char('r') if items[*selected].state != attachments::State::Missing => {
+                        let name = items[*selected].path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                        *renaming = Some(name);
+                    }
+                    KeyCode::Char('d') if *vault || items[*selected].state == attachments::State::Unreferenced => {
+                        let path = items[*selected].path.clone();
+                        self.modal = Some(Modal::ConfirmDelete { path });
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::Present { slides, index } => match key.code {
+                    KeyCode::Right | KeyCode::Down | KeyCode::PageDown | KeyCode::Char(' ' | 'l' | 'n') => {
+                        *index = (*index + 1).min(slides.len() - 1);
+                    }
+                    KeyCode::Left | KeyCode::Up | KeyCode::PageUp | KeyCode::Backspace | KeyCode::Char('h' | 'p') => {
+                        *index = index.saturating_sub(1);
+                    }
+                    KeyCode::Home => *index = 0,
+                    KeyCode::End => *index = slides.len() - 1,
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        // Leave the cursor on the slide shown last.
+                        self.cursor_row = slides[*index].0;
+                        self.cursor_col = 0;
+                        self.modal = None;
+                        self.ensure_cursor_visible();
+                    }
+                    _ => {}
+                },
+                Modal::ImportFile { input, candidates, to_assets } => match key.code {
+                    KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => *to_assets = !*to_assets,
+                    KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        input.push(c);
+                        candidates.clear();
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                        candidates.clear();
+                    }
+                    KeyCode::Tab => {
+                        *candidates = attachments::complete_path(input);
+                        if let Some(first) = candidates.first() {
+                            let common = candidates.iter().fold(first.len(), |n, c| {
+                                first.bytes().zip(c.bytes()).take(n).take_while(|(a, b)| a == b).count()
+                            });
+                            *input = first[..clamp_to_char_boundary(first, common)].to_string();
+                        }
+                        if candidates.len() == 1 && !input.ends_with('/') {
+                            candidates.clear();
+                        }
+                    }
+                    KeyCode::Enter if !input.trim().is_empty() => {
+                        let (source, to_assets) = (crate::config::expand_tilde(input.trim()), *to_assets);
+                        self.modal = None;
+                        self.import_file(&source, to_assets)?;
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::Passphrase { path, input, sealing, at } => match key.code {
+                    KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => input.push(c),
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Enter if !input.is_empty() => {
+                        let (path, sealing, at) = (path.clone(), *sealing, at.take());
+                        self.keys.insert(path.clone(), std::mem::take(input));
+                        self.modal = None;
+                        if sealing {
+                            self.save_current()?;
+                        } else if let Err(e) = self.open_file(&path, at.clone()) {
+                            self.keys.remove(&path);
+                            self.modal = Some(Modal::Passphrase { path, input: String::new(), sealing, at });
+                            self.status_message = Some(format!("{:#}", e));
+                        }
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::Batch { action, input, confirming: false } => match key.code {
+                    KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => input.push(c),
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Enter if !input.trim().is_empty() => {
+                        let (action, input) = (*action, input.clone());
+                        self.modal = Some(Modal::Batch { action, input, confirming: true });
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::Batch { action, input, confirming: true } => match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') if self.read_only => {
+                        self.modal = None;
+                        self.status_message = Some("Read-only: nothing changed".into());
+                    }
+                    KeyCode::Char('y') | KeyCode::Char('Y') => {
+                        let (action, input) = (*action, input.trim().to_string());
+                        self.modal = None;
+                        self.run_batch(action, &input)?;
+                    }
+                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::Deleted { selected } => match key.code {
+                    KeyCode::Down => *selected = (*selected + 1).min(self.deleted.len() - 1),
+                    KeyCode::Up => *selected = selected.saturating_sub(1),
+                    KeyCode::Enter => {
+                        let index = self.deleted.len() - 1 - *selected;
+                        self.modal = None;
+                        self.restore_deleted(index)?;
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::Stashes { stashes, selected } => match key.code {
+                    KeyCode::Down => *selected = (*selected + 1).min(stashes.len() - 1),
+                    KeyCode::Up => *selected = selected.saturating_sub(1),
+                    KeyCode::Enter | KeyCode::Char('a') => {
+                        let (reference, pop) = (stashes[*selected].0.clone(), key.code == KeyCode::Enter);
+                        self.modal = None;
+                        if self.dirty && !self.read_only {
+                            self.save_current()?;
+                        }
+                        self.git_section.unstash(&reference, pop);
+                        self.status_message = Some(format!("Applying {}...", reference));
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::Yanks { selected } => match key.code {
+                    KeyCode::Down => *selected = (*selected + 1).min(self.clipboard.history.len() - 1),
+                    KeyCode::Up => *selected = selected.saturating_sub(1),
+                    KeyCode::Enter => {
+                        let index = *selected;
+                        self.modal = None;
+                        if let Some(text) = self.clipboard.promote(index) {
+                            self.paste_text(&text);
+                            self.ensure_cursor_visible();
+                        }
+                    }
+                    KeyCode::Delete => {
+                        self.clipboard.history.remove(*selected);
+                        if self.clipboard.history.is_empty() {
+                            self.modal = None;
+                        } else {
+                            *selected = (*selected).min(self.clipboard.history.len() - 1);
+                        }
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::Replace { pattern, with, editing_with, matches, selected, previewing: false, .. } => match key.code {
+                    KeyCode::Tab | KeyCode::BackTab => *editing_with = !*editing_with,
+                    KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        if *editing_with { with.push(c) } else { pattern.push(c) }
+                    }
+                    KeyCode::Backspace => {
+                        if *editing_with { with.pop() } else { pattern.pop() };
+                    }
+                    KeyCode::Enter if !pattern.is_empty() => {
+                        let options = self.searches.options;
+                        match Matcher::new(pattern, options) {
+                            Err(e) => self.status_message = Some(format!("Invalid regex: {}", e)),
+                            Ok(matcher) => {
+                                *matches = replace::find_matches(&collect_files(&self.notes_tree), &matcher, with, options.regex, 2000);
+                                *selected = 0;
+                                if matches.is_empty() {
+                                    self.status_message = Some("No matches".into());
+                                } else if let Some(Modal::Replace { previewing, .. }) = &mut self.modal {
+                                    *previewing = true;
+                                }
+                            }
+                        }
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::Replace { pattern, with, matches, selected, previewing, commit, .. } => match key.code {
+                    KeyCode::Down => *selected = (*selected + 1).min(matches.len() - 1),
+                    KeyCode::Up => *selected = selected.saturating_sub(1),
+                    KeyCode::PageDown => *selected = (*selected + 10).min(matches.len() - 1),
+                    KeyCode::PageUp => *selected = selected.saturating_sub(10),
+                    KeyCode::Char(' ') => {
+                        let m = &mut matches[*selected];
+                        m.accepted = !m.accepted;
+                        *selected = (*selected + 1).min(matches.len() - 1);
+                    }
+                    KeyCode::Char('a') => {
+                        let all = matches.iter().all(|m| m.accepted);
+                        matches.iter_mut().for_each(|m| m.accepted = !all);
+                    }
+                    KeyCode::Char('c') => *commit = !*commit,
+                    KeyCode::Enter => {
+                        let matches = std::mem::take(matches);
+                        let message = format!("Replace '{}' with '{}'", pattern, with);
+                        let commit = *commit;
+                        self.modal = None;
+                        self.apply_replace(&matches, commit.then_some(message.as_str()))?;
+                    }
+                    KeyCode::Esc => *previewing = false,
+                    _ => {}
+                },
+                Modal::GotoLine { input } => match key.code {
+                    KeyCode::Char(c) if c.is_ascii_digit() || (c == '%' && !input.is_empty() && !input.ends_with('%')) => input.push(c),
+                    KeyCode::Backspace => { input.pop(); }
+                    KeyCode::Enter => {
+                        let input = std::mem::take(input);
+                        self.modal = None;
+                        self.goto_line(&input);
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::InsertDate { selected } => match key.code {
+                    KeyCode::Down => *selected = (*selected + 1).min(self.date_formats.len() - 1),
+                    KeyCode::Up => *selected = selected.saturating_sub(1),
+                    KeyCode::Enter => {
+                        let fmt = self.date_formats[*selected].clone();
+                        self.modal = None;
+                        self.insert_date(&fmt);
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::MacroRegister { record, count } => {
+                    let record = *record;
+                    match key.code {
+                        // Digits are replay counts, so registers are letters.
+                        KeyCode::Char(c) if c.is_ascii_digit() && !record => count.push(c),
+                        KeyCode::Char(c) if c.is_ascii_alphabetic() => {
+                            let times = count.parse::<usize>().unwrap_or(1).max(1);
+                            self.modal = None;
+                            if record {
+                                self.recording = Some((c, Vec::new()));
+                                self.status_message = Some(format!("Recording @{} (Alt+Q stops)", c));
+                            } else {
+                                self.replay_macro(c, times)?;
+                            }
+                        }
+                        KeyCode::Backspace => {
+                            count.pop();
+                        }
+                        KeyCode::Esc => self.modal = None,
+                        _ => {}
+                    }
+                }
+                Modal::Menu { menu } => {
+                    let menu = *menu;
+                    match key.code {
+                        KeyCode::Char(c) => {
+                            self.modal = None;
+                            if let Some(entry) = menu.entry(c) {
+                                self.run_menu_action(entry.action)?;
+                            }
+                        }
+                        KeyCode::Backspace => self.modal = Some(Modal::Menu { menu: &menu::ROOT }),
+                        KeyCode::Esc => self.modal = None,
+                        _ => {}
+                    }
+                }
+                Modal::ConfirmQuit => match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => {
+                        self.modal = None;
+                        self.save_current()?;
+                        self.quitting = true;
+                    }
+                    KeyCode::Char('n') | KeyCode::Char('N') => self.quitting = true,
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::Log { scroll } => match key.code {
+                    KeyCode::Up => *scroll += 1,
+                    KeyCode::Down => *scroll = scroll.saturating_sub(1),
+                    KeyCode::PageUp => *scroll += 10,
+                    KeyCode::PageDown => *scroll = scroll.saturating_sub(10),
+                    KeyCode::Esc | KeyCode::Char('q') => self.modal = None,
+                    _ => {}
+                },
+                Modal::History { revisions, selected, lines, scroll } => match key.code {
+                    KeyCode::Up | KeyCode::Down => {
+                        let next = if key.code == KeyCode::Up {
+                            selected.saturating_sub(1)
+                        } else {
+                            (*selected + 1).min(revisions.len() - 1)
+                        };
+                        if next != *selected {
+                            let rev = revisions[next].clone();
+                            *selected = next;
+                            *scroll = 0;
+                            *lines = match Self::revision_lines(&self.git_section, &rev) {
+                                Ok(lines) => lines,
+                                Err(e) => vec![format!("({:#})", e)],
+                            };
+                        }
+                    }
+                    KeyCode::PageDown => *scroll = (*scroll + 10).min(lines.len().saturating_sub(1)),
+                    KeyCode::PageUp => *scroll = scroll.saturating_sub(10),
+                    KeyCode::Enter => {
+                        let hash = revisions[*selected].hash.clone();
+                        self.lines = std::mem::take(lines);
+                        self.modal = None;
+                        self.cursor_row = 0;
+                        self.cursor_col = 0;
+                        self.scroll_y = 0;
+                        self.selection_anchor = None;
+                        self.find_matches.clear();
+                        self.dirty = true;
+                        self.status_message = Some(format!("Restored version {}; save to keep it", hash));
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::Merge { regions, selected, theirs, mtime } => {
+                    let count = merge::conflicts(regions);
+                    match key.code {
+                        KeyCode::Down | KeyCode::Char('n') => *selected = (*selected + 1).min(count - 1),
+                        KeyCode::Up | KeyCode::Char('p') => *selected = selected.saturating_sub(1),
+                        KeyCode::Char(c @ ('m' | 't' | 'b')) => {
+                            if let Some(Region::Conflict { choice, .. }) =
+                                regions.iter_mut().filter(|r| matches!(r, Region::Conflict { .. })).nth(*selected)
+                            {
+                                *choice = match c {
+                                    'm' => Choice::Mine,
+                                    't' => Choice::Theirs,
+                                    _ => Choice::Both,
+                                };
+                            }
+                            *selected = (*selected + 1).min(count - 1);
+                        }
+                        KeyCode::Enter => {
+                            let (merged, theirs, mtime) = (merge::resolve(regions), std::mem::take(theirs), *mtime);
+                            self.modal = None;
+                            self.lines = merged;
+                            self.adopt_disk_version(theirs, mtime);
+                            self.dirty = true;
+                            self.status_message = Some("Merged; save to write the result".into());
+                        }
+                        KeyCode::Esc => {
+                            let (theirs, mtime) = (std::mem::take(theirs), *mtime);
+                            self.modal = None;
+                            self.adopt_disk_version(theirs, mtime);
+                            self.status_message = Some("Kept your version; saving overwrites the file on disk".into());
+                        }
+                        _ => {}
+                    }
+                }
+                Modal::CommitDiff { rows, scroll, .. } => match key.code {
+                    KeyCode::Down => *scroll = (*scroll + 1).min(rows.len().saturating_sub(1)),
+                    KeyCode::Up => *scroll = scroll.saturating_sub(1),
+                    KeyCode::PageDown => *scroll = (*scroll + 10).min(rows.len().saturating_sub(1)),
+                    KeyCode::PageUp => *scroll = scroll.saturating_sub(10),
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => self.modal = None,
+                    _ => {}
+                },
+                Modal::Diff { base, rows, scroll } => match key.code {
+                    KeyCode::Down => *scroll = (*scroll + 1).min(rows.len().saturating_sub(1)),
+                    KeyCode::Up => *scroll = scroll.saturating_sub(1),
+                    KeyCode::PageDown => *scroll = (*scroll + 10).min(rows.len().saturating_sub(1)),
+                    KeyCode::PageUp => *scroll = scroll.saturating_sub(10),
+                    KeyCode::Tab => {
+                        let other = if *base == DiffBase::Saved { DiffBase::Committed } else { DiffBase::Saved };
+                        self.modal = None;
+                        self.open_diff(other);
+                    }
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => self.modal = None,
+                    _ => {}
+                },
+                Modal::LocalHistory { snapshots, selected, lines, scroll } => match key.code {
+                    KeyCode::Up | KeyCode::Down => {
+                        let next = if key.code == KeyCode::Up {
+                            selected.saturating_sub(1)
+                        } else {
+                            (*selected + 1).min(snapshots.len() - 1)
+                        };
+                        if next != *selected {
+                            *selected = next;
+                            *scroll = 0;
+                            *lines = Self::snapshot_lines(&snapshots[next]);
+                        }
+                    }
+                    KeyCode::PageDown => *scroll = (*scroll + 10).min(lines.len().saturating_sub(1)),
+                    KeyCode::PageUp => *scroll = scroll.saturating_sub(10),
+                    KeyCode::Enter => {
+                        let label = snapshots[*selected].label();
+                        let restored = std::mem::take(lines);
+                        self.modal = None;
+                        // Keep what is on disk now, so the restore can be undone.
+                        if let (Some(history), Some(path)) = (&self.local_history, &self.opened_path)
+                            && let Err(e) = read_note(path).and_then(|current| history.snapshot(path, &current, true))
+                        {
+                            self.report_error(e);
+                        }
+                        self.lines = restored;
+                        self.cursor_row = 0;
+                        self.cursor_col = 0;
+                        self.scroll_y = 0;
+                        self.selection_anchor = None;
+                        self.find_matches.clear();
+                        self.dirty = true;
+                        self.status_message = Some(format!("Restored snapshot from {}; save to keep it", label));
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::Stats(_) | Modal::NoteInfo(_) => {
+                    if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q')) {
+                        self.modal = None;
+                    }
+                }
+                Modal::Errors => match key.code {
+                    KeyCode::Char('c') => {
+                        self.errors.clear();
+                        self.modal = None;
+                    }
+                    KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => self.modal = None,
+                    _ => {}
+                },
+                Modal::CommitMessage { message, amend } => {
+                    match key.code {
+                        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => message.push(c),
+                        KeyCode::Backspace => {
+                            message.pop();
+                        }
+                        KeyCode::Enter if self.read_only => {
+                            self.modal = None;
+                            self.status_message = Some("Read-only: not committed".into());
+                        }
+                        KeyCode::Enter if !message.trim().is_empty() => {
+                            if *amend {
+                                self.git_section.amend(message.trim());
+                                self.status_message = Some("Amending...".into());
+                            } else {
+                                self.git_section.commit_all(message.trim());
+                                self.status_message = Some("Committing...".into());
+                            }
+                            self.modal = None;
+                        }
+                        KeyCode::Esc => self.modal = None,
+                        _ => {}
+                    }
+                }
+                Modal::CommitForm { fields, field } => match key.code {
+                    KeyCode::Tab => *field = (*field + 1) % fields.len(),
+                    KeyCode::BackTab => *field = (*field + fields.len() - 1) % fields.len(),
+                    KeyCode::Up | KeyCode::Down if *field == 0 && !self.commit_types.is_empty() => {
+                        let n = self.commit_types.len();
+                        let next = match self.commit_types.iter().position(|t| *t == fields[0]) {
+                            Some(i) if key.code == KeyCode::Up => (i + n - 1) % n,
+                            Some(i) => (i + 1) % n,
+                            None => 0,
+                        };
+                        fields[0] = self.commit_types[next].clone();
+                    }
+                    KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => fields[*field].push(c),
+                    KeyCode::Backspace => {
+                        fields[*field].pop();
+                    }
+                    KeyCode::Enter if self.read_only => {
+                        self.modal = None;
+                        self.status_message = Some("Read-only: not committed".into());
+                    }
+                    KeyCode::Enter if !fields[0].trim().is_empty() && !fields[2].trim().is_empty() => {
+                        let message = commit_message(self.commit_template.as_deref().unwrap_or_default(), fields);
+                        self.git_section.commit_all(&message);
+                        self.status_message = Some("Committing...".into());
+                        self.modal = None;
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::VaultSearch { query, hits, selected, history_pos } => {
+                    let history = &self.searches.history;
+                    match key.code {
+                        KeyCode::Up if *selected == 0 && !history.is_empty() => {
+                            let pos = history_pos.map_or(0, |p| (p + 1).min(history.len() - 1));
+                            *history_pos = Some(pos);
+                            *query = history[pos].clone();
+                            *hits = Self::run_vault_search(&self.notes_tree, query, self.searches.options);
+                        }
+                        KeyCode::Down if *selected + 1 >= hits.len() && history_pos.is_some() => {
+                            *history_pos = history_pos.and_then(|p| p.checked_sub(1));
+                            *query = history_pos.map(|p| history[p].clone()).unwrap_or_default();
+                            *hits = Self::run_vault_search(&self.notes_tree, query, self.searches.options);
+                            *selected = 0;
+                        }
+                        KeyCode::Down => *selected = (*selected + 1).min(hits.len().saturating_sub(1)),
+                        KeyCode::Up => *selected = selected.saturating_sub(1),
+                        KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) && !query.trim().is_empty() => {
+                            let query = query.trim().to_string();
+                            self.modal = Some(Modal::NameSearch { name: query.clone(), query });
+                        }
+                        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            query.push(c);
+                            *hits = Self::run_vault_search(&self.notes_tree, query, self.searches.options);
+                            *selected = 0;
+                        }
+                        KeyCode::Backspace => {
+                            query.pop();
+                            *hits = Self::run_vault_search(&self.notes_tree, query, self.searches.options);
+                            *selected = 0;
+                        }
+                        KeyCode::Enter => {
+                            let query = query.clone();
+                            let hit = hits.get(*selected).cloned();
+                            self.modal = None;
+                            self.searches.remember(&query);
+                            if let Err(e) = self.searches.save() {
+                                self.report_error(e);
+                            }
+                            if let Some(hit) = hit {
+                                self.open_search_hit(&hit, &query)?;
+                            }
+                        }
+                        KeyCode::Esc => self.modal = None,
+                        _ => {}
+                    }
+                }
+                Modal::NameSearch { query, name } => match key.code {
+                    KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => name.push(c),
+                    KeyCode::Backspace => { name.pop(); }
+                    KeyCode::Enter if !name.trim().is_empty() => {
+                        let (name, query) = (name.trim().to_string(), query.clone());
+                        self.modal = None;
+                        self.searches.remember(&query);
+                        self.searches.saved.insert(name.clone(), query);
+                        match self.searches.save() {
+                            Ok(()) => self.status_message = Some(format!("Saved search '{}' to the sidebar", name)),
+                            Err(e) => self.report_error(e),
+                        }
+                    }
+                    KeyCode::Esc => self.modal = None,
+                    _ => {}
+                },
+                Modal::InsertLink { text, url, editing_url } => {
+                    match key.code {
+                        KeyCode::Tab | KeyCode::BackTab => *editing_url = !*editing_url,
+                        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            if *editing_url { url.push(c) } else { text.push(c) }
+                        }
+                        KeyCode::Backspace => {
+                            if *editing_url { url.pop() } else { text.pop() };
+                        }
+                        KeyCode::Enter => {
+                            let link = format!("[{}]({})", text, url.trim());
+                            self.modal = None;
+                            self.delete_selection();
+                            self.insert_text(&link);
+                            self.ensure_cursor_visible();
+                        }
+                        KeyCode::Esc => self.modal = None,
+                        _ => {}
+                    }
+                }
+                Modal::NotePicker { query, matches, selected, action } => {
+                    match key.code {
+                        KeyCode::Down => *selected = (*selected + 1).min(matches.len().saturating_sub(1)),
+                        KeyCode::Up => *selected = selected.saturating_sub(1),
+                        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            query.push(c);
+                            *matches = Self::picker_matches(&self.notes_tree, &self.notes_dir, &self.link_index, *action, query);
+                            *selected = 0;
+                        }
+                        KeyCode::Backspace => {
+                            query.pop();
+                            *matches = Self::picker_matches(&self.notes_tree, &self.notes_dir, &self.link_index, *action, query);
+                            *selected = 0;
+                        }
+                        KeyCode::Enter => {
+                            let action = *action;
+                            let picked = matches.get(*selected).cloned();
+                            self.modal = None;
+                            if let Some(path) = picked {
+                                self.apply_pick(action, &path)?;
+                            }
+                        }
+                        KeyCode::Esc => self.modal = None,
+                        _ => {}
+                    }
+                }
+                Modal::Find { query, origin } => {
+                    let origin = *origin;
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                    match key.code {
+                        KeyCode::Char('n') if ctrl => self.find_step(true),
+                        KeyCode::Char('p') if ctrl => self.find_step(false),
+                        KeyCode::Down => self.find_step(true),
+                        KeyCode::Up => self.find_step(false),
+                        KeyCode::Char(c) if !ctrl => {
+                            query.push(c);
+                            self.update_find();
+                        }
+                        KeyCode::Backspace => {
+                            query.pop();
+                            self.update_find();
+                        }
+                        KeyCode::Enter => {
+                            self.find_matches.clear();
+                            self.modal = None;
+                        }
+                        KeyCode::Esc => {
+                            (self.cursor_row, self.cursor_col) = origin;
+                            self.find_matches.clear();
+                            self.modal = None;
+                            self.ensure_cursor_visible();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn toggle_macro_recording(&mut self) {
+        match self.recording.take() {
+            Some((reg, keys)) => {
+                self.status_message = Some(format!("Recorded {} keys into @{}", keys.len(), reg));
+                self.macros.insert(reg, keys);
+            }
+            None => self.modal = Some(Modal::MacroRegister { record: true, count: String::new() }),
+        }
+    }
+
+    /// Feeds a recorded macro back through `handle_key` `times` times. A
+    /// macro replaying another one is refused so a self-referencing register
+    /// cannot loop forever; a quit key inside a macro just ends the replay.
+    fn replay_macro(&mut self, reg: char, times: usize) -> Result<()> {
+        let Some(keys) = self.macros.get(&reg).cloned() else {
+            self.status_message = Some(format!("Register @{} is empty", reg));
+            return Ok(());
+        };
+        if self.replaying {
+            self.status_message = Some("Nested macro replay skipped".into());
+            return Ok(());
+        }
+        self.replaying = true;
+        let res = (|| {
+            for _ in 0..times {
+                for &key in &keys {
+                    if self.handle_key(key)? {
+                        return Ok(());
+                    }
+                }
+            }
+            Ok(())
+        })();
+        self.replaying = false;
+        res
+    }
+
+    fn open_note_picker(&mut self, action: PickAction) {
+        self.link_index = LinkIndex::build(&collect_files(&self.notes_tree), &self.notes_dir);
+        let matches = Self::picker_matches(&self.notes_tree, &self.notes_dir, &self.link_index, action, "");
+        self.modal = Some(Modal::NotePicker { query: String::new(), matches, selected: 0, action });
+    }
+
+    /// Ranks notes by their vault path or, when it scores better, one of
+    /// their aliases.
+    fn picker_matches(tree: &NoteNode, root: &Path, index: &LinkIndex, action: PickAction, query: &str) -> Vec<PathBuf> {
+        let mut scored: Vec<(i64, PathBuf)> = collect_files(tree)
+            .into_iter()
+            .filter(|p| match action {
+                PickAction::InsertWikilink | PickAction::CompleteWikilink => links::is_note(p),
+            })
+            .filter_map(|p| {
+                let rel = relative_to(&p, root).to_string_lossy().into_owned();
+                let best = std::iter::once(fuzzy_score(&rel, query))
+                    .chain(index.aliases_of(&p).iter().map(|a| fuzzy_score(a, query)))
+                    .flatten()
+                    .max()?;
+                Some((best, p))
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().take(200).map(|(_, p)| p).collect()
+    }
+
+    /// On a heading, extracts its section; otherwise everything from the
+    /// cursor to the end of the note.
+    fn prompt_extract_from_cursor(&mut self) {
+        if self.read_only {
+            self.status_message = Some("Read-only: the vault is open in another instance".into());
+            return;
+        }
+        let headings = markdown::headings(&self.lines);
+        let last = self.lines.len() - 1;
+        let end_of_note = (last, self.lines[last].len());
+        let (name, from, to) = match headings.iter().position(|h| h.row == self.cursor_row) {
+            Some(idx) => {
+                let end = markdown::section_end(&headings, idx, self.lines.len());
+                let to = if end == self.lines.len() { end_of_note } else { (end, 0) };
+                (headings[idx].title.replace(['/', '\\', '.'], ""), (self.cursor_row, 0), to)
+            }
+            None => (String::new(), (self.cursor_row, self.cursor_col), end_of_note),
+        };
+        if from == to {
+            self.status_message = Some("Nothing to extract below the cursor".into());
+            return;
+        }
+        self.modal = Some(Modal::ExtractNote { name, from, to, open: false });
+    }
+
+    /// Suggests a name from the selection's first line, minus heading and
+    /// list markers.
+    fn prompt_extract_selection(&mut self) {
+        if self.read_only {
+            self.status_message = Some("Read-only: the vault is open in another instance".into());
+            return;
+        }
+        let Some((from, to)) = self.selection_range() else {
+            self.status_message = Some("Select the text to extract first".into());
+            return;
+        };
+        let first = self.selected_text().unwrap_or_default();
+        let first = first.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or_default();
+        let first = first.trim_start_matches(['#', '>', '-', '*', '+', ' ']).trim_start_matches("[ ] ").trim_start_matches("[x] ");
+        let name: String = first.chars().filter(|c| !matches!(c, '/' | '\\' | '.' | ':' | '[' | ']')).take(60).collect();
+        self.modal = Some(Modal::ExtractNote { name: name.trim().to_string(), from, to, open: true });
+    }
+
+    /// Writes the text between `from` and `to` to `<name>.md` next to the
+    /// open note and replaces it with `[[name]]`.
+    fn extract_to_note(&mut self, name: &str, from: (usize, usize), to: (usize, usize), open: bool) -> Result<()> {
+        let dir = self
+            .opened_path
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .or_else(|| self.new_note_dir.clone())
+            .unwrap_or_else(|| self.notes_dir.clone());
+        let path = dir.join(format!("{}.md", name));
+        if path.exists() {
+            self.status_message = Some(format!("{}.md already exists", name));
+            return Ok(());
+        }
+        self.block_anchor = None;
+        self.selection_anchor = Some(from);
+        (self.cursor_row, self.cursor_col) = to;
+        let text = self.selected_text().unwrap_or_default();
+        // A range ending at a line start took the line break before the
+        // next section with it; put one back after the link.
+        let ends_at_line_start = to.1 == 0 && to.0 > from.0;
+        let body = if ends_at_line_start { text.strip_suffix('\n').unwrap_or(&text) } else { &text };
+        write_note(&path, body.trim_start_matches('\n'))?;
+        self.delete_selection();
+        let link = format!("[[{}]]{}", name, if ends_at_line_start { "\n" } else { "" });
+        self.insert_text(&link);
+        if ends_at_line_start {
+            self.cursor_row -= 1;
+            self.cursor_col = self.lines[self.cursor_row].len();
+        }
+        self.ensure_cursor_visible();
+        if self.opened_path.is_some() {
+            self.save_current()?;
+        }
+        self.rescan_sidebar_dir(&dir);
+        self.git_section.request_refresh();
+        let rows = body.lines().count();
+        self.status_message = Some(format!("Moved {} line{} to {}", rows, if rows == 1 { "" } else { "s" }, name));
+        if open && self.open_file(&path, None)? {
+            self.focus = Focus::Content;
+            self.last_right_focus = RightFocus::Content;
+        }
+        Ok(())
+    }
+
+    fn open_link_report(&mut self) {
+        let files = collect_files(&self.notes_tree);
+        self.link_index = LinkIndex::build(&files, &self.notes_dir);
+        let issues = links::diagnose(&files, &self.link_index);
+        if issues.is_empty() {
+            self.modal = None;
+            self.status_message = Some("No broken links or orphaned notes".into());
+        } else {
+            self.modal = Some(Modal::LinkReport { issues, selected: 0 });
+        }
+    }
+
+    /// Lists the remotes of the notes repo. Without any, goes straight to
+    /// adding `origin`.
+    fn open_remotes(&mut self) -> Result<()> {
+        if self.git_section.repo_root.is_none() {
+            self.status_message = Some(self.git_section.empty_reason());
+            return Ok(());
+        }
+        let remotes = self.git_section.remotes()?;
+        let form = remotes.is_empty().then(|| ("origin".to_string(), String::new(), true));
+        self.modal = Some(Modal::Remotes { remotes, selected: 0, form });
+        Ok(())
+    }
+
+    /// Lists the open note's attachments, or with `vault` every attachment
+    /// no note links to.
+    fn open_attachments(&mut self, vault: bool) {
+        let files = collect_files(&self.notes_tree);
+        let items: Vec<Attachment> = if vault {
+            attachments::orphans(&files, &self.notes_dir)
+                .into_iter()
+                .map(|path| Attachment { path, state: attachments::State::Unreferenced })
+                .collect()
+        } else {
+            let Some(note) = self.opened_path.clone() else {
+                self.status_message = Some("Open a saved note to see its attachments".into());
+                return;
+            };
+            attachments::for_note(&note, &self.lines.join("\n"), &files, &self.notes_dir)
+        };
+        if items.is_empty() {
+            self.modal = None;
+            self.status_message = Some(if vault { "No orphaned attachments" } else { "This note has no attachments" }.into());
+        } else {
+            self.modal = Some(Modal::Attachments { items, selected: 0, vault, renaming: None });
+        }
+    }
+
+    /// Copies `source` next to the open note, or into its `assets/`, and
+    /// links it at the cursor.
+    fn import_file(&mut self, source: &Path, to_assets: bool) -> Result<()> {
+        let note_dir = self
+            .opened_path
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .or_else(|| self.new_note_dir.clone())
+            .unwrap_or_else(|| self.notes_dir.clone());
+        let dir = if to_assets { note_dir.join(attachments::ASSETS) } else { note_dir.clone() };
+        let copied = attachments::import(source, &dir)?;
+        self.selection_anchor = None;
+        self.insert_text(&attachments::link_to(&copied, &note_dir));
+        self.ensure_cursor_visible();
+        self.focus = Focus::Content;
+        self.rescan_sidebar_dir(&note_dir);
+        self.status_message = Some(format!("Imported {}", relative_to(&copied, &self.notes_dir).display()));
+        Ok(())
+    }
+
+    /// Renames `old` within its folder and repoints links to it, in the
+    /// buffer and in every other note.
+    fn rename_attachment(&mut self, old: &Path, name: &str) -> Result<()> {
+        let new = old.with_file_name(name);
+        if new.exists() {
+            bail!("{} already exists", new.display());
+        }
+        std::fs::rename(old, &new).with_context(|| format!("Renaming {}", old.display()))?;
+        let note = self.opened_path.clone();
+        let own = match &note {
+            Some(note) => attachments::rewrite(&mut self.lines, note, old, &new, &self.notes_dir),
+            None => 0,
+        };
+        if own > 0 {
+            self.dirty = true;
+        }
+        let files = collect_files(&self.notes_tree);
+        let (links, notes) = attachments::rewrite_vault(&files, note.as_deref(), old, &new, &self.notes_dir)?;
+        if let Some(parent) = new.parent() {
+            self.rescan_sidebar_dir(parent);
+        }
+        let vault = matches!(self.modal, Some(Modal::Attachments { vault: true, .. }));
+        self.open_attachments(vault);
+        self.status_message = Some(format!("Renamed to {}; updated {} links in {} other notes", name, own + links, notes));
+        self.git_section.request_refresh();
+        Ok(())
+    }
+
+    fn jump_to_issue(&mut self, issue: &LinkIssue) -> Result<()> {
+        if self.dirty && !self.read_only {
+            self.save_current()?;
+        }
+        if !self.open_file(issue.path(), None)? {
+            return Ok(());
+        }
+        self.focus = Focus::Content;
+        self.last_right_focus = RightFocus::Content;
+        if let LinkIssue::Broken { row, col, .. } = issue {
+            self.cursor_row = (*row).min(self.lines.len() - 1);
+            self.cursor_col = clamp_to_char_boundary(&self.lines[self.cursor_row], *col);
+            self.scroll_y = self.cursor_row.saturating_sub(10);
+            self.ensure_cursor_visible();
+        }
+        Ok(())
+    }
+
+    /// Opens the note the wikilink under the cursor points at, by file name,
+    /// vault path or frontmatter alias, or jumps to the heading of this note
+    /// that a `[[#Heading]]` or `[#Heading]` link names. On a `[^1]`
+    /// footnote it goes to the definition, and from there back.
+    fn follow_link(&mut self) -> Result<()> {
+        let line = &self.lines[self.cursor_row];
+        if let Some(f) = markdown::footnotes(line).into_iter().find(|f| (f.start..=f.end).contains(&self.cursor_col)) {
+            match markdown::footnote_jump(&self.lines, self.cursor_row, self.cursor_col) {
+                Some((row, col)) => {
+                    self.selection_anchor = None;
+                    self.cursor_row = row;
+                    self.cursor_col = col;
+                    self.ensure_cursor_visible();
+                }
+                None if f.definition => self.status_message = Some(format!("Nothing refers to [^{}]", f.label)),
+                None => self.status_message = Some(format!("No definition for [^{}]", f.label)),
+            }
+            return Ok(());
+        }
+        if let Some((_, _, heading)) = links::heading_links(line).into_iter().find(|(start, end, _)| (*start..=*end).contains(&self.cursor_col)) {
+            self.jump_to_heading(&heading);
+            return Ok(());
+        }
+        let Some(link) = links::wikilinks(line).into_iter().find(|l| (l.start..=l.end).contains(&self.cursor_col)) else {
+            self.status_message = Some("No [[link]] under the cursor".into());
+            return Ok(());
+        };
+        self.link_index = LinkIndex::build(&collect_files(&self.notes_tree), &self.notes_dir);
+        let Some(path) = self.link_index.resolve(&link.target).cloned() else {
+            self.status_message = Some(format!("No note named '{}'", link.target));
+            return Ok(());
+        };
+        if self.dirty && !self.read_only {
+            self.save_current()?;
+        }
+        if !self.open_file(&path, link.heading.map(Anchor::Heading))? {
+            return Ok(());
+        }
+        self.focus = Focus::Content;
+        self.last_right_focus = RightFocus::Content;
+        Ok(())
+    }
+
+    fn apply_pick(&mut self, action: PickAction, path: &Path) -> Result<()> {
+        match action {
+            PickAction::InsertWikilink => {
+                let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+                self.delete_selection();
+                self.insert_text(&format!("[[{}]]", name));
+                self.ensure_cursor_visible();
+            }
+            PickAction::CompleteWikilink => {
+                let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+                self.insert_text(name);
+                // Auto-pair may already have closed the brackets.
+                if self.lines[self.cursor_row][self.cursor_col..].starts_with("]]") {
+                    self.cursor_col += 2;
+                } else {
+                    self.insert_text("]]");
+                }
+                self.ensure_cursor_visible();
+            }
+        }
+        Ok(())
+    }
+
+    fn run_vault_search(tree: &NoteNode, query: &str, options: SearchOptions) -> Vec<SearchHit> {
+        if query.chars().count() < 2 {
+            return Vec::new();
+        }
+        match Matcher::new(query, options) {
+            Ok(matcher) => search_vault(&collect_files(tree), &matcher, 500),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Flips a search toggle for Alt+R (regex), Alt+C (case) or Alt+W
+    /// (whole word) and remembers it; returns the new options.
+    fn toggle_search_option(&mut self, key: KeyEvent) -> Option<SearchOptions> {
+        if key.modifiers != KeyModifiers::ALT {
+            return None;
+        }
+        let options = &mut self.searches.options;
+        match key.code {
+            KeyCode::Char('r') => options.regex = !options.regex,
+            KeyCode::Char('c') => options.case_sensitive = !options.case_sensitive,
+            KeyCode::Char('w') => options.whole_word = !options.whole_word,
+            _ => return None,
+        }
+        let options = *options;
+        if let Err(e) = self.searches.save() {
+            self.report_error(e);
+        }
+        Some(options)
+    }
+
+    /// Matches of `query` in the open note with the current options; an
+    /// invalid regex matches nothing.
+    fn note_matches(&self, query: &str) -> Vec<(usize, usize, usize)> {
+        Matcher::new(query, self.searches.options).map_or_else(|_| Vec::new(), |m| find_in_lines(&self.lines, &m))
+    }
+
+    /// Saves the open note first so the replace sees what is on screen.
+    fn open_replace(&mut self) -> Result<()> {
+        if self.read_only {
+            self.status_message = Some("Read-only: the vault is open in another instance".into());
+            return Ok(());
+        }
+        if self.dirty {
+            self.save_current()?;
+        }
+        self.modal = Some(Modal::Replace {
+            pattern: String::new(),
+            with: String::new(),
+            editing_with: false,
+            matches: Vec::new(),
+            selected: 0,
+            previewing: false,
+            commit: false,
+        });
+        Ok(())
+    }
+
+    fn apply_replace(&mut self, matches: &[ReplaceMatch], commit_message: Option<&str>) -> Result<()> {
+        let (changed, written) = replace::apply(matches)?;
+        if let Some(path) = self.opened_path.clone()
+            && changed.contains(&path)
+        {
+            let (row, col) = (self.cursor_row, self.cursor_col);
+            if self.open_file(&path, None)? {
+                self.cursor_row = row.min(self.lines.len() - 1);
+                self.cursor_col = clamp_to_char_boundary(&self.lines[self.cursor_row], col);
+                self.ensure_cursor_visible();
+            }
+        }
+        if let Some(message) = commit_message
+            && !changed.is_empty()
+        {
+            self.git_section.commit_paths(message, &changed);
+        } else {
+            self.git_section.request_refresh();
+        }
+        let skipped = matches.iter().filter(|m| m.accepted).count() - written;
+        self.status_message = Some(if skipped > 0 {
+            format!("Replaced {} matches in {} notes; skipped {} that changed on disk", written, changed.len(), skipped)
+        } else {
+            format!("Replaced {} matches in {} notes", written, changed.len())
+        });
+        Ok(())
+    }
+
+    /// Opens the vault search, running `query` right away when given.
+    fn open_vault_search(&mut self, query: String) {
+        let hits = Self::run_vault_search(&self.notes_tree, &query, self.searches.options);
+        self.modal = Some(Modal::VaultSearch { query, hits, selected: 0, history_pos: None });
+    }
+
+    /// Opens a vault-search result with the cursor on the hit, the hit line
+    /// centred, and every match of the query in that note highlighted.
+    fn open_search_hit(&mut self, hit: &SearchHit, query: &str) -> Result<()> {
+        if !self.open_file(&hit.path, None)? {
+            return Ok(());
+        }
+        self.focus = Focus::Content;
+        self.last_right_focus = RightFocus::Content;
+        self.cursor_row = hit.row.min(self.lines.len() - 1);
+        self.cursor_col = hit.start;
+        self.scroll_y = self.cursor_row.saturating_sub(10);
+        self.find_matches = self.note_matches(query);
+        self.ensure_cursor_visible();
+        Ok(())
+    }
+
+    /// Recomputes matches for the current query and jumps to the first one
+    /// at or after where the search started, wrapping around.
+    fn update_find(&mut self) {
+        let Some(Modal::Find { query, origin }) = &self.modal else { return };
+        self.find_matches = self.note_matches(query);
+        let origin = *origin;
+        let target = self
+            .find_matches
+            .iter()
+            .find(|(r, s, _)| (*r, *s) >= origin)
+            .or(self.find_matches.first())
+            .map(|(r, s, _)| (*r, *s));
+        (self.cursor_row, self.cursor_col) = target.unwrap_or(origin);
+        self.ensure_cursor_visible();
+    }
+
+    fn find_step(&mut self, forward: bool) {
+        if self.find_matches.is_empty() {
+            return;
+        }
+        let cur = (self.cursor_row, self.cursor_col);
+        let next = if forward {
+            self.find_matches.iter().find(|(r, s, _)| (*r, *s) > cur).or(self.find_matches.first())
+        } else {
+            self.find_matches.iter().rev().find(|(r, s, _)| (*r, *s) < cur).or(self.find_matches.last())
+        };
+        if let Some(&(r, s, _)) = next {
+            self.cursor_row = r;
+            self.cursor_col = s;
+            self.ensure_cursor_visible();
+        }
+    }
+
+    /// 1-based index of the match under the cursor, for the find prompt.
+    pub fn find_position(&self) -> Option<usize> {
+        self.find_matches
+            .iter()
+            .position(|(r, s, _)| (*r, *s) == (self.cursor_row, self.cursor_col))
+            .map(|i| i + 1)
+    }
+
+    fn type_char(&mut self, c: char) {
+        let line = &self.lines[self.cursor_row];
+        let col = clamp_to_char_boundary(line, self.cursor_col);
+        let next = line[col..].chars().next();
+        if self.auto_pair {
+            // Typing the closer right before an identical one just steps over it.
+            if next == Some(c) && is_closer(c) {
+                self.cursor_col = col + c.len_utf8();
+                return;
+            }
+            let before = &line[..col];
+            let bullet = c == '*' && before.trim().is_empty();
+            let next_is_word = next.is_some_and(|n| n.is_alphanumeric());
+            if let Some(close) = closing_pair(c)
+                && !bullet
+                && !next_is_word
+            {
+                self.lines[self.cursor_row].insert(col, close);
+                self.lines[self.cursor_row].insert(col, c);
+                self.cursor_col = col + c.len_utf8();
+                self.dirty = true;
+                return;
+            }
+        }
+        self.lines[self.cursor_row].insert(col, c);
+        self.cursor_col = col + c.len_utf8();
+        self.dirty = true;
+        if self.auto_wrap && c != ' ' {
+            self.hard_wrap_line();
+        }
+    }
+
+    /// Moves the words past `text_width` on the cursor line to a new line
+    /// with the paragraph's continuation prefix.
+    fn hard_wrap_line(&mut self) {
+        let row = self.cursor_row;
+        let Some(at) = format::wrap_point(&self.lines[row], self.text_width) else { return };
+        let (_, cont) = format::wrap_prefixes(&self.lines[row]);
+        let tail = self.lines[row].split_off(at);
+        let trimmed = self.lines[row].trim_end().len();
+        self.lines[row].truncate(trimmed);
+        let spaces = tail.len() - tail.trim_start().len();
+        if self.cursor_col > at {
+            self.cursor_row += 1;
+            self.cursor_col = cont.len() + (self.cursor_col - at).saturating_sub(spaces);
+        }
+        self.lines.insert(row + 1, format!("{}{}", cont, tail.trim_start()));
+    }
+
+    /// Moves to line `N` (1-based) or `N%` of the note, clamped, with the
+    /// target line centred.
+    fn goto_line(&mut self, input: &str) {
+        let last = self.lines.len() - 1;
+        let row = match input.strip_suffix('%') {
+            Some(pct) => pct.parse::<usize>().ok().map(|p| (last * p.min(100)).div_ceil(100)),
+            None => input.parse::<usize>().ok().map(|n| n.saturating_sub(1).min(last)),
+        };
+        let Some(row) = row else { return };
+        self.selection_anchor = None;
+        self.cursor_row = row;
+        self.cursor_col = 0;
+        self.scroll_y = row.saturating_sub(10);
+        self.focus = Focus::Content;
+        self.last_right_focus = RightFocus::Content;
+        self.ensure_cursor_visible();
+        self.status_message = Some(format!("Line {} of {}", row + 1, last + 1));
+    }
+
+    fn prompt_insert_date(&mut self) {
+        match self.date_formats.as_slice() {
+            [] => self.status_message = Some("No date_formats configured".into()),
+            [only] => {
+                let fmt = only.clone();
+                self.insert_date(&fmt);
+            }
+            _ => self.modal = Some(Modal::InsertDate { selected: 0 }),
+        }
+    }
+
+    fn insert_date(&mut self, fmt: &str) {
+        let text = datefmt::strftime(datefmt::now(), fmt);
+        self.focus = Focus::Content;
+        self.last_right_focus = RightFocus::Content;
+        self.delete_selection();
+        self.insert_text(&text);
+        self.ensure_cursor_visible();
+    }
+
+    /// Converts the selection, or the word under the cursor, and leaves
+    /// the result selected.
+    fn convert_case(&mut self, case: Case) {
+        let start = match self.selection_range() {
+            Some((start, _)) => start,
+            None => {
+                let line = &self.lines[self.cursor_row];
+                let col = clamp_to_char_boundary(line, self.cursor_col);
+                let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+                let start = line[..col].char_indices().rev().find(|&(_, c)| !is_word(c)).map_or(0, |(i, c)| i + c.len_utf8());
+                let end = line[col..].find(|c| !is_word(c)).map_or(line.len(), |i| col + i);
+                if start == end {
+                    self.status_message = Some("No word under the cursor".into());
+                    return;
+                }
+                self.selection_anchor = Some((self.cursor_row, start));
+                self.cursor_col = end;
+                (self.cursor_row, start)
+            }
+        };
+        let Some(text) = self.selected_text() else { return };
+        let converted = case.apply(&text);
+        if converted != text {
+            self.delete_selection();
+            self.insert_text(&converted);
+        }
+        self.selection_anchor = Some(start);
+        self.focus = Focus::Content;
+        self.last_right_focus = RightFocus::Content;
+        self.status_message = Some(format!("Converted to {}", case.label()));
+        self.ensure_cursor_visible();
+    }
+
+    /// Rewraps the selected lines, or the paragraph under the cursor, to
+    /// `text_width`.
+    fn reflow(&mut self) {
+        let (start, end) = match self.selected_rows() {
+            Some(rows) => (*rows.start(), *rows.end()),
+            None => match format::paragraph_bounds(&self.lines, self.cursor_row) {
+                Some(rows) => rows,
+                None => {
+                    self.status_message = Some("No paragraph under the cursor".into());
+                    return;
+                }
+            },
+        };
+        let wrapped = format::reflow_paragraphs(&self.lines, start, end, self.text_width);
+        let last = start + wrapped.len() - 1;
+        if wrapped[..] != self.lines[start..=end] {
+            self.lines.splice(start..=end, wrapped);
+            self.dirty = true;
+        }
+        self.selection_anchor = None;
+        self.find_matches.clear();
+        self.cursor_row = last;
+        self.cursor_col = self.lines[last].len();
+        self.focus = Focus::Content;
+        self.last_right_focus = RightFocus::Content;
+        self.ensure_cursor_visible();
+    }
+
+    fn cursor_in_empty_pair(&self) -> bool {
+        let line = &self.lines[self.cursor_row];
+        let col = self.cursor_col;
+        if col == 0 || col >= line.len() || !line.is_char_boundary(col) {
+            return false;
+        }
+        let (Some(open), Some(close)) = (line[..col].chars().next_back(), line[col..].chars().next()) else {
+            return false;
+        };
+        closing_pair(open) == Some(close)
+    }
+
+    /// Rows touched by a multi-line selection, if there is one.
+    fn selected_rows(&self) -> Option<std::ops::RangeInclusive<usize>> {
+        let ((sr, _), (er, _)) = self.selection_range()?;
+        (sr != er).then_some(sr..=er)
+    }
+
+    /// Applies `f` to the selected lines (a block selection counts by rows)
+    /// or to the whole note, and leaves the result selected.
+    fn transform_lines(&mut self, verb: &str, f: impl FnOnce(&mut Vec<String>)) {
+        let rows = self
+            .selected_rows()
+            .or_else(|| self.block_range().map(|(top, bottom, _, _)| top..=bottom))
+            .unwrap_or(0..=self.lines.len() - 1);
+        let (start, end) = (*rows.start(), *rows.end());
+        let mut chunk: Vec<String> = self.lines.drain(rows).collect();
+        let before = chunk.len();
+        f(&mut chunk);
+        let after = chunk.len();
+        if chunk.is_empty() {
+            chunk.push(String::new());
+        }
+        let last = start + chunk.len() - 1;
+        self.lines.splice(start..start, chunk);
+        self.block_anchor = None;
+        self.selection_anchor = Some((start, 0));
+        self.cursor_row = last;
+        self.cursor_col = self.lines[last].len();
+        self.find_matches.clear();
+        self.dirty = true;
+        self.focus = Focus::Content;
+        self.last_right_focus = RightFocus::Content;
+        self.ensure_cursor_visible();
+        let removed = before - after;
+        self.status_message = Some(if removed > 0 {
+            format!("{} lines {}-{}, removed {}", verb, start + 1, end + 1, removed)
+        } else {
+            format!("{} lines {}-{}", verb, start + 1, end + 1)
+        });
+    }
+
+    /// Starts the code block around the cursor with the `run_blocks`
+    /// command for its language, in the note's folder; `finish_code_block`
+    /// writes its output in once it is done.
+    fn run_code_block(&mut self) -> Result<()> {
+        if self.running_block.is_some() {
+            self.status_message = Some("A code block is still running".into());
+            return Ok(());
+        }
+        let Some(block) = markdown::code_block_at(&self.lines, self.cursor_row) else {
+            self.status_message = Some("The cursor is not in a code block".into());
+            return Ok(());
+        };
+        let Some(command) = self.run_blocks.get(&block.lang).cloned() else {
+            self.status_message = Some(format!("Not running '{}' blocks; add them to run_blocks in the config", block.lang));
+            return Ok(());
+        };
+        if block.end == self.lines.len() {
+            self.status_message = Some("The code block is not closed".into());
+            return Ok(());
+        }
+        let dir = self.opened_path.as_deref().and_then(Path::parent).unwrap_or(&self.notes_dir).to_path_buf();
+        let note = self.running_note();
+        let code = self.lines[block.body()].to_vec();
+        self.status_message = Some(format!("Running the {} block...", block.lang));
+        self.running_block = Some(runbook::Job::spawn(command, note, block, code, dir));
+        Ok(())
+    }
+
+    /// The note a code block runs for; None in the scratch buffer.
+    fn running_note(&self) -> Option<PathBuf> {
+        if self.scratch_active { None } else { self.opened_path.clone() }
+    }
+
+    /// Writes the output of a finished code block below it, wherever the
+    /// block has moved to in the meantime.
+    fn finish_code_block(&mut self) {
+        let Some(result) = self.running_block.as_ref().and_then(runbook::Job::finished) else { return };
+        let Some(job) = self.running_block.take() else { return };
+        let (ok, output) = match result {
+            Ok(done) => done,
+            Err(e) => {
+                self.report_error(e);
+                return;
+            }
+        };
+        let block = if job.note == self.running_note() {
+            markdown::code_blocks(&self.lines)
+                .into_iter()
+                .filter(|b| b.lang == job.block.lang && b.end < self.lines.len() && self.lines[b.body()] == job.code[..])
+                .min_by_key(|b| b.start.abs_diff(job.block.start))
+        } else {
+            None
+        };
+        let Some(block) = block else {
+            self.status_message = Some("The block's note changed while it ran; its output was dropped".into());
+            return;
+        };
+        let count = output.len();
+        let before = self.lines.len();
+        runbook::insert_output(&mut self.lines, &block, output);
+        // Keep the cursor on its text when rows below the block moved.
+        if self.cursor_row > block.end {
+            self.cursor_row = (self.cursor_row + self.lines.len()).saturating_sub(before).min(self.lines.len() - 1);
+            self.cursor_col = clamp_to_char_boundary(&self.lines[self.cursor_row], self.cursor_col);
+        }
+        self.dirty = true;
+        self.selection_anchor = None;
+        self.find_matches.clear();
+        self.ensure_cursor_visible();
+        self.status_message = Some(format!("{} ({} lines of output)", if ok { "Ran the block" } else { "The block failed" }, count));
+    }
+
+    /// Replaces the buffer with its formatted version, keeping the cursor
+    /// on the same row where it still exists.
+    fn format_note(&mut self) -> Result<()> {
+        let formatted = match &self.formatter {
+            Some(command) => format::run_external(command, &self.lines)?,
+            None => format::format_markdown(&self.lines),
+        };
+        if formatted == self.lines {
+            return Ok(());
+        }
+        self.lines = formatted;
+        self.cursor_row = self.cursor_row.min(self.lines.len() - 1);
+        self.cursor_col = clamp_to_char_boundary(&self.lines[self.cursor_row], self.cursor_col);
+        self.selection_anchor = None;
+        self.block_anchor = None;
+        self.find_matches.clear();
+        self.dirty = true;
+        self.ensure_cursor_visible();
+        Ok(())
+    }
+
+    fn indent(&mut self) {
+        let unit = match self.tab_mode {
+            TabMode::Tab => "\t".to_string(),
+            _ => " ".repeat(self.tab_width),
+        };
+        if let Some(rows) = self.selected_rows() {
+            for row in rows {
+                self.lines[row].insert_str(0, &unit);
+            }
+            self.cursor_col += unit.len();
+            if let Some((_, col)) = &mut self.selection_anchor {
+                *col += unit.len();
+            }
+            self.dirty = true;
+            return;
+        }
+        self.delete_selection();
+        let text = match self.tab_mode {
+            TabMode::Tab => unit,
+            _ => " ".repeat(self.tab_width - self.cursor_display_col() % self.tab_width),
+        };
+        self.insert_text(&text);
+    }
+
+    /// Removes one indent level (a tab or up to `tab_width` spaces) from the
+    /// current line or every selected line.
+    fn dedent(&mut self) {
+        let rows = self.selected_rows().unwrap_or(self.cursor_row..=self.cursor_row);
+        for row in rows {
+            let line = &mut self.lines[row];
+            let n = if line.starts_with('\t') {
+                1
+            } else {
+                line.chars().take(self.tab_width).take_while(|c| *c == ' ').count()
+            };
+            if n == 0 {
+                continue;
+            }
+            line.drain(..n);
+            if row == self.cursor_row {
+                self.cursor_col = self.cursor_col.saturating_sub(n);
+            }
+            if let Some((r, col)) = &mut self.selection_anchor
+                && *r == row
+            {
+                *col = col.saturating_sub(n);
+            }
+            self.dirty = true;
+        }
+    }
+
+    /// Keys while a block selection is active. Motions extend it; returns
+    /// false for keys that end it and should be handled as usual.
+    fn handle_block_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down | KeyCode::Home | KeyCode::End => {
+                // Let the normal motion run without dropping the block.
+                self.selection_anchor = None;
+                return false;
+            }
+            KeyCode::Esc => self.block_anchor = None,
+            KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::ALT) => self.block_anchor = None,
+            KeyCode::Char('c') if ctrl => {
+                self.copy_block();
+                self.block_anchor = None;
+            }
+            KeyCode::Char('x') if ctrl => {
+                self.copy_block();
+                self.delete_block();
+                self.block_anchor = None;
+            }
+            KeyCode::Backspace | KeyCode::Delete => {
+                let Some((top, bottom, left, right)) = self.block_range() else { return false };
+                if left == right {
+                    // Zero-width block: delete one column beside it on every line.
+                    let (left, right) = if key.code == KeyCode::Backspace {
+                        (left.saturating_sub(1), left)
+                    } else {
+                        (left, left + 1)
+                    };
+                    self.set_block(top, bottom, left, right);
+                }
+                self.delete_block();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                let Some((top, bottom, left, _)) = self.block_range() else { return false };
+                self.delete_block();
+                for row in top..=bottom {
+                    let line = &mut self.lines[row];
+                    let width = line.chars().count();
+                    if width < left {
+                        line.extend(std::iter::repeat_n(' ', left - width));
+                    }
+                    let at = char_to_byte(line, left);
+                    line.insert(at, c);
+                }
+                self.set_block(top, bottom, left + 1, left + 1);
+                self.dirty = true;
+            }
+            _ => {
+                self.block_anchor = None;
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Rows and char columns `(top, bottom, left, right)` of the block
+    /// selection, `right` exclusive.
+    pub fn block_range(&self) -> Option<(usize, usize, usize, usize)> {
+        let (row, col) = self.block_anchor.filter(|&(row, _)| row < self.lines.len())?;
+        let cursor_col = char_col(&self.lines[self.cursor_row], self.cursor_col);
+        Some((row.min(self.cursor_row), row.max(self.cursor_row), col.min(cursor_col), col.max(cursor_col)))
+    }
+
+    /// Moves the block to the given rectangle, anchored top-left with the
+    /// cursor bottom-right.
+    fn set_block(&mut self, top: usize, bottom: usize, left: usize, right: usize) {
+        self.block_anchor = Some((top, left));
+        self.cursor_row = bottom;
+        self.cursor_col = char_to_byte(&self.lines[bottom], right);
+    }
+
+    fn copy_block(&mut self) {
+        let Some((top, bottom, left, right)) = self.block_range() else { return };
+        let text = self.lines[top..=bottom]
+            .iter()
+            .map(|line| &line[char_to_byte(line, left)..char_to_byte(line, right)])
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.copy_to_clipboard(&text);
+        self.clipboard.block = true;
+    }
+
+    /// Removes the block's columns from every row and collapses it to zero
+    /// width at its left edge.
+    fn delete_block(&mut self) {
+        let Some((top, bottom, left, right)) = self.block_range() else { return };
+        if left == right {
+            return;
+        }
+        for line in &mut self.lines[top..=bottom] {
+            let range = char_to_byte(line, left)..char_to_byte(line, right);
+            line.replace_range(range, "");
+        }
+        self.set_block(top, bottom, left, left);
+        self.dirty = true;
+    }
+
+    /// Pastes a block yank as a column: line `i` of `text` goes into row
+    /// `cursor_row + i` at the cursor's column, padding short rows.
+    fn paste_block(&mut self, text: &str) {
+        let left = char_col(&self.lines[self.cursor_row], self.cursor_col);
+        for (i, part) in text.split('\n').enumerate() {
+            let row = self.cursor_row + i;
+            if row == self.lines.len() {
+                self.lines.push(String::new());
+            }
+            let line = &mut self.lines[row];
+            let width = line.chars().count();
+            if width < left {
+                line.extend(std::iter::repeat_n(' ', left - width));
+            }
+            let at = char_to_byte(line, left);
+            line.insert_str(at, part);
+        }
+        self.selection_anchor = None;
+        self.dirty = true;
+    }
+
+    pub fn selection_range(&self) -> Option<((usize, usize), (usize, usize))> {
+        let anchor = self.selection_anchor?;
+        let cursor = (self.cursor_row, self.cursor_col);
+        if anchor == cursor {
+            return None;
+        }
+        Some(if anchor < cursor { (anchor, cursor) } else { (cursor, anchor) })
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        let ((sr, sc), (er, ec)) = self.selection_range()?;
+        let mut out = String::new();
+        for row in sr..=er {
+            let line = &self.lines[row];
+            let start = if row == sr { clamp_to_char_boundary(line, sc) } else { 0 };
+            let end = if row == er { clamp_to_char_boundary(line, ec) } else { line.len() };
+            out.push_str(&line[start..end.max(start)]);
+            if row != er {
+                out.push('\n');
+            }
+        }
+        Some(out)
+    }
+
+    /// Removes the selected text and leaves the cursor at its start.
+    /// Returns whether anything was removed.
+    fn delete_selection(&mut self) -> bool {
+        let Some(((sr, sc), (er, ec))) = self.selection_range() else {
+            self.selection_anchor = None;
+            return false;
+        };
+        let sc = clamp_to_char_boundary(&self.lines[sr], sc);
+        let ec = clamp_to_char_boundary(&self.lines[er], ec);
+        let tail = self.lines[er][ec..].to_string();
+        self.lines[sr].truncate(sc);
+        self.lines[sr].push_str(&tail);
+        self.lines.drain(sr + 1..=er);
+        self.cursor_row = sr;
+        self.cursor_col = sc;
+        self.selection_anchor = None;
+        self.dirty = true;
+        true
+    }
+
+    /// Inserts possibly multi-line text at the cursor, leaving the cursor after it.
+    fn insert_text(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let col = clamp_to_char_boundary(&self.lines[self.cursor_row], self.cursor_col);
+        let tail = self.lines[self.cursor_row].split_off(col);
+        let mut parts = text.split('\n');
+        if let Some(first) = parts.next() {
+            self.lines[self.cursor_row].push_str(first);
+        }
+        for part in parts {
+            self.cursor_row += 1;
+            self.lines.insert(self.cursor_row, part.to_string());
+        }
+        self.cursor_col = self.lines[self.cursor_row].len();
+        self.lines[self.cursor_row].push_str(&tail);
+        self.dirty = true;
+    }
+
+    /// Pasting a URL over a single-line selection turns it into `[selection](url)`.
+    fn paste_text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if looks_like_url(text)
+            && let Some(selected) = self.selected_text()
+            && !selected.contains('\n')
+        {
+            self.delete_selection();
+            self.insert_text(&format!("[{}]({})", selected, text.trim()));
+            self.status_message = Some("Linked selection".to_string());
+        } else {
+            self.delete_selection();
+            self.insert_text(text);
+        }
+        self.ensure_cursor_visible();
+    }
+
+    fn handle_paste(&mut self, text: &str) {
+        // Never fall through to the note behind an open modal.
+        if self.modal.is_some() {
+            self.paste_into_modal(text.lines().next().unwrap_or_default());
+            return;
+        }
+        match self.focus {
+            Focus::Content => {
+                self.paste_text(text);
+                self.heading_to_title();
+            }
+            Focus::Title => {
+                let clean: String = text
+                    .chars()
+                    .filter(|c| !matches!(c, '/' | '\\' | '.' | '\n' | '\r'))
+                    .collect();
+                self.title.insert_str(self.title_cursor, &clean);
+                self.title_cursor += clean.len();
+                self.dirty = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Appends a pasted line to the text field of the open modal and
+    /// refreshes what depends on it; modals without one ignore pastes.
+    fn paste_into_modal(&mut self, line: &str) {
+        let Some(modal) = &mut self.modal else { return };
+        let field = match modal {
+            Modal::InputName { current: field, .. }
+            | Modal::CommitMessage { message: field, .. }
+            | Modal::ExtractNote { name: field, .. }
+            | Modal::Passphrase { input: field, .. }
+            | Modal::NameSearch { name: field, .. }
+            | Modal::GotoLine { input: field }
+            | Modal::ImportFile { input: field, .. }
+            | Modal::Batch { input: field, confirming: false, .. }
+            | Modal::Attachments { renaming: Some(field), .. }
+            | Modal::Find { query: field, .. }
+            | Modal::VaultSearch { query: field, .. }
+            | Modal::NotePicker { query: field, .. } => field,
+            Modal::CommitForm { fields, field } => &mut fields[*field],
+            Modal::InsertLink { text, url, editing_url } => if *editing_url { url } else { text },
+            Modal::Remotes { form: Some((name, url, editing_url)), .. } => if *editing_url { url } else { name },
+            Modal::Replace { pattern, with, editing_with, previewing: false, .. } => if *editing_with { with } else { pattern },
+            _ => return,
+        };
+        field.push_str(line);
+        match &mut self.modal {
+            Some(Modal::Find { .. }) => self.update_find(),
+            Some(Modal::VaultSearch { query, hits, selected, .. }) => {
+                *hits = Self::run_vault_search(&self.notes_tree, query, self.searches.options);
+                *selected = 0;
+            }
+            Some(Modal::NotePicker { query, matches, selected, action }) => {
+                *matches = Self::picker_matches(&self.notes_tree, &self.notes_dir, &self.link_index, *action, query);
+                *selected = 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str) {
+        match self.clipboard.copy(text) {
+            Ok(target) => self.status_message = Some(format!("Copied {} chars to {}", text.chars().count(), target)),
+            Err(e) => self.report_error(e.context("Copy failed")),
+        }
+    }
+
+    /// Records the inner size of the Content pane; a resize scrolls the
+    /// cursor back into view.
+    pub fn set_content_size(&mut self, width: usize, height: usize) {
+        if (width, height) != (self.content_width, self.content_height) {
+            self.content_width = width;
+            self.content_height = height;
+            self.ensure_cursor_visible();
+        }
+    }
+
+    fn ensure_cursor_visible(&mut self) {
+        let window = self.content_height.max(1);
+        // Anything that lands the cursor inside a folded section opens it.
+        let folds = loop {
+            let folds = self.fold_ranges();
+            match folds.iter().find(|&&f| markdown::in_fold(f, self.cursor_row)) {
+                Some(&(start, _)) => {
+                    let key = self.lines[start].clone();
+                    self.folded.remove(&key);
+                }
+                None => break folds,
+            }
+        };
+        if let Some(&(start, _)) = folds.iter().find(|&&f| markdown::in_fold(f, self.scroll_y)) {
+            self.scroll_y = start;
+        }
+        if self.cursor_row < self.scroll_y {
+            self.scroll_y = self.cursor_row;
+        } else {
+            while self.screen_row_with(&folds, self.cursor_row) >= window {
+                self.scroll_y += 1;
+                while folds.iter().any(|&f| markdown::in_fold(f, self.scroll_y)) {
+                    self.scroll_y += 1;
+                }
+            }
+        }
+        if self.wrap {
+            self.scroll_x = 0;
+            return;
+        }
+        let col = self.cursor_display_col();
+        let width = self.content_width.max(1);
+        if col < self.scroll_x {
+            self.scroll_x = col;
+        } else if col >= self.scroll_x + width {
+            self.scroll_x = col + 1 - width;
+        }
+    }
+
+    /// Folded sections as `(heading_row, end_row)`, outermost only.
+    pub fn fold_ranges(&self) -> Vec<(usize, usize)> {
+        if self.folded.is_empty() {
+            return Vec::new();
+        }
+        let headings = markdown::headings(&self.lines);
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for (i, h) in headings.iter().enumerate() {
+            if !self.folded.contains(&self.lines[h.row]) || ranges.last().is_some_and(|&(_, end)| h.row < end) {
+                continue;
+            }
+            ranges.push((h.row, markdown::section_end(&headings, i, self.lines.len())));
+        }
+        ranges
+    }
+
+    /// Screen row of buffer row `row` below `scroll_y`, skipping folded lines.
+    pub fn screen_row(&self, row: usize) -> usize {
+        self.screen_row_with(&self.fold_ranges(), row)
+    }
+
+    fn screen_row_with(&self, folds: &[(usize, usize)], row: usize) -> usize {
+        (self.scroll_y..row)
+            .filter(|&r| !folds.iter().any(|&f| markdown::in_fold(f, r)))
+            .count()
+    }
+
+    /// Folds or unfolds the section the cursor is in.
+    fn toggle_fold(&mut self) {
+        let headings = markdown::headings(&self.lines);
+        let Some(idx) = markdown::current_heading(&headings, self.cursor_row) else {
+            self.status_message = Some("No heading to fold".into());
+            return;
+        };
+        let row = headings[idx].row;
+        let key = self.lines[row].clone();
+        if !self.folded.remove(&key) {
+            self.folded.insert(key);
+            self.cursor_row = row;
+            self.cursor_col = self.cursor_col.min(self.lines[row].len());
+            self.selection_anchor = None;
+        }
+    }
+
+    /// Moves a cursor that stepped into a folded section onto its heading, or
+    /// past it when moving forward.
+    fn skip_folded(&mut self, forward: bool) {
+        let folds = self.fold_ranges();
+        if let Some(&(start, end)) = folds.iter().find(|&&f| markdown::in_fold(f, self.cursor_row)) {
+            self.cursor_row = if forward && end < self.lines.len() { end } else { start };
+            self.cursor_col = self.cursor_col.min(self.lines[self.cursor_row].len());
+        }
+    }
+
+    /// Files the open note's folds and marks away under its path.
+    fn stash_note_state(&mut self) {
+        let folded = std::mem::take(&mut self.folded);
+        let marks = std::mem::take(&mut self.marks);
+        if let Some(path) = &self.opened_path {
+            self.fold_memory.insert(path.clone(), folded);
+            self.mark_memory.insert(path.clone(), marks);
+        }
+    }
+
+    fn swap_note_state(&mut self, next: &Path) {
+        self.stash_note_state();
+        self.folded = self.fold_memory.get(next).cloned().unwrap_or_default();
+        self.marks = self.mark_memory.get(next).cloned().unwrap_or_default();
+    }
+
+    fn set_mark(&mut self, name: char) {
+        let line = self.lines[self.cursor_row].clone();
+        self.marks.insert(name, Mark { row: self.cursor_row, col: self.cursor_col, line });
+        self.status_message = Some(format!("Mark '{}' set at line {}", name, self.cursor_row + 1));
+    }
+
+    fn jump_to_mark(&mut self, name: char) {
+        let Some(mark) = self.marks.get(&name) else {
+            self.status_message = Some(format!("No mark '{}'", name));
+            return;
+        };
+        let row = self.mark_row(mark);
+        self.selection_anchor = None;
+        self.cursor_row = row;
+        self.cursor_col = clamp_to_char_boundary(&self.lines[row], mark.col);
+        self.ensure_cursor_visible();
+    }
+
+    /// Current row of a mark: its recorded row if the line is unchanged,
+    /// otherwise the nearest row with the same text, otherwise the recorded
+    /// row clamped to the buffer.
+    pub fn mark_row(&self, mark: &Mark) -> usize {
+        if self.lines.get(mark.row) == Some(&mark.line) {
+            return mark.row;
+        }
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(_, l)| **l == mark.line)
+            .min_by_key(|(r, _)| r.abs_diff(mark.row))
+            .map_or(mark.row.min(self.lines.len() - 1), |(r, _)| r)
+    }
+
+    /// Cursor column in characters rather than bytes.
+    pub fn cursor_display_col(&self) -> usize {
+        let line = &self.lines[self.cursor_row];
+        line[..clamp_to_char_boundary(line, self.cursor_col)].chars().count()
+    }
+
+    /// Loads `path` into the editor. Returns `false` when a sealed note has
+    /// to wait for its passphrase instead; the prompt opens it later.
+    pub fn open_file(&mut self, path: &Path, at: Option<Anchor>) -> Result<bool> {
+        let content = read_note(path)?;
+        let content = if crypt::is_sealed(&content) {
+            let Some(key) = self.keys.get(path) else {
+                self.modal = Some(Modal::Passphrase { path: path.to_path_buf(), input: String::new(), sealing: false, at });
+                return Ok(false);
+            };
+            crypt::unseal(&content, key)?
+        } else {
+            content
+        };
+        self.leave_scratch();
+        let title = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_string();
+
+        self.title = title;
+        self.title_cursor = self.title.len();
+        self.lines = split_lines_preserve(&content);
+        if self.lines.is_empty() {
+            self.lines.push(String::new());
+        }
+        self.cursor_row = 0;
+        self.cursor_col = 0;
+        self.scroll_y = 0;
+        self.scroll_x = 0;
+        self.selection_anchor = None;
+        self.block_anchor = None;
+        self.swap_note_state(path);
+        self.opened_path = Some(path.to_path_buf());
+        self.dirty = false;
+        self.focus = self.last_right_focus.into();
+        self.disk_lines = self.lines.clone();
+        self.disk_mtime = modified(path);
+        if self.title_sync {
+            self.heading_to_title();
+            self.title_cursor = self.title.len();
+        }
+        match at {
+            Some(Anchor::Line(n)) => self.cursor_row = n.saturating_sub(1).min(self.lines.len() - 1),
+            Some(Anchor::Heading(name)) => self.jump_to_heading(&name),
+            None => {}
+        }
+        self.ensure_cursor_visible();
+        Ok(true)
+    }
+
+    /// Puts the heading titled `name` at the top of the Content pane.
+    fn jump_to_heading(&mut self, name: &str) {
+        let Some(row) = markdown::find_heading(&self.lines, name) else {
+            self.status_message = Some(format!("No heading '{}' in {}", name, self.title));
+            return;
+        };
+        self.selection_anchor = None;
+        self.cursor_row = row;
+        self.cursor_col = 0;
+        self.scroll_y = row;
+        self.ensure_cursor_visible();
+    }
+
+    /// Picks up edits made to the open note by other programs: reloads a
+    /// clean buffer, merges into a dirty one and asks about conflicts.
+    fn check_disk_changes(&mut self) -> Result<()> {
+        if self.scratch_active {
+            return Ok(());
+        }
+        let Some(path) = self.opened_path.clone() else { return Ok(()) };
+        let mtime = modified(&path);
+        if mtime.is_none() || mtime == self.disk_mtime {
+            return Ok(());
+        }
+        let content = read_note(&path)?;
+        let content = match self.keys.get(&path) {
+            Some(key) if crypt::is_sealed(&content) => crypt::unseal(&content, key)?,
+            // Can't compare without the key; leave the buffer alone.
+            None if crypt::is_sealed(&content) => return Ok(()),
+            _ => content,
+        };
+        let mut theirs = split_lines_preserve(&content);
+        if theirs.is_empty() {
+            theirs.push(String::new());
+        }
+        if theirs == self.disk_lines {
+            self.disk_mtime = mtime;
+            return Ok(());
+        }
+        if !self.dirty {
+            self.lines = theirs.clone();
+            self.adopt_disk_version(theirs, mtime);
+            self.status_message = Some(format!("Reloaded {}: it changed on disk", self.title));
+            return Ok(());
+        }
+        let regions = merge::merge(&self.disk_lines, &self.lines, &theirs);
+        if merge::conflicts(&regions) == 0 {
+            self.lines = merge::resolve(&regions);
+            self.adopt_disk_version(theirs, mtime);
+            self.status_message = Some("Merged edits made on disk into yours; save to keep them".into());
+        } else {
+            self.modal = Some(Modal::Merge { regions, selected: 0, theirs, mtime });
+        }
+        Ok(())
+    }
+
+    fn adopt_disk_version(&mut self, theirs: Vec<String>, mtime: Option<SystemTime>) {
+        self.disk_lines = theirs;
+        self.disk_mtime = mtime;
+        self.cursor_row = self.cursor_row.min(self.lines.len() - 1);
+        self.cursor_col = clamp_to_char_boundary(&self.lines[self.cursor_row], self.cursor_col);
+        self.selection_anchor = None;
+        self.block_anchor = None;
+        self.find_matches.clear();
+        self.ensure_cursor_visible();
+    }
+
+    /// Opens the capture inbox at its end, creating it when missing.
+    fn open_inbox(&mut self) -> Result<()> {
+        let path = self.inbox.clone();
+        if !path.is_file() {
+            if self.read_only {
+                self.status_message = Some("Read-only: the vault is open in another instance".into());
+                return Ok(());
+            }
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            write_note(&path, "")?;
+            if let Some(dir) = path.parent() {
+                self.rescan_sidebar_dir(dir);
+            }
+        }
+        if self.dirty && !self.read_only && self.opened_path.as_ref() != Some(&path) {
+            self.save_current()?;
+        }
+        if !self.open_file(&path, None)? {
+            return Ok(());
+        }
+        self.reveal_in_sidebar();
+        self.focus = Focus::Content;
+        self.last_right_focus = RightFocus::Content;
+        self.cursor_row = self.lines.len() - 1;
+        self.cursor_col = self.lines[self.cursor_row].len();
+        self.ensure_cursor_visible();
+        Ok(())
+    }
+
+    /// Opens a note named on the command line, relative to the vault with
+    /// the `.md` extension optional, and reveals it in the sidebar.
+    /// `note` may end in `:LINE`.
+    pub fn open_from_cli(&mut self, note: &str) -> Result<()> {
+        let (note, line) = match note.rsplit_once(':') {
+            Some((name, n)) if !self.notes_dir.join(note).is_file() && n.parse::<usize>().is_ok() => (name, n.parse().ok()),
+            _ => (note, None),
+        };
+        let mut path = self.notes_dir.join(note);
+        if !path.is_file() && path.extension().is_none() {
+            path.set_extension("md");
+        }
+        if !path.is_file() {
+            bail!("No note '{}' in {}", note, self.notes_dir.display());
+        }
+        if !self.open_file(&path, line.map(Anchor::Line))? {
+            return Ok(());
+        }
+        self.reveal_in_sidebar();
+        self.focus = Focus::Content;
+        self.last_right_focus = RightFocus::Content;
+        Ok(())
+    }
+
+    fn save_current(&mut self) -> Result<()> {
+        if self.scratch_active {
+            if !self.persist_scratch {
+                self.status_message = Some("The scratch buffer is not saved (see persist_scratch)".into());
+            }
+            self.save_scratch();
+            return Ok(());
+        }
+        if self.title.trim().is_empty() {
+            return Ok(());
+        }
+        if self.read_only {
+            self.status_message = Some("Read-only: the vault is open in another instance".into());
+            return Ok(());
+        }
+        if !self.notes_dir.is_dir() {
+            self.status_message = Some(format!("Not saved: {} is unavailable (unmounted?)", self.notes_dir.display()));
+            return Ok(());
+        }
+        // Don't overwrite edits made elsewhere since we loaded the note.
+        self.check_disk_changes()?;
+        if matches!(self.modal, Some(Modal::Merge { .. })) {
+            return Ok(());
+        }
+        if self.format_on_save
+            && let Err(e) = self.format_note()
+        {
+            self.report_error(e);
+        }
+        let old_path = self.opened_path.clone();
+        let target_dir = self
+            .new_note_dir
+            .clone()
+            .or_else(|| old_path.as_deref().and_then(Path::parent).map(Path::to_path_buf))
+            .unwrap_or_else(|| self.notes_dir.clone());
+        let new_path = target_dir.join(self.file_name());
+        let marked = crypt::is_marked(&self.lines[..self.lines.len().min(50)].join("\n"));
+        let key = self.keys.get(&new_path).or_else(|| old_path.as_ref().and_then(|p| self.keys.get(p))).cloned();
+        if marked && key.is_none() {
+            self.modal = Some(Modal::Passphrase { path: new_path, input: String::new(), sealing: true, at: None });
+            return Ok(());
+        }
+
+        let mut renamed = None;
+        if let Some(old) = &old_path
+            && *old != new_path
+        {
+            // Still write the new file so the edit isn't lost; the old one
+            // stays behind and the failure is reported.
+            let files = collect_files(&self.notes_tree);
+            let index = LinkIndex::build(&files, &self.notes_dir);
+            match rename_note(old, &new_path) {
+                Ok(()) => renamed = Some((files, index)),
+                Err(e) => self.report_error(e),
+            }
+        }
+        let own_links = match (&renamed, &old_path) {
+            (Some((_, index)), Some(old)) => links::rewrite_links(&mut self.lines, &new_path, old, &new_path, index),
+            _ => 0,
+        };
+        let content = match (&key, marked) {
+            (Some(key), true) => {
+                self.keys.insert(new_path.clone(), key.clone());
+                crypt::seal(&self.lines.join("\n"), key)?
+            }
+            _ => self.lines.join("\n"),
+        };
+        write_note(&new_path, &content)?;
+        if marked {
+            self.encrypted.insert(new_path.clone());
+        } else {
+            self.encrypted.remove(&new_path);
+        }
+        if let Some(old) = &old_path
+            && *old != new_path
+        {
+            self.encrypted.remove(old);
+        }
+        self.disk_lines = self.lines.clone();
+        self.disk_mtime = modified(&new_path);
+        if let Some(history) = &self.local_history
+            && let Err(e) = history.snapshot(&new_path, &content, false)
+        {
+            self.report_error(e);
+        }
+        if let (Some((files, index)), Some(old)) = (&renamed, &old_path) {
+            match links::rewrite_vault_links(files, &new_path, old, &new_path, index) {
+                Ok((n, notes)) => {
+                    let (n, notes) = (n + own_links, notes + usize::from(own_links > 0));
+                    self.status_message = Some(format!(
+                        "Renamed to {}; updated {} link{} in {} note{}",
+                        self.title.trim(),
+                        n,
+                        if n == 1 { "" } else { "s" },
+                        notes,
+                        if notes == 1 { "" } else { "s" },
+                    ));
+                }
+                Err(e) => self.report_error(e),
+            }
+        }
+
+        self.opened_path = Some(new_path.clone());
+        self.dirty = false;
+        self.new_note_dir = None;
+
+        if let Some(old_parent) = old_path.as_deref().and_then(Path::parent)
+            && Some(old_parent) != new_path.parent()
+        {
+            self.rescan_sidebar_dir(old_parent);
+        }
+        self.refresh_sidebar_select_path(&new_path);
+        self.git_section.request_refresh();
+
+        Ok(())
+    }
+
+    fn refresh_sidebar_select_path(&mut self, path: &Path) {
+        if let Some(parent) = path.parent() {
+            self.rescan_sidebar_dir(parent);
+        }
+        if let Some(idx) = self
+            .sidebar_items
+            .iter()
+            .position(|n| !n.is_dir && n.path == path)
+        {
+            self.sidebar_state.select(Some(idx));
+        }
+    }
+
+    /// Swaps the open note for the in-memory scratch buffer, or back.
+    fn toggle_scratch(&mut self) {
+        std::mem::swap(&mut self.title, &mut self.parked.title);
+        std::mem::swap(&mut self.lines, &mut self.parked.lines);
+        std::mem::swap(&mut self.folded, &mut self.parked.folded);
+        std::mem::swap(&mut self.marks, &mut self.parked.marks);
+        std::mem::swap(&mut self.title_cursor, &mut self.parked.title_cursor);
+        std::mem::swap(&mut self.cursor_row, &mut self.parked.cursor_row);
+        std::mem::swap(&mut self.cursor_col, &mut self.parked.cursor_col);
+        std::mem::swap(&mut self.scroll_y, &mut self.parked.scroll_y);
+        std::mem::swap(&mut self.scroll_x, &mut self.parked.scroll_x);
+        std::mem::swap(&mut self.opened_path, &mut self.parked.opened_path);
+        std::mem::swap(&mut self.new_note_dir, &mut self.parked.new_note_dir);
+        std::mem::swap(&mut self.dirty, &mut self.parked.dirty);
+        self.cursor_row = self.cursor_row.min(self.lines.len() - 1);
+        self.cursor_col = clamp_to_char_boundary(&self.lines[self.cursor_row], self.cursor_col);
+        self.selection_anchor = None;
+        self.block_anchor = None;
+        self.find_matches.clear();
+
+        self.scratch_active = !self.scratch_active;
+        if self.scratch_active {
+            self.focus = Focus::Content;
+            self.status_message = Some("Scratch buffer (Alt+S to go back)".into());
+        } else {
+            self.save_scratch();
+            self.focus = self.last_right_focus.into();
+        }
+    }
+
+    fn leave_scratch(&mut self) {
+        if self.scratch_active {
+            self.toggle_scratch();
+        }
+    }
+
+    /// Writes the scratch buffer to its file when `persist_scratch` is on.
+    fn save_scratch(&mut self) {
+        if !self.persist_scratch {
+            return;
+        }
+        let lines = if self.scratch_active { &self.lines } else { &self.parked.lines };
+        let path = crate::config::config_dir().join(SCRATCH_FILE);
+        if let Err(e) = std::fs::write(&path, lines.join("\n")).with_context(|| format!("Save scratch buffer {}", path.display())) {
+            self.report_error(e);
+        }
+    }
+
+    /// Name the note is saved under: the title styled per `filename_style`.
+    fn file_name(&self) -> String {
+        let title = self.title.trim();
+        let stem = match self.filename_style {
+            FilenameStyle::Title => title.to_string(),
+            FilenameStyle::Kebab => Case::Kebab.apply(title),
+            FilenameStyle::Snake => Case::Snake.apply(title),
+        };
+        // A title of only punctuation has no slug.
+        format!("{}.md", if stem.is_empty() { title } else { &stem })
+    }
+
+    /// With `title_sync`, writes the title into the first `# Heading`,
+    /// adding one at the top of the body when the note has none.
+    fn title_to_heading(&mut self) {
+        let title = self.title.trim();
+        if !self.title_sync || self.scratch_active || title.is_empty() {
+            return;
+        }
+        let heading = format!("# {}", title);
+        match markdown::headings(&self.lines).into_iter().find(|h| h.level == 1) {
+            Some(h) if h.title != title => self.lines[h.row] = heading,
+            Some(_) => return,
+            None => {
+                let row = markdown::body_start(&self.lines);
+                let mut added = vec![heading];
+                if self.lines.get(row).is_some_and(|l| !l.trim().is_empty()) {
+                    added.push(String::new());
+                }
+                if self.cursor_row >= row {
+                    self.cursor_row += added.len();
+                }
+                self.lines.splice(row..row, added);
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// With `title_sync`, takes the title from the first `# Heading`.
+    fn heading_to_title(&mut self) {
+        if !self.title_sync || self.scratch_active {
+            return;
+        }
+        if let Some(h) = markdown::headings(&self.lines).into_iter().find(|h| h.level == 1)
+            && !h.title.is_empty()
+            && h.title != self.title.trim()
+        {
+            // Characters a file name can't have stay out, as when typing.
+            self.title = h.title.chars().filter(|c| !matches!(c, '/' | '\\' | '.')).collect();
+            self.title_cursor = self.title_cursor.min(self.title.len());
+            self.title_cursor = clamp_to_char_boundary(&self.title, self.title_cursor);
+        }
+    }
+
+    /// Path segments from the vault root to the open (or about to be
+    /// created) note, e.g. `["Notes", "projects", "todo.md"]`.
+    pub fn breadcrumb(&self) -> Vec<String> {
+        let path = match (&self.opened_path, &self.new_note_dir) {
+            (Some(p), _) => p.clone(),
+            (None, Some(dir)) if !self.title.is_empty() => dir.join(self.file_name()),
+            _ => return Vec::new(),
+        };
+        let root = self.notes_dir.file_name().map_or_else(|| "Notes".to_string(), |n| n.to_string_lossy().into_owned());
+        let mut parts = vec![root];
+        if let Ok(rel) = path.strip_prefix(&self.notes_dir) {
+            parts.extend(rel.iter().map(|c| c.to_string_lossy().into_owned()));
+        }
+        parts
+    }
+
+    /// Expands every ancestor of the open note and selects it in the sidebar,
+    /// whatever was collapsed or selected before. An unsaved new note reveals
+    /// the folder it will be created in.
+    fn reveal_in_sidebar(&mut self) {
+        let Some(path) = self.opened_path.clone().or_else(|| self.new_note_dir.clone()) else {
+            self.status_message = Some("No note to reveal".into());
+            return;
+        };
+        if !path.starts_with(&self.notes_dir) {
+            self.status_message = Some(format!("{} is outside the notes directory", path.display()));
+            return;
+        }
+        for dir in path.ancestors().skip(1) {
+            self.expanded_dirs.insert(dir.to_path_buf());
+            if dir == self.notes_dir {
+                break;
+            }
+        }
+        self.reflatten_sidebar();
+        let mut found = self.sidebar_items.iter().position(|n| n.path == path);
+        if found.is_none()
+            && let Some(parent) = path.parent()
+        {
+            // Created behind our back since the last scan.
+            self.rescan_sidebar_dir(parent);
+            found = self.sidebar_items.iter().position(|n| n.path == path);
+        }
+        match found {
+            Some(idx) => {
+                self.sidebar_state.select(Some(idx));
+                self.focus = Focus::Sidebar;
+            }
+            None => self.status_message = Some(format!("{} is not in the sidebar", path.display())),
+        }
+    }
+
+    /// Applies `action` to every marked file, reporting the ones it fails
+    /// on, then clears the marks.
+    fn run_batch(&mut self, action: BatchAction, input: &str) -> Result<()> {
+        let target = match action {
+            BatchAction::Move => {
+                let dir = links::normalize(&self.notes_dir.join(input.trim_matches('/')));
+                if !dir.starts_with(&self.notes_dir) {
+                    bail!("{} is outside the notes directory", input);
+                }
+                dir
+            }
+            _ => self.notes_dir.join(ARCHIVE),
+        };
+        let (mut files, index) = match action {
+            BatchAction::Move | BatchAction::Archive => {
+                let files = collect_files(&self.notes_tree);
+                let index = LinkIndex::build(&files, &self.notes_dir);
+                (files, index)
+            }
+            _ => (Vec::new(), LinkIndex::default()),
+        };
+        let mut paths: Vec<PathBuf> = self.marked.drain().filter(|p| p.is_file()).collect();
+        paths.sort();
+        let (mut done, mut relinked) = (0, 0);
+        for path in &paths {
+            let result = match action {
+                BatchAction::Delete => std::fs::read(path)
+                    .and_then(|content| std::fs::remove_file(path).map(|()| content))
+                    .with_context(|| format!("Delete {}", path.display()))
+                    .map(|content| self.deleted.push(DeletedFile { path: path.clone(), content, at: datefmt::now() })),
+                BatchAction::Tag if !links::is_note(path) => continue,
+                BatchAction::Tag => read_note(path).and_then(|content| match markdown::add_tag(&content, input.trim_start_matches('#')) {
+                    Some(tagged) => write_note(path, &tagged),
+                    None => Ok(()),
+                }),
+                BatchAction::Move | BatchAction::Archive => self.move_file(path, &target, &mut files, &index).map(|n| relinked += n),
+            };
+            match result {
+                Ok(()) => done += 1,
+                Err(e) => self.report_error(e),
+            }
+        }
+        self.rescan_sidebar_dir(&self.notes_dir.clone());
+        self.git_section.request_refresh();
+        // The open note may be one whose links were just rewritten.
+        self.check_disk_changes()?;
+        let files = format!("{} file{}", done, if done == 1 { "" } else { "s" });
+        self.status_message = Some(match action {
+            BatchAction::Delete => format!("Deleted {} (u in the sidebar undoes one at a time)", files),
+            BatchAction::Move | BatchAction::Archive => format!(
+                "Moved {} to {}/; updated {} link{}",
+                files,
+                relative_to(&target, &self.notes_dir).display(),
+                relinked,
+                if relinked == 1 { "" } else { "s" },
+            ),
+            BatchAction::Tag => format!("Tagged {} with #{}", files, input.trim_start_matches('#')),
+        });
+        Ok(())
+    }
+
+    /// Moves `path` into `dir` under the same name, following it if it is
+    /// the open note, and keeps links to and from it working. `files` (the
+    /// vault as `index` was built from) is updated for the next move.
+    /// Returns the number of links rewritten.
+    fn move_file(&mut self, path: &Path, dir: &Path, files: &mut [PathBuf], index: &LinkIndex) -> Result<usize> {
+        let new = dir.join(path.file_name().unwrap_or_default());
+        if new == path {
+            return Ok(0);
+        }
+        if new.exists() {
+            bail!("{} already exists", new.display());
+        }
+        std::fs::create_dir_all(dir).with_context(|| format!("Create folder {}", dir.display()))?;
+        rename_note(path, &new)?;
+        if self.encrypted.remove(path) {
+            self.encrypted.insert(new.clone());
+        }
+        if let Some(key) = self.keys.remove(path) {
+            self.keys.insert(new.clone(), key);
+        }
+        if self.opened_path.as_deref() == Some(path) {
+            self.opened_path = Some(new.clone());
+            self.disk_mtime = modified(&new);
+        }
+        if let Some(file) = files.iter_mut().find(|f| *f == path) {
+            *file = new.clone();
+        }
+        if !links::is_note(&new) {
+            return Ok(0);
+        }
+        let mut count = 0;
+        let content = read_note(&new)?;
+        if !crypt::is_sealed(&content) {
+            let mut lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+            count = links::rebase_links(&mut lines, path.parent().unwrap_or(&self.notes_dir), dir, &self.notes_dir);
+            if count > 0 {
+                write_note(&new, &lines.join("\n"))?;
+            }
+        }
+        let (rewritten, _) = links::rewrite_vault_links(files, &new, path, &new, index)?;
+        Ok(count + rewritten)
+    }
+
+    /// The first lines of the file selected in the sidebar, for the preview
+    /// popup; `None` on folders and saved searches.
+    pub fn sidebar_preview(&mut self) -> Option<(&Path, &[String])> {
+        let item = self.sidebar_state.selected().and_then(|i| self.sidebar_items.get(i)).filter(|it| !it.is_dir)?;
+        let mtime = modified(&item.path);
+        if !self.preview_cache.as_ref().is_some_and(|(path, time, _)| *path == item.path && *time == mtime) {
+            let lines = if !is_text_file(&item.path) {
+                vec!["(not a text file)".to_string()]
+            } else {
+                match read_note(&item.path) {
+                    Ok(content) if crypt::is_sealed(&content) => vec!["(encrypted)".to_string()],
+                    Ok(content) => content.lines().take(PREVIEW_LINES).map(str::to_string).collect(),
+                    Err(e) => vec![format!("{:#}", e)],
+                }
+            };
+            self.preview_cache = Some((item.path.clone(), mtime, lines));
+        }
+        self.preview_cache.as_ref().map(|(path, _, lines)| (path.as_path(), lines.as_slice()))
+    }
+
+    /// Writes `self.deleted[index]` back and selects it in the sidebar.
+    fn restore_deleted(&mut self, index: usize) -> Result<()> {
+        if self.read_only {
+            self.status_message = Some("Read-only: not restored".into());
+            return Ok(());
+        }
+        let path = self.deleted[index].path.clone();
+        if path.exists() {
+            bail!("{} exists again; not overwriting it", path.display());
+        }
+        let file = self.deleted.remove(index);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("Create folder {}", parent.display()))?;
+        }
+        if let Err(e) = std::fs::write(&path, &file.content) {
+            self.deleted.insert(index, file);
+            return Err(e).with_context(|| format!("Restore {}", path.display()));
+        }
+        if let Some(parent) = path.parent() {
+            self.rescan_sidebar_dir(parent);
+        }
+        if let Some(idx) = self.sidebar_items.iter().position(|n| n.path == path) {
+            self.sidebar_state.select(Some(idx));
+        }
+        self.git_section.request_refresh();
+        self.status_message = Some(format!("Restored {}", relative_to(&path, &self.notes_dir).display()));
+        Ok(())
+    }
+
+    /// `(notes, words)` of the sidebar folder `dir` when `folder_counts` is
+    /// on, counted the first time it is asked for.
+    pub fn folder_count(&mut self, dir: &Path) -> Option<(usize, usize)> {
+        let counts = self.folder_counts.as_mut()?;
+        if let Some(&count) = counts.get(dir) {
+            return Some(count);
+        }
+        let notes: Vec<PathBuf> = find_dir(&self.notes_tree, dir).map(collect_files)?.into_iter().filter(|p| links::is_note(p)).collect();
+        let words = notes
+            .iter()
+            .filter(|p| !self.encrypted.contains(*p))
+            .filter_map(|p| read_note(p).ok())
+            .map(|content| content.split_whitespace().count())
+            .sum();
+        counts.insert(dir.to_path_buf(), (notes.len(), words));
+        Some((notes.len(), words))
+    }
+
+    /// Re-reads a single directory from disk and patches the sidebar.
+    fn rescan_sidebar_dir(&mut self, dir: &Path) {
+        if let Some(counts) = &mut self.folder_counts {
+            // The folder, everything in it, and every folder it is in.
+            counts.retain(|p, _| !dir.starts_with(p) && !p.starts_with(dir));
+        }
+        if !self.notes_dir.is_dir() {
+            self.status_message = Some(format!("Notes directory {} is unavailable (unmounted?)", self.notes_dir.display()));
+        } else if let Err(e) = rescan_subtree(&mut self.notes_tree, dir, self.symlinks) {
+            self.report_error(e.context("Refreshing the sidebar"));
+        }
+        self.reflatten_sidebar();
+    }
+
+    /// Rebuilds the flat sidebar list from the cached tree, keeping the
+    /// selection on the same path when it still exists.
+    fn reflatten_sidebar(&mut self) {
+        let old_idx = self.sidebar_state.selected();
+        let old_path = old_idx.and_then(|i| self.sidebar_items.get(i)).map(|n| n.path.clone());
+        self.sidebar_items = flatten_tree_for_sidebar(&self.notes_tree, &self.expanded_dirs);
+        if self.sidebar_items.is_empty() {
+            self.sidebar_state.select(None);
+            return;
+        }
+        let idx = old_path
+            .and_then(|p| self.sidebar_items.iter().position(|n| n.path == p))
+            .unwrap_or_else(|| old_idx.unwrap_or(0).min(self.sidebar_items.len() - 1));
+        self.sidebar_state.select(Some(idx));
+    }
+}
+
+impl From<RightFocus> for Focus {
+    fn from(value: RightFocus) -> Self {
+        match value {
+            RightFocus::Title => Focus::Title,
+            RightFocus::Content => Focus::Content,
+        }
+    }
+}
+
+/// Char column of byte offset `byte` in `line`.
+fn char_col(line: &str, byte: usize) -> usize {
+    line[..clamp_to_char_boundary(line, byte)].chars().count()
+}
+
+/// Byte offset of char column `col` in `line`, or its end when shorter.
+pub fn char_to_byte(line: &str, col: usize) -> usize {
+    line.char_indices().nth(col).map_or(line.len(), |(i, _)| i)
+}
+
+pub fn clamp_to_char_boundary(s: &str, idx: usize) -> usize {
+    let mut idx = idx.min(s.len());
+    while !s.is_char_boundary(idx) {
+        idx -= 1;
+    }
+    idx
+}
+
+/// Start of the character before byte `idx` of `s`.
+fn prev_char_boundary(s: &str, idx: usize) -> usize {
+    let idx = clamp_to_char_boundary(s, idx);
+    s[..idx].char_indices().next_back().map_or(0, |(i, _)| i)
+}
+
+/// End of the character at byte `idx` of `s`.
+fn next_char_boundary(s: &str, idx: usize) -> usize {
+    let idx = clamp_to_char_boundary(s, idx);
+    s[idx..].chars().next().map_or(s.len(), |c| idx + c.len_utf8())
+}
+
+fn closing_pair(c: char) -> Option<char> {
+    match c {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '`' => Some('`'),
+        '*' => Some('*'),
+        _ => None,
+    }
+}
+
+fn is_closer(c: char) -> bool {
+    matches!(c, ')' | ']' | '`' | '*')
+}
+
+/// Leading indentation plus any blockquote markers (`  > > `), carried over
+/// to the next line on Enter.
+fn continuation_prefix(line: &str) -> &str {
+    let end = line
+        .find(|c: char| !c.is_whitespace() && c != '>')
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+fn looks_like_url(s: &str) -> bool {
+    let s = s.trim();
+    !s.is_empty()
+        && !s.contains(char::is_whitespace)
+        && ["http://", "https://", "ftp://", "mailto:", "www."]
+            .iter()
+            .any(|prefix| s.starts_with(prefix))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Fills `{type}`, `{scope}` and `{summary}` of a `commit_template`. An
+/// empty scope takes its `({scope})` parentheses with it.
+pub fn commit_message(template: &str, fields: &[String; 3]) -> String {
+    let [kind, scope, summary] = fields.each_ref().map(|f| f.trim());
+    let template = if scope.is_empty() { template.replace("({scope})", "") } else { template.to_string() };
+    template.replace("{type}", kind).replace("{scope}", scope).replace("{summary}", summary)
+}
+
+fn split_lines_preserve(s: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    for line in s.split_inclusive('\n') {
+        if line.ends_with('\n') {
+            let mut ln = line.to_string();
+            ln.pop();
+            out.push(ln);
+        } else {
+            out.push(line.to_string());
+        }
+    }
+    if out.is_empty() {
+        out.push(String::new());
+    }
+    out
+}
+
+fn load_scratch(persist: bool) -> Vec<String> {
+    if !persist {
+        return vec![String::new()];
+    }
+    split_lines_preserve(&std::fs::read_to_string(crate::config::config_dir().join(SCRATCH_FILE)).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(app: &mut App, code: KeyCode) {
+        app.handle_key(KeyEvent::new(code, KeyModifiers::NONE)).unwrap();
+    }
+
+    fn app_in(name: &str) -> (App, PathBuf) {
+        let root = std::env::temp_dir().join(format!("lazynotes-test-{}-{}", name, std::process::id()));
+        let config = Config { notes_dir: root.to_string_lossy().to_string(), ..Config::default() };
+        (App::new(config).unwrap(), root)
+    }
+
+    #[test]
+    fn editing_keys_step_over_multibyte_chars() {
+        let (mut app, root) = app_in("multibyte");
+        app.focus = Focus::Content;
+        for c in "aéb".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        press(&mut app, KeyCode::Left);
+        press(&mut app, KeyCode::Backspace);
+        assert_eq!(app.lines[0], "ab");
+        assert_eq!(app.cursor_col, 1);
+
+        press(&mut app, KeyCode::Char('é'));
+        press(&mut app, KeyCode::Left);
+        assert_eq!(app.cursor_col, 1);
+        press(&mut app, KeyCode::Delete);
+        assert_eq!(app.lines[0], "ab");
+        press(&mut app, KeyCode::Right);
+        assert_eq!(app.cursor_col, 2);
+
+        app.focus = Focus::Title;
+        for c in "ñø".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        // Left leaves the title for the sidebar, so step in from Home.
+        press(&mut app, KeyCode::Home);
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Backspace);
+        assert_eq!(app.title, "ø");
+        press(&mut app, KeyCode::Delete);
+        assert_eq!(app.title, "");
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn paste_goes_to_the_open_modal() {
+        let (mut app, root) = app_in("paste");
+        app.focus = Focus::Content;
+        app.modal = Some(Modal::InsertLink { text: "docs".into(), url: String::new(), editing_url: true });
+        app.handle_paste("https://example.com\nsecond line");
+        assert!(matches!(&app.modal, Some(Modal::InsertLink { url, .. }) if url == "https://example.com"));
+        assert_eq!(app.lines, vec![String::new()]);
+
+        app.modal = Some(Modal::Outline { selected: 0 });
+        app.handle_paste("ignored");
+        assert_eq!(app.lines, vec![String::new()]);
+        let _ = std::fs::remove_dir_all(root);
+    }
+    #[test]
+    fn batch_move_keeps_links_working() {
+        let (mut app, root) = app_in("batch-move");
+        std::fs::write(root.join("a.md"), "See [b](b.md), [[b]] and [[/b#Top|b]].").unwrap();
+        std::fs::write(root.join("b.md"), "Back to [a](a.md).").unwrap();
+        app.rescan_sidebar_dir(&root.clone());
+        app.marked.insert(root.join("b.md"));
+        app.run_batch(BatchAction::Move, "done").unwrap();
+        let read = |path: &str| std::fs::read_to_string(root.join(path)).unwrap();
+        assert_eq!(read("a.md"), "See [b](done/b.md), [[b]] and [[/done/b#Top|b]].");
+        assert_eq!(read("done/b.md"), "Back to [a](../a.md).");
+        let _ = std::fs::remove_dir_all(&root);
+    }
 }
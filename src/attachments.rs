@@ -0,0 +1,234 @@
+//! Files notes embed or link from `assets/` folders: listing a note's
+//! attachments, renaming one along with the links to it, and finding the
+//! ones no note references any more.
+
+use crate::fs::{read_note, write_note};
+use crate::links::{is_note, markdown_targets, normalize};
+use crate::search::relative_to;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// Folder name that marks a file as an attachment, wherever it sits.
+pub const ASSETS: &str = "assets";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Linked from the note and present.
+    Linked,
+    /// Linked from the note but not on disk.
+    Missing,
+    /// In the note's `assets/` folder, and no note links to it.
+    Unreferenced,
+}
+
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub path: PathBuf,
+    pub state: State,
+}
+
+/// Takes a path relative to the vault, so folders above it don't count.
+fn in_assets(path: &Path) -> bool {
+    path.components().any(|c| c == Component::Normal(ASSETS.as_ref()))
+}
+
+/// Where the link target `target` in a note in `dir` points, if at an
+/// attachment.
+fn resolve(dir: &Path, target: &str, root: &Path) -> Option<PathBuf> {
+    let decoded = target.replace("%20", " ");
+    if decoded.contains("://") || decoded.ends_with(".md") {
+        return None;
+    }
+    let path = normalize(&match decoded.strip_prefix('/') {
+        Some(abs) => root.join(abs),
+        None => dir.join(&decoded),
+    });
+    in_assets(relative_to(&path, root)).then_some(path)
+}
+
+/// Attachments linked from `content`, a note at `note`, in link order.
+fn linked(content: &str, note: &Path, root: &Path) -> Vec<PathBuf> {
+    let dir = note.parent().unwrap_or(root);
+    let mut out = Vec::new();
+    for line in content.lines() {
+        for (start, end) in markdown_targets(line) {
+            if let Some(path) = resolve(dir, &line[start..end], root)
+                && !out.contains(&path)
+            {
+                out.push(path);
+            }
+        }
+    }
+    out
+}
+
+/// Every attachment some note in `files` links to.
+fn referenced(files: &[PathBuf], root: &Path) -> HashSet<PathBuf> {
+    files
+        .iter()
+        .filter(|p| is_note(p))
+        .filter_map(|p| Some(linked(&read_note(p).ok()?, p, root)))
+        .flatten()
+        .collect()
+}
+
+/// What `content` (the note at `note`) links to, then whatever sits
+/// unreferenced in the note's own `assets/` folder.
+pub fn for_note(note: &Path, content: &str, files: &[PathBuf], root: &Path) -> Vec<Attachment> {
+    let mut out: Vec<Attachment> = linked(content, note, root)
+        .into_iter()
+        .map(|path| {
+            let state = if path.is_file() { State::Linked } else { State::Missing };
+            Attachment { path, state }
+        })
+        .collect();
+    let folder = note.parent().unwrap_or(root).join(ASSETS);
+    let used = referenced(files, root);
+    let mut loose: Vec<&PathBuf> = files.iter().filter(|p| p.parent() == Some(&folder) && !used.contains(*p)).collect();
+    loose.sort();
+    out.extend(loose.into_iter().map(|p| Attachment { path: p.clone(), state: State::Unreferenced }));
+    out
+}
+
+/// Files in any `assets/` folder of the vault that no note links to.
+pub fn orphans(files: &[PathBuf], root: &Path) -> Vec<PathBuf> {
+    let used = referenced(files, root);
+    let mut out: Vec<PathBuf> = files
+        .iter()
+        .filter(|p| !is_note(p) && in_assets(relative_to(p, root)) && !used.contains(*p))
+        .cloned()
+        .collect();
+    out.sort();
+    out
+}
+
+/// Points links to `old` at `new`, which sits in the same folder, in the
+/// lines of the note at `note`. Returns the number of links changed.
+pub fn rewrite(lines: &mut [String], note: &Path, old: &Path, new: &Path, root: &Path) -> usize {
+    let Some(new_name) = new.file_name().and_then(|n| n.to_str()) else { return 0 };
+    let new_name = new_name.replace(' ', "%20");
+    let dir = note.parent().unwrap_or(root);
+    let mut count = 0;
+    for line in lines.iter_mut() {
+        // Back to front so earlier ranges stay valid.
+        for (start, end) in markdown_targets(line).into_iter().rev() {
+            if resolve(dir, &line[start..end], root).as_deref() != Some(old) {
+                continue;
+            }
+            let name_start = line[start..end].rfind('/').map_or(start, |i| start + i + 1);
+            line.replace_range(name_start..end, &new_name);
+            count += 1;
+        }
+    }
+    count
+}
+
+/// [`rewrite`] over the notes in `files` other than `except`, saving the
+/// ones that changed. Returns `(links, notes)` updated.
+pub fn rewrite_vault(files: &[PathBuf], except: Option<&Path>, old: &Path, new: &Path, root: &Path) -> Result<(usize, usize)> {
+    let (mut links, mut notes) = (0, 0);
+    for path in files.iter().filter(|p| is_note(p) && Some(p.as_path()) != except) {
+        let content = read_note(path)?;
+        let mut lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+        let n = rewrite(&mut lines, path, old, new, root);
+        if n > 0 {
+            write_note(path, &lines.join("\n"))?;
+            links += n;
+            notes += 1;
+        }
+    }
+    Ok((links, notes))
+}
+
+/// Hands `path` to `command` when given, else to the desktop's default
+/// application.
+pub fn open_externally(path: &Path, command: Option<&str>) -> Result<()> {
+    let mut words = command.map(str::split_whitespace);
+    let mut cmd = if let Some(program) = words.as_mut().and_then(Iterator::next) {
+        let mut cmd = std::process::Command::new(program);
+        cmd.args(words.into_iter().flatten());
+        cmd
+    } else if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        let mut cmd = std::process::Command::new("cmd");
+        cmd.args(["/C", "start", ""]);
+        cmd
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    cmd.arg(path)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .with_context(|| format!("Opening {}", path.display()))?;
+    Ok(())
+}
+
+/// Extensions linked as `![..]` embeds rather than plain links.
+pub const IMAGE_EXTENSIONS: [&str; 7] = ["png", "jpg", "jpeg", "gif", "svg", "webp", "bmp"];
+
+/// Copies `source` into `dir`, adding ` 2`, ` 3`, ... to the name when it
+/// is taken. Returns the new path.
+pub fn import(source: &Path, dir: &Path) -> Result<PathBuf> {
+    if !source.is_file() {
+        anyhow::bail!("{} is not a file", source.display());
+    }
+    std::fs::create_dir_all(dir).with_context(|| format!("Creating {}", dir.display()))?;
+    let stem = source.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let ext = source.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut target = dir.join(format!("{}{}", stem, ext));
+    let mut n = 2;
+    while target.exists() {
+        target = dir.join(format!("{} {}{}", stem, n, ext));
+        n += 1;
+    }
+    std::fs::copy(source, &target).with_context(|| format!("Copying {}", source.display()))?;
+    Ok(target)
+}
+
+/// Markdown link from a note in `note_dir` to `file`: an image embed for
+/// pictures, a plain link otherwise.
+pub fn link_to(file: &Path, note_dir: &Path) -> String {
+    let mut rel = PathBuf::new();
+    let mut base = note_dir;
+    while !file.starts_with(base) {
+        rel.push("..");
+        base = base.parent().unwrap_or(Path::new(""));
+    }
+    rel.push(file.strip_prefix(base).unwrap_or(file));
+    let target = rel.to_string_lossy().replace('\\', "/").replace(' ', "%20");
+    let name = file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let image = file
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+    format!("{}[{}]({})", if image { "!" } else { "" }, name, target)
+}
+
+/// Entries of the folder `input` points into whose names start with its
+/// last component, as full paths (folders end in `/`), sorted.
+pub fn complete_path(input: &str) -> Vec<String> {
+    let (dir, prefix) = match input.rfind('/') {
+        Some(i) => (&input[..=i], &input[i + 1..]),
+        None => ("", input),
+    };
+    let folder = if dir.is_empty() { PathBuf::from(".") } else { crate::config::expand_tilde(dir) };
+    let Ok(entries) = std::fs::read_dir(folder) else { return Vec::new() };
+    let mut out: Vec<String> = entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            // Hidden entries only when asked for.
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let slash = if e.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, name, slash))
+        })
+        .collect();
+    out.sort();
+    out
+}
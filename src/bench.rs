@@ -0,0 +1,96 @@
+use crate::app::App;
+use crate::config::{Config, Symlinks};
+use crate::fs::{build_notes_tree, flatten_tree_for_sidebar, read_note, write_note, FlatNode};
+use crate::search::{search_vault, Matcher, SearchOptions};
+use anyhow::Result;
+use ratatui::backend::TestBackend;
+use ratatui::Terminal;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const FILES_PER_DIR: usize = 25;
+const LINES_PER_NOTE: usize = 60;
+const RENDER_FRAMES: u32 = 200;
+
+/// Generates a throwaway vault of `files` notes and times the hot paths of
+/// the fs layer and the renderer. Not listed in `--help`.
+pub fn run(files: usize) -> Result<()> {
+    let root = std::env::temp_dir().join(format!("lazynotes-bench-{}", std::process::id()));
+    let res = bench_vault(&root, files);
+    let _ = std::fs::remove_dir_all(&root);
+    res
+}
+
+fn bench_vault(root: &Path, files: usize) -> Result<()> {
+    let (paths, t) = timed(|| generate_vault(root, files))?;
+    report("generate vault", t, files);
+
+    let (tree, t) = timed(|| build_notes_tree(root, Symlinks::Follow))?;
+    report("build_notes_tree", t, files);
+
+    let all_dirs: HashSet<PathBuf> = paths
+        .iter()
+        .flat_map(|p| p.ancestors().skip(1).take_while(|a| a.starts_with(root)))
+        .map(Path::to_path_buf)
+        .collect();
+    let (flat, t) = timed(|| Ok::<Vec<FlatNode>, anyhow::Error>(flatten_tree_for_sidebar(&tree, &all_dirs)))?;
+    report("flatten (all expanded)", t, flat.len());
+
+    let (lines, t) = timed(|| {
+        let mut total = 0usize;
+        for p in &paths {
+            total += read_note(p)?.lines().count();
+        }
+        Ok::<usize, anyhow::Error>(total)
+    })?;
+    report("read all notes", t, lines);
+
+    let (hits, t) = timed(|| Ok::<_, anyhow::Error>(search_vault(&paths, &Matcher::new("task 35", SearchOptions::default())?, usize::MAX)))?;
+    report("vault search", t, hits.len());
+
+    let config = Config { notes_dir: root.to_string_lossy().to_string(), ..Config::default() };
+    let mut app = App::new(config)?;
+    app.expanded_dirs = all_dirs;
+    if let Some(first) = paths.first() {
+        app.open_file(first, None)?;
+    }
+    let mut terminal = Terminal::new(TestBackend::new(160, 48))?;
+    let start = Instant::now();
+    for _ in 0..RENDER_FRAMES {
+        terminal.draw(|f| crate::ui::draw(f, &mut app))?;
+    }
+    let t = start.elapsed();
+    report("render frame (avg)", t / RENDER_FRAMES, RENDER_FRAMES as usize);
+
+    Ok(())
+}
+
+fn generate_vault(root: &Path, files: usize) -> Result<Vec<PathBuf>> {
+    let body: String = (0..LINES_PER_NOTE)
+        .map(|i| match i % 10 {
+            0 => format!("## Section {}\n", i / 10),
+            5 => format!("- [ ] task {} with a [[link-{}]]\n", i, i * 7),
+            _ => format!("Line {} of synthetic text used to measure lazynotes performance.\n", i),
+        })
+        .collect();
+    let mut paths = Vec::with_capacity(files);
+    for i in 0..files {
+        let dir_idx = i / FILES_PER_DIR;
+        let dir = root.join(format!("area-{:02}", dir_idx % 10)).join(format!("topic-{:03}", dir_idx));
+        let path = dir.join(format!("note-{:05}.md", i));
+        write_note(&path, &format!("# Note {}\n\n{}", i, body))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+fn timed<T, E>(f: impl FnOnce() -> Result<T, E>) -> Result<(T, Duration), E> {
+    let start = Instant::now();
+    let value = f()?;
+    Ok((value, start.elapsed()))
+}
+
+fn report(label: &str, elapsed: Duration, items: usize) {
+    println!("{:<24} {:>10.3} ms  ({} items)", label, elapsed.as_secs_f64() * 1000.0, items);
+}
@@ -0,0 +1,89 @@
+//! Quick capture: `lazynotes capture "text"` appends a timestamped entry to
+//! the inbox note without starting the TUI.
+
+use crate::config::Config;
+use crate::crypt;
+use crate::datefmt;
+use crate::fs::{notes_dir_status, read_note, write_note, NotesDirStatus};
+use anyhow::{bail, Result};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// The configured `inbox`, relative to the notes directory (`.md` optional).
+pub fn inbox_path(config: &Config) -> PathBuf {
+    let mut path = config.notes_path().join(&config.inbox);
+    if path.extension().is_none() {
+        path.set_extension("md");
+    }
+    path
+}
+
+/// Appends `text` (or stdin when `None` or `-`) to the inbox and returns
+/// the inbox path.
+pub fn run(config: &Config, text: Option<String>) -> Result<PathBuf> {
+    let text = match text.filter(|t| t != "-") {
+        Some(text) => text,
+        None => {
+            let mut buf = String::new();
+            std::io::stdin().read_to_string(&mut buf)?;
+            buf
+        }
+    };
+    let text = text.trim();
+    if text.is_empty() {
+        bail!("Nothing to capture");
+    }
+    if notes_dir_status(&config.notes_path()) == NotesDirStatus::Unavailable {
+        bail!("Notes directory {} is not available (unmounted?)", config.notes_path().display());
+    }
+    let path = inbox_path(config);
+    append(&path, text, &datefmt::strftime(datefmt::now(), &config.capture_format))?;
+    Ok(path)
+}
+
+/// Adds `prefix` + `text` as a new line at the end of `path`, creating it
+/// and its folders as needed. When `prefix` is a list item, later lines
+/// of `text` are indented to stay inside it. An encrypted inbox is left
+/// alone: plain text added after the ciphertext would be lost on unseal.
+pub fn append(path: &Path, text: &str, prefix: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut content = if path.is_file() { read_note(path)? } else { String::new() };
+    if crypt::is_sealed(&content) {
+        bail!("{} is encrypted; open it in lazynotes to add to it", path.display());
+    }
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    let indent = if prefix.starts_with("- ") || prefix.starts_with("* ") { "  " } else { "" };
+    for (i, line) in text.lines().enumerate() {
+        if i == 0 {
+            content.push_str(prefix);
+        } else if !line.is_empty() {
+            content.push_str(indent);
+        }
+        content.push_str(line.trim_end());
+        content.push('\n');
+    }
+    write_note(path, &content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_leaves_an_encrypted_inbox_alone() {
+        let path = std::env::temp_dir().join(format!("lazynotes-test-inbox-{}.md", std::process::id()));
+        let sealed = "---\nencrypted: true\n---\n-----BEGIN PGP MESSAGE-----\nabc\n-----END PGP MESSAGE-----\n";
+        std::fs::write(&path, sealed).unwrap();
+        assert!(append(&path, "idea", "- ").is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), sealed);
+
+        std::fs::write(&path, "# Inbox").unwrap();
+        append(&path, "idea\nmore", "- ").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# Inbox\n- idea\n  more\n");
+        let _ = std::fs::remove_file(&path);
+    }
+}
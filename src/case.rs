@@ -0,0 +1,85 @@
+//! Case conversions for the Text ▸ Case menu.
+
+#[derive(Debug, Clone, Copy)]
+pub enum Case {
+    Upper,
+    Lower,
+    Title,
+    Kebab,
+    Snake,
+}
+
+impl Case {
+    pub fn label(self) -> &'static str {
+        match self {
+            Case::Upper => "UPPERCASE",
+            Case::Lower => "lowercase",
+            Case::Title => "Title Case",
+            Case::Kebab => "kebab-case",
+            Case::Snake => "snake_case",
+        }
+    }
+
+    /// Converts `text`; line breaks are kept for the multi-line selections.
+    pub fn apply(self, text: &str) -> String {
+        text.split('\n').map(|line| self.apply_line(line)).collect::<Vec<_>>().join("\n")
+    }
+
+    fn apply_line(self, line: &str) -> String {
+        match self {
+            Case::Upper => line.to_uppercase(),
+            Case::Lower => line.to_lowercase(),
+            Case::Title => title_case(line),
+            Case::Kebab => slug(line, '-'),
+            Case::Snake => slug(line, '_'),
+        }
+    }
+}
+
+/// Capitalizes the first letter of every word and lowercases the rest,
+/// keeping the original spacing.
+fn title_case(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut at_word_start = true;
+    for c in line.chars() {
+        if c.is_alphanumeric() || c == '\'' {
+            if at_word_start {
+                out.extend(c.to_uppercase());
+            } else {
+                out.extend(c.to_lowercase());
+            }
+            at_word_start = false;
+        } else {
+            out.push(c);
+            at_word_start = true;
+        }
+    }
+    out
+}
+
+/// Lowercase words joined by `sep`; camelCase humps and any punctuation
+/// or whitespace count as word breaks. Leading indentation is kept.
+fn slug(line: &str, sep: char) -> String {
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let mut words: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for c in line.trim_start().chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        current.extend(c.to_lowercase());
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    format!("{}{}", indent, words.join(&sep.to_string()))
+}
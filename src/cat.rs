@@ -0,0 +1,42 @@
+//! `lazynotes cat <note> [--html]`: prints a note to stdout for shell
+//! pipelines, without starting the TUI.
+
+use crate::config::Config;
+use crate::fs::{build_notes_tree, collect_files, notes_dir_status, read_note, NotesDirStatus};
+use crate::links::LinkIndex;
+use anyhow::{bail, Result};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// `note` as a path relative to the notes directory (`.md` optional), else
+/// by name or alias the way wikilinks resolve.
+pub fn resolve(config: &Config, note: &str) -> Result<PathBuf> {
+    let root = config.notes_path();
+    if notes_dir_status(&root) == NotesDirStatus::Unavailable {
+        bail!("Notes directory {} is not available (unmounted?)", root.display());
+    }
+    let mut path = root.join(note);
+    if !path.is_file() && path.extension().is_none() {
+        path.set_extension("md");
+    }
+    if path.is_file() {
+        return Ok(path);
+    }
+    let files = collect_files(&build_notes_tree(&root, config.symlinks)?);
+    match LinkIndex::build(&files, &root).resolve(note) {
+        Some(path) => Ok(path.clone()),
+        None => bail!("No note '{}' in {}", note, root.display()),
+    }
+}
+
+/// Prints the note as written, or rendered to HTML.
+pub fn run(config: &Config, note: &str, html: bool) -> Result<()> {
+    let content = read_note(&resolve(config, note)?)?;
+    let out = if html { crate::html::to_html(&content) } else { content };
+    let mut stdout = std::io::stdout().lock();
+    // A closed pipe (`| head`) is the reader being done, not an error.
+    match stdout.write_all(out.as_bytes()).and_then(|_| stdout.flush()) {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        other => Ok(other?),
+    }
+}
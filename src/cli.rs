@@ -0,0 +1,247 @@
+use crate::query::Query;
+use anyhow::{bail, Result};
+
+pub const USAGE: &str = "\
+Usage: lazynotes [OPTIONS] [NOTE]
+       lazynotes capture [TEXT...]
+       lazynotes cat <NOTE> [--html]
+       lazynotes list|tasks [--json]
+       lazynotes search <QUERY> [--json]
+       lazynotes completions <bash|zsh|fish>
+
+Arguments:
+  [NOTE]  Note to open, relative to the notes directory (.md optional);
+          append :LINE to start at that line
+
+Commands:
+  capture [TEXT...]  Append a timestamped entry to the inbox note; reads
+                     stdin when TEXT is missing or `-`
+  cat <NOTE>         Print a note (by path, name or alias) to stdout;
+                     --html renders it
+  list               Print the paths of all notes
+  search <QUERY>     Print matching lines as path:line: text
+  tasks              Print open `- [ ]` tasks as path:line: text
+                     (--json prints path, line, snippet, tags and mtime
+                     for list, search and tasks)
+  completions        Print a shell completion script
+
+Options:
+  -p, --profile <NAME>  Use the named profile from config.toml
+      --debug           Write a debug log to ~/.config/lazynotes/lazynotes.log
+                        (RUST_LOG=<level> does the same at that level)
+  -h, --help            Print help
+  -V, --version         Print version";
+
+#[derive(Debug, Default)]
+pub struct Cli {
+    pub profile: Option<String>,
+    pub help: bool,
+    pub version: bool,
+    pub debug: bool,
+    pub note: Option<String>,
+    pub command: Option<Command>,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    /// Hidden developer command: `lazynotes bench [--files N]`.
+    Bench { files: usize },
+    /// `lazynotes capture [text...]` appends to the inbox; `None` reads stdin.
+    Capture { text: Option<String> },
+    /// `lazynotes cat <note> [--html]` prints the note.
+    Cat { note: String, html: bool },
+    /// `lazynotes list|search <query>|tasks [--json]`.
+    Query { query: Query, text: Option<String>, json: bool },
+    /// `lazynotes completions <shell>` prints a completion script.
+    Completions { shell: String },
+    /// Hidden helper the completion scripts call: `lazynotes __complete notes`.
+    Complete { what: String },
+}
+
+impl Cli {
+    pub fn parse() -> Result<Self> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    pub fn parse_from<I: IntoIterator<Item = String>>(args: I) -> Result<Self> {
+        let mut cli = Cli::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if let Some(value) = arg.strip_prefix("--profile=") {
+                cli.profile = Some(value.to_string());
+                continue;
+            }
+            match arg.as_str() {
+                "-p" | "--profile" => {
+                    let Some(value) = args.next() else {
+                        bail!("{} requires a profile name", arg);
+                    };
+                    cli.profile = Some(value);
+                }
+                "-h" | "--help" => cli.help = true,
+                "-V" | "--version" => cli.version = true,
+                "--debug" => cli.debug = true,
+                "bench" if cli.command.is_none() => {
+                    let mut files = 2000;
+                    while let Some(opt) = args.next() {
+                        match opt.as_str() {
+                            "-n" | "--files" => {
+                                let Some(value) = args.next() else {
+                                    bail!("{} requires a number", opt);
+                                };
+                                files = value.parse()?;
+                            }
+                            other => bail!("Unexpected bench argument '{}'", other),
+                        }
+                    }
+                    cli.command = Some(Command::Bench { files });
+                }
+                "capture" if cli.command.is_none() && cli.note.is_none() => {
+                    let words: Vec<String> = args.by_ref().collect();
+                    let text = (!words.is_empty()).then(|| words.join(" "));
+                    cli.command = Some(Command::Capture { text });
+                }
+                "cat" if cli.command.is_none() && cli.note.is_none() => {
+                    let (mut note, mut html) = (None, false);
+                    for opt in args.by_ref() {
+                        match opt.as_str() {
+                            "--html" => html = true,
+                            other if !other.starts_with('-') && note.is_none() => note = Some(other.to_string()),
+                            other => bail!("Unexpected cat argument '{}'", other),
+                        }
+                    }
+                    let Some(note) = note else {
+                        bail!("cat requires a note");
+                    };
+                    cli.command = Some(Command::Cat { note, html });
+                }
+                "list" | "search" | "tasks" if cli.command.is_none() && cli.note.is_none() => {
+                    let query = match arg.as_str() {
+                        "list" => Query::List,
+                        "search" => Query::Search,
+                        _ => Query::Tasks,
+                    };
+                    let (mut words, mut json) = (Vec::new(), false);
+                    for opt in args.by_ref() {
+                        match opt.as_str() {
+                            "--json" => json = true,
+                            _ if query == Query::Search && !opt.starts_with("--") => words.push(opt),
+                            other => bail!("Unexpected {} argument '{}'", arg, other),
+                        }
+                    }
+                    let text = (!words.is_empty()).then(|| words.join(" "));
+                    if query == Query::Search && text.is_none() {
+                        bail!("search requires a query");
+                    }
+                    cli.command = Some(Command::Query { query, text, json });
+                }
+                "completions" if cli.command.is_none() && cli.note.is_none() => {
+                    let Some(shell) = args.next() else {
+                        bail!("completions requires a shell (bash, zsh or fish)");
+                    };
+                    cli.command = Some(Command::Completions { shell });
+                }
+                "__complete" if cli.command.is_none() => {
+                    let Some(what) = args.next() else {
+                        bail!("__complete requires notes or profiles");
+                    };
+                    cli.command = Some(Command::Complete { what });
+                }
+                note if !note.starts_with('-') && cli.note.is_none() && cli.command.is_none() => {
+                    cli.note = Some(note.to_string());
+                }
+                other => bail!("Unexpected argument '{}'\n\n{}", other, USAGE),
+            }
+        }
+        Ok(cli)
+    }
+}
+
+/// Plain stdin picker shown before the TUI starts when profiles exist but
+/// none was requested. An empty answer keeps the top-level settings.
+pub fn prompt_profile(names: &[String]) -> Result<Option<String>> {
+    use std::io::{BufRead, Write};
+
+    let mut stdout = std::io::stdout();
+    writeln!(stdout, "Select a profile:")?;
+    writeln!(stdout, "  0) (default)")?;
+    for (i, name) in names.iter().enumerate() {
+        writeln!(stdout, "  {}) {}", i + 1, name)?;
+    }
+    write!(stdout, "> ")?;
+    stdout.flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim();
+    if answer.is_empty() || answer == "0" {
+        return Ok(None);
+    }
+    if let Ok(n) = answer.parse::<usize>() {
+        return match names.get(n.wrapping_sub(1)) {
+            Some(name) => Ok(Some(name.clone())),
+            None => bail!("No profile numbered {}", n),
+        };
+    }
+    Ok(Some(answer.to_string()))
+}
+
+pub enum LockedChoice {
+    ReadOnly,
+    OpenAnyway,
+    Quit,
+}
+
+pub fn prompt_vault_locked(dir: &std::path::Path, pid: Option<u32>) -> Result<LockedChoice> {
+    use std::io::{BufRead, Write};
+
+    let mut stdout = std::io::stdout();
+    let holder = pid.map_or_else(|| "another lazynotes".to_string(), |pid| format!("lazynotes (pid {})", pid));
+    writeln!(stdout, "{} is already open in {}.", dir.display(), holder)?;
+    writeln!(stdout, "Editing it from two instances can overwrite changes.")?;
+    write!(stdout, "[r]ead-only, [o]pen anyway, [q]uit: ")?;
+    stdout.flush()?;
+
+    let mut answer = String::new();
+    if std::io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Ok(LockedChoice::Quit);
+    }
+    match answer.trim() {
+        "r" | "R" | "" => Ok(LockedChoice::ReadOnly),
+        "o" | "O" => Ok(LockedChoice::OpenAnyway),
+        _ => Ok(LockedChoice::Quit),
+    }
+}
+
+pub enum MissingDirChoice {
+    Retry,
+    ChangePath(String),
+    Quit,
+}
+
+pub fn prompt_missing_notes_dir(dir: &std::path::Path) -> Result<MissingDirChoice> {
+    use std::io::{BufRead, Write};
+
+    let mut stdout = std::io::stdout();
+    let stdin = std::io::stdin();
+    writeln!(stdout, "Notes directory {} is not available.", dir.display())?;
+    writeln!(stdout, "If it lives on a removable or network drive, mount it and retry.")?;
+    write!(stdout, "[r]etry, [c]hange path, [q]uit: ")?;
+    stdout.flush()?;
+
+    let mut answer = String::new();
+    if stdin.lock().read_line(&mut answer)? == 0 {
+        return Ok(MissingDirChoice::Quit);
+    }
+    match answer.trim() {
+        "r" | "R" | "" => Ok(MissingDirChoice::Retry),
+        "c" | "C" => {
+            write!(stdout, "Notes directory for this session: ")?;
+            stdout.flush()?;
+            let mut path = String::new();
+            stdin.lock().read_line(&mut path)?;
+            Ok(MissingDirChoice::ChangePath(path.trim().to_string()))
+        }
+        _ => Ok(MissingDirChoice::Quit),
+    }
+}
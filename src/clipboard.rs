@@ -0,0 +1,178 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardMode {
+    /// OSC 52 inside SSH sessions, otherwise a system tool when one is found.
+    #[default]
+    Auto,
+    Osc52,
+    System,
+    /// Never leave the app; copies only land in the internal register.
+    Internal,
+}
+
+/// Yanks kept for the paste-from-history popup.
+const HISTORY_LEN: usize = 20;
+
+/// Copies always fill the internal register so paste works even when the
+/// terminal or system clipboard silently ignores us.
+pub struct Clipboard {
+    mode: ClipboardMode,
+    pub register: String,
+    /// Earlier copies, newest first; the front is always `register`.
+    pub history: VecDeque<String>,
+    /// `register` came from a block selection and pastes as a column.
+    pub block: bool,
+}
+
+impl Clipboard {
+    pub fn new(mode: ClipboardMode) -> Self {
+        Self { mode, register: String::new(), history: VecDeque::new(), block: false }
+    }
+
+    /// Returns a short description of where the text went, for the status bar.
+    pub fn copy(&mut self, text: &str) -> Result<&'static str> {
+        self.register = text.to_string();
+        self.block = false;
+        self.remember(text);
+        match self.effective_mode() {
+            ClipboardMode::Osc52 => {
+                write_osc52(text)?;
+                Ok("terminal clipboard (OSC 52)")
+            }
+            ClipboardMode::System => {
+                copy_with_system_tool(text)?;
+                Ok("system clipboard")
+            }
+            ClipboardMode::Internal | ClipboardMode::Auto => Ok("internal register"),
+        }
+    }
+
+    /// Copies `html` as `text/html` where the system tool can (wl-copy,
+    /// xclip), so it pastes formatted; elsewhere as its source text.
+    pub fn copy_html(&mut self, html: &str) -> Result<&'static str> {
+        if self.effective_mode() != ClipboardMode::System {
+            return self.copy(html);
+        }
+        let typed: Option<(&str, &[&str])> = match system_tool() {
+            Some(("wl-copy", _)) => Some(("wl-copy", &["--type", "text/html"])),
+            Some(("xclip", _)) => Some(("xclip", &["-selection", "clipboard", "-t", "text/html"])),
+            _ => None,
+        };
+        let Some(tool) = typed else { return self.copy(html) };
+        self.register = html.to_string();
+        self.block = false;
+        self.remember(html);
+        run_tool(tool, html)?;
+        Ok("system clipboard as HTML")
+    }
+
+    /// Makes history entry `index` the register again, so plain paste
+    /// repeats it.
+    pub fn promote(&mut self, index: usize) -> Option<String> {
+        let text = self.history.remove(index)?;
+        self.history.push_front(text.clone());
+        self.register = text.clone();
+        self.block = false;
+        Some(text)
+    }
+
+    fn remember(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.history.retain(|t| t != text);
+        self.history.push_front(text.to_string());
+        self.history.truncate(HISTORY_LEN);
+    }
+
+    fn effective_mode(&self) -> ClipboardMode {
+        match self.mode {
+            ClipboardMode::Auto if in_ssh_session() => ClipboardMode::Osc52,
+            ClipboardMode::Auto if system_tool().is_some() => ClipboardMode::System,
+            ClipboardMode::Auto => ClipboardMode::Osc52,
+            mode => mode,
+        }
+    }
+}
+
+fn in_ssh_session() -> bool {
+    ["SSH_CONNECTION", "SSH_CLIENT", "SSH_TTY"]
+        .iter()
+        .any(|v| std::env::var_os(v).is_some())
+}
+
+fn system_tool() -> Option<(&'static str, &'static [&'static str])> {
+    let candidates: [(&str, &[&str], bool); 4] = [
+        ("pbcopy", &[], cfg!(target_os = "macos")),
+        ("wl-copy", &[], std::env::var_os("WAYLAND_DISPLAY").is_some()),
+        ("xclip", &["-selection", "clipboard"], std::env::var_os("DISPLAY").is_some()),
+        ("xsel", &["--clipboard", "--input"], std::env::var_os("DISPLAY").is_some()),
+    ];
+    candidates
+        .into_iter()
+        .filter(|(_, _, usable)| *usable)
+        .find(|(bin, _, _)| which(bin))
+        .map(|(bin, args, _)| (bin, args))
+}
+
+fn which(bin: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(bin).is_file()))
+        .unwrap_or(false)
+}
+
+fn copy_with_system_tool(text: &str) -> Result<()> {
+    let Some(tool) = system_tool() else {
+        bail!("No clipboard tool found (pbcopy, wl-copy, xclip or xsel)");
+    };
+    run_tool(tool, text)
+}
+
+fn run_tool((bin, args): (&str, &[&str]), text: &str) -> Result<()> {
+    let mut child = Command::new(bin)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Spawn {}", bin))?;
+    if let Some(stdin) = child.stdin.as_mut() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    child.wait()?;
+    Ok(())
+}
+
+fn write_osc52(text: &str) -> Result<()> {
+    let seq = format!("\x1b]52;c;{}\x07", base64_encode(text.as_bytes()));
+    // tmux swallows OSC 52 unless it is wrapped in a DCS passthrough.
+    let seq = if std::env::var_os("TMUX").is_some() {
+        format!("\x1bPtmux;{}\x1b\\", seq.replace('\x1b', "\x1b\x1b"))
+    } else {
+        seq
+    };
+    let mut stdout = std::io::stdout();
+    stdout.write_all(seq.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        out.push(TABLE[(n >> 18) as usize & 63] as char);
+        out.push(TABLE[(n >> 12) as usize & 63] as char);
+        out.push(if chunk.len() > 1 { TABLE[(n >> 6) as usize & 63] as char } else { '=' });
+        out.push(if chunk.len() > 2 { TABLE[n as usize & 63] as char } else { '=' });
+    }
+    out
+}
@@ -0,0 +1,108 @@
+//! Shell completion scripts. Note paths and profile names are completed
+//! dynamically by calling back into `lazynotes __complete <notes|profiles>`.
+
+use crate::config::Config;
+use crate::fs::{build_notes_tree, collect_files};
+use crate::search::relative_to;
+use anyhow::{bail, Result};
+
+pub const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+pub fn script(shell: &str) -> Result<&'static str> {
+    Ok(match shell {
+        "bash" => BASH,
+        "zsh" => ZSH,
+        "fish" => FISH,
+        other => bail!("Unsupported shell '{}' (expected one of: {})", other, SHELLS.join(", ")),
+    })
+}
+
+/// Prints one candidate per line for the hidden `__complete` command.
+pub fn candidates(what: &str) -> Result<Vec<String>> {
+    let config = Config::load_or_create()?;
+    match what {
+        "profiles" => Ok(config.profile_names()),
+        "notes" => {
+            let config = match &config.default_profile {
+                Some(name) => config.with_profile(name)?,
+                None => config,
+            };
+            let root = config.notes_path();
+            let Ok(tree) = build_notes_tree(&root, config.symlinks) else { return Ok(Vec::new()) };
+            Ok(collect_files(&tree)
+                .iter()
+                .filter(|p| p.extension().is_some_and(|e| e == "md"))
+                .map(|p| relative_to(p, &root).to_string_lossy().into_owned())
+                .collect())
+        }
+        other => bail!("Nothing to complete for '{}'", other),
+    }
+}
+
+const BASH: &str = r#"_lazynotes() {
+    local cur prev
+    cur="${COMP_WORDS[COMP_CWORD]}"
+    prev="${COMP_WORDS[COMP_CWORD-1]}"
+    case "$prev" in
+        -p|--profile)
+            COMPREPLY=($(compgen -W "$(lazynotes __complete profiles 2>/dev/null)" -- "$cur"))
+            return ;;
+        completions)
+            COMPREPLY=($(compgen -W "bash zsh fish" -- "$cur"))
+            return ;;
+    esac
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "--profile --debug --help --version" -- "$cur"))
+        return
+    fi
+    local IFS=$'\n'
+    local words="$(lazynotes __complete notes 2>/dev/null)"
+    if [[ $COMP_CWORD -eq 1 ]]; then
+        words+=$'\n'completions$'\n'capture$'\n'cat$'\n'list$'\n'search$'\n'tasks
+    fi
+    COMPREPLY=($(compgen -W "$words" -- "$cur"))
+}
+complete -F _lazynotes lazynotes
+"#;
+
+const ZSH: &str = r#"#compdef lazynotes
+
+_lazynotes() {
+    local state
+    _arguments -C \
+        '(-p --profile)'{-p,--profile}'[use the named profile]:profile:->profiles' \
+        '--debug[write a debug log]' \
+        '(-h --help)'{-h,--help}'[print help]' \
+        '(-V --version)'{-V,--version}'[print version]' \
+        '1: :->first' \
+        '2: :->second'
+    case $state in
+        profiles) compadd -- ${(f)"$(lazynotes __complete profiles 2>/dev/null)"} ;;
+        first) compadd -- completions capture cat list search tasks ${(f)"$(lazynotes __complete notes 2>/dev/null)"} ;;
+        second)
+            case $words[2] in
+                completions) compadd -- bash zsh fish ;;
+                cat) compadd -- --html ${(f)"$(lazynotes __complete notes 2>/dev/null)"} ;;
+            esac ;;
+    esac
+}
+
+_lazynotes "$@"
+"#;
+
+const FISH: &str = r#"complete -c lazynotes -f
+complete -c lazynotes -s p -l profile -x -a '(lazynotes __complete profiles 2>/dev/null)' -d 'Use the named profile'
+complete -c lazynotes -l debug -d 'Write a debug log'
+complete -c lazynotes -s h -l help -d 'Print help'
+complete -c lazynotes -s V -l version -d 'Print version'
+complete -c lazynotes -n '__fish_use_subcommand' -a completions -d 'Print shell completions'
+complete -c lazynotes -n '__fish_use_subcommand' -a capture -d 'Append to the inbox note'
+complete -c lazynotes -n '__fish_use_subcommand' -a cat -d 'Print a note'
+complete -c lazynotes -n '__fish_seen_subcommand_from cat' -l html -d 'Render as HTML'
+complete -c lazynotes -n '__fish_use_subcommand' -a list -d 'Print all notes'
+complete -c lazynotes -n '__fish_use_subcommand' -a search -d 'Print matching lines'
+complete -c lazynotes -n '__fish_use_subcommand' -a tasks -d 'Print open tasks'
+complete -c lazynotes -n '__fish_seen_subcommand_from list search tasks' -l json -d 'Print JSON'
+complete -c lazynotes -n '__fish_seen_subcommand_from completions' -a 'bash zsh fish'
+complete -c lazynotes -n 'not __fish_seen_subcommand_from completions' -a '(lazynotes __complete notes 2>/dev/null)'
+"#;
@@ -1,15 +1,220 @@
+use crate::clipboard::ClipboardMode;
+use crate::theme::{Background, IconSet};
+use anyhow::bail;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::{fs, path::PathBuf};
 use dirs::home_dir;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub notes_dir: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_email: Option<String>,
+    pub theme: String,
+    /// `auto` (detect from the terminal), `dark` or `light`.
+    pub background: Background,
+    /// Sidebar icons: `emoji`, `nerd` (needs a Nerd Font) or `ascii`.
+    pub icons: IconSet,
+    /// Show how many notes, and words, each sidebar folder holds.
+    pub folder_counts: bool,
+    /// Symlinks in the vault: `follow` (loops are cut off) or `skip`.
+    pub symlinks: Symlinks,
+    /// `auto`, `osc52`, `system` or `internal`.
+    pub clipboard: ClipboardMode,
+    /// Soft-wrap long lines in Content; when off the pane scrolls horizontally.
+    pub wrap: bool,
+    /// What Tab does in Content: `spaces`, `tab` or `focus`.
+    pub tab_mode: TabMode,
+    pub tab_width: usize,
+    /// Auto-insert closing `)`, `]`, backticks and `*` while typing.
+    pub auto_pair: bool,
+    /// Column that reflow (Alt+W) wraps paragraphs at.
+    pub text_width: usize,
+    /// Break lines at `text_width` while typing.
+    pub auto_wrap: bool,
+    /// strftime-style formats offered by insert date/time (Alt+D); the
+    /// first is inserted straight away when it is the only one.
+    pub date_formats: Vec<String>,
+    /// Update the notes repo in the background at startup: `off`, `rebase`
+    /// (`git pull --rebase --autostash`) or `ff` (fast-forward only).
+    pub pull_on_launch: LaunchPull,
+    /// strftime-style format of commit dates shown as absolute timestamps
+    /// (toggled with `t` in the Commits pane).
+    pub commit_date_format: String,
+    /// Language of relative commit dates (`en`, `de`, `fr`, `es`); taken
+    /// from `LC_TIME`/`LANG` when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_locale: Option<String>,
+    /// Note that `lazynotes capture` appends to and Alt+I opens, relative to
+    /// the notes directory.
+    pub inbox: String,
+    /// strftime-style prefix of each captured entry.
+    pub capture_format: String,
+    /// Keep snapshots of saved notes in `.history/` in the vault, browsable
+    /// under File > Local history even without git. Unset means on unless
+    /// the vault is in a git repository.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_history: Option<bool>,
+    /// Minutes between two snapshots of the same note.
+    pub snapshot_interval: u64,
+    /// Size cap of `.history/` in MiB; the oldest snapshots go first.
+    pub history_max_mb: u64,
+    /// Keep the scratch buffer (Alt+S) in `scratch.md` in the config dir
+    /// between sessions; otherwise it only lives in memory.
+    pub persist_scratch: bool,
+    /// Layout of commit messages, e.g. `{type}({scope}): {summary}`. When
+    /// set, committing asks for the three parts instead of a free-form
+    /// message; an empty scope drops its parentheses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_template: Option<String>,
+    /// Types offered (Up/Down) in the `{type}` field of `commit_template`.
+    pub commit_types: Vec<String>,
+    /// On quit, commit whatever is uncommitted in the vault as one commit,
+    /// so closing the terminal never leaves it dirty.
+    pub commit_on_exit: bool,
+    /// strftime-style message of that commit.
+    pub exit_commit_message: String,
+    /// Run "Format note" on every save.
+    pub format_on_save: bool,
+    /// Treat the note's first `# Heading` as its title: editing either one
+    /// updates the other.
+    pub title_sync: bool,
+    /// Show simple TeX math as Unicode (`\alpha^2` as `α²`) in the note
+    /// preview and presentation mode, instead of only highlighting it.
+    pub math_unicode: bool,
+    /// How saving names the file after the title: `title` (as typed),
+    /// `kebab` or `snake`.
+    pub filename_style: FilenameStyle,
+    /// Shell command that formats Markdown from stdin to stdout, used by
+    /// "Format note" instead of the built-in formatter.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formatter: Option<String>,
+    /// Programs that open files with the given extensions from the sidebar,
+    /// e.g. `pdf = "zathura"`, instead of the system default. Split on
+    /// whitespace; the path is added as the last argument.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub open_with: BTreeMap<String, String>,
+    /// Languages whose fenced blocks "Run code block" may execute, with
+    /// the shell command that reads the block on stdin.
+    pub run_blocks: BTreeMap<String, String>,
+    /// Profile used when `--profile` is not given. Without it, a picker is
+    /// shown at launch whenever profiles are defined.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TabMode {
+    /// Tab inserts spaces up to the next `tab_width` stop.
+    #[default]
+    Spaces,
+    /// Tab inserts a literal `\t`.
+    Tab,
+    /// Tab cycles pane focus, as in the other panes.
+    Focus,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchPull {
+    #[default]
+    Off,
+    Rebase,
+    /// Fetch, then fast-forward when the branch hasn't diverged.
+    Ff,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilenameStyle {
+    #[default]
+    Title,
+    /// `Weekly Review` is saved as `weekly-review.md`.
+    Kebab,
+    Snake,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Symlinks {
+    #[default]
+    Follow,
+    /// Leaves symlinked files and folders out of the sidebar and searches.
+    Skip,
+}
+
+/// A named override set, e.g. `[profiles.work]`. Unset fields fall back to
+/// the top-level values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub notes_dir: Option<String>,
+    pub git_name: Option<String>,
+    pub git_email: Option<String>,
+    pub theme: Option<String>,
+    pub commit_template: Option<String>,
+    pub commit_types: Option<Vec<String>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let default_dir = home_dir()
+            .unwrap_or_default()
+            .join("Documents")
+            .join("Notes");
+        Self {
+            notes_dir: default_dir.to_string_lossy().to_string(),
+            git_name: None,
+            git_email: None,
+            theme: "default".to_string(),
+            background: Background::Auto,
+            icons: IconSet::Emoji,
+            folder_counts: false,
+            symlinks: Symlinks::Follow,
+            clipboard: ClipboardMode::Auto,
+            wrap: true,
+            tab_mode: TabMode::Spaces,
+            tab_width: 4,
+            auto_pair: false,
+            text_width: 80,
+            auto_wrap: false,
+            date_formats: vec!["%Y-%m-%d".into(), "%H:%M".into(), "%Y-%m-%d %H:%M".into()],
+            pull_on_launch: LaunchPull::Off,
+            commit_date_format: "%Y-%m-%d %H:%M".into(),
+            date_locale: None,
+            inbox: "Inbox.md".into(),
+            capture_format: "- %Y-%m-%d %H:%M ".into(),
+            local_history: None,
+            snapshot_interval: 10,
+            history_max_mb: 50,
+            commit_template: None,
+            commit_types: ["docs", "feat", "fix", "refactor", "chore"].map(String::from).to_vec(),
+            persist_scratch: false,
+            commit_on_exit: false,
+            exit_commit_message: "Session %Y-%m-%d %H:%M".into(),
+            format_on_save: false,
+            title_sync: false,
+            math_unicode: false,
+            filename_style: FilenameStyle::Title,
+            formatter: None,
+            open_with: BTreeMap::new(),
+            run_blocks: [("sh", "sh"), ("bash", "bash")].into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            default_profile: None,
+            profiles: BTreeMap::new(),
+        }
+    }
 }
 
 impl Config {
     pub fn load_or_create() -> anyhow::Result<Self> {
-        let cfg_dir = home_dir().unwrap_or_default().join(".config").join("lazynotes");
+        let cfg_dir = config_dir();
         fs::create_dir_all(&cfg_dir)?;
         let cfg_path = cfg_dir.join("config.toml");
 
@@ -18,13 +223,7 @@ impl Config {
             let cfg: Config = toml::from_str(&s)?;
             Ok(cfg)
         } else {
-            let default_dir = home_dir()
-                .unwrap_or_default()
-                .join("Documents")
-                .join("Notes");
-            let cfg = Config {
-                notes_dir: default_dir.to_string_lossy().to_string(),
-            };
+            let cfg = Config::default();
             let content = toml::to_string_pretty(&cfg)?;
             fs::write(&cfg_path, content)?;
             Ok(cfg)
@@ -34,13 +233,52 @@ impl Config {
     pub fn notes_path(&self) -> PathBuf {
         expand_tilde(&self.notes_dir)
     }
-}
 
-fn expand_tilde(path: &str) -> PathBuf {
-    if let Some(stripped) = path.strip_prefix("~/") {
-        if let Some(home) = home_dir() {
-            return home.join(stripped);
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profiles.keys().cloned().collect()
+    }
+
+    /// Returns a copy of the config with the named profile's overrides applied.
+    pub fn with_profile(&self, name: &str) -> anyhow::Result<Config> {
+        let Some(profile) = self.profiles.get(name) else {
+            bail!(
+                "Unknown profile '{}' (available: {})",
+                name,
+                self.profile_names().join(", ")
+            );
+        };
+        let mut cfg = self.clone();
+        if let Some(dir) = &profile.notes_dir {
+            cfg.notes_dir = dir.clone();
+        }
+        if profile.git_name.is_some() {
+            cfg.git_name = profile.git_name.clone();
+        }
+        if profile.git_email.is_some() {
+            cfg.git_email = profile.git_email.clone();
+        }
+        if let Some(theme) = &profile.theme {
+            cfg.theme = theme.clone();
+        }
+        if profile.commit_template.is_some() {
+            cfg.commit_template = profile.commit_template.clone();
         }
+        if let Some(types) = &profile.commit_types {
+            cfg.commit_types = types.clone();
+        }
+        Ok(cfg)
+    }
+}
+
+pub fn config_dir() -> PathBuf {
+    home_dir().unwrap_or_default().join(".config").join("lazynotes")
+}
+
+pub fn expand_tilde(path: &str) -> PathBuf {
+    if let Some(stripped) = path.strip_prefix("~/")
+        && let Some(home) = home_dir()
+    {
+        return home.join(stripped);
     }
     PathBuf::from(path)
 }
@@ -0,0 +1,84 @@
+//! Per-note encryption. A note whose frontmatter says `encrypted: true`
+//! keeps that frontmatter readable and stores its body as an ASCII-armored
+//! `gpg --symmetric` message. Keys are asked for on open and only kept in
+//! memory for the session.
+
+use crate::process;
+use anyhow::{bail, Context, Result};
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+const ARMOR_BEGIN: &str = "-----BEGIN PGP MESSAGE-----";
+const GPG_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// `(frontmatter including its closing line, body)`; the frontmatter is
+/// empty when `content` doesn't start with one.
+fn split_frontmatter(content: &str) -> (&str, &str) {
+    if !content.starts_with("---\n") && !content.starts_with("---\r\n") {
+        return ("", content);
+    }
+    let mut at = content.find('\n').map_or(content.len(), |i| i + 1);
+    while at < content.len() {
+        let end = content[at..].find('\n').map_or(content.len(), |i| at + i + 1);
+        if matches!(content[at..end].trim(), "---" | "...") {
+            return content.split_at(end);
+        }
+        at = end;
+    }
+    ("", content)
+}
+
+/// Whether the frontmatter of `content` asks for encryption.
+pub fn is_marked(content: &str) -> bool {
+    split_frontmatter(content).0.lines().any(|line| {
+        line.split_once(':')
+            .is_some_and(|(key, value)| key == "encrypted" && value.trim() == "true")
+    })
+}
+
+/// Whether `content` is a marked note whose body is already encrypted.
+pub fn is_sealed(content: &str) -> bool {
+    is_marked(content) && split_frontmatter(content).1.trim_start().starts_with(ARMOR_BEGIN)
+}
+
+/// Checks the start of the file only, which is enough for the frontmatter,
+/// so the sidebar can mark encrypted notes cheaply.
+pub fn file_is_marked(path: &Path) -> bool {
+    let Ok(file) = std::fs::File::open(path) else { return false };
+    let mut head = Vec::new();
+    if file.take(4096).read_to_end(&mut head).is_err() {
+        return false;
+    }
+    is_marked(&String::from_utf8_lossy(&head))
+}
+
+/// Encrypts the body of `content` with `key`, keeping the frontmatter.
+pub fn seal(content: &str, key: &str) -> Result<String> {
+    let (frontmatter, body) = split_frontmatter(content);
+    let armored = gpg(&["--symmetric", "--armor"], key, body)?;
+    Ok(format!("{}{}", frontmatter, armored))
+}
+
+/// Inverse of [`seal`]; fails on a wrong key.
+pub fn unseal(content: &str, key: &str) -> Result<String> {
+    let (frontmatter, body) = split_frontmatter(content);
+    let plain = gpg(&["--decrypt"], key, body)?;
+    Ok(format!("{}{}", frontmatter, plain))
+}
+
+/// Runs gpg with the key on the first line of stdin and `input` after it.
+fn gpg(args: &[&str], key: &str, input: &str) -> Result<String> {
+    let mut cmd = Command::new("gpg");
+    cmd.args(["--batch", "--yes", "--quiet", "--no-symkey-cache", "--pinentry-mode", "loopback", "--passphrase-fd", "0"]).args(args);
+    let output = process::run_piped(cmd, format!("{}\n{}", key, input), GPG_TIMEOUT).context("Running gpg (needed for encrypted notes)")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("Bad session key") {
+            bail!("Wrong key");
+        }
+        bail!("gpg failed: {}", stderr.trim());
+    }
+    String::from_utf8(output.stdout).context("Decrypted note is not UTF-8")
+}
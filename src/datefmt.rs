@@ -0,0 +1,111 @@
+//! strftime-style formatting for the insert-date command; just the
+//! specifiers people put in notes.
+
+use time::OffsetDateTime;
+
+/// The local time, falling back to UTC when the offset is unknown.
+pub fn now() -> OffsetDateTime {
+    OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc())
+}
+
+/// Supports `%Y %y %m %d %e %H %I %M %S %p %j %a %A %b %B %F %T %R %%`;
+/// anything else is copied through as written.
+pub fn strftime(t: OffsetDateTime, fmt: &str) -> String {
+    let mut out = String::new();
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let Some(spec) = chars.next() else {
+            out.push('%');
+            break;
+        };
+        let month = t.month();
+        let weekday = t.weekday();
+        match spec {
+            'Y' => out.push_str(&t.year().to_string()),
+            'y' => out.push_str(&format!("{:02}", t.year().rem_euclid(100))),
+            'm' => out.push_str(&format!("{:02}", u8::from(month))),
+            'd' => out.push_str(&format!("{:02}", t.day())),
+            'e' => out.push_str(&format!("{:>2}", t.day())),
+            'H' => out.push_str(&format!("{:02}", t.hour())),
+            'I' => out.push_str(&format!("{:02}", (t.hour() + 11) % 12 + 1)),
+            'M' => out.push_str(&format!("{:02}", t.minute())),
+            'S' => out.push_str(&format!("{:02}", t.second())),
+            'p' => out.push_str(if t.hour() < 12 { "AM" } else { "PM" }),
+            'j' => out.push_str(&format!("{:03}", t.ordinal())),
+            'a' => out.push_str(&weekday.to_string()[..3]),
+            'A' => out.push_str(&weekday.to_string()),
+            'b' => out.push_str(&month.to_string()[..3]),
+            'B' => out.push_str(&month.to_string()),
+            'F' => out.push_str(&strftime(t, "%Y-%m-%d")),
+            'T' => out.push_str(&strftime(t, "%H:%M:%S")),
+            'R' => out.push_str(&strftime(t, "%H:%M")),
+            '%' => out.push('%'),
+            other => {
+                out.push('%');
+                out.push(other);
+            }
+        }
+    }
+    out
+}
+
+/// Language of relative dates: the first two letters of `LC_ALL`,
+/// `LC_TIME` or `LANG`, whichever is set first.
+pub fn env_language() -> String {
+    ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|v| !v.is_empty())
+        .map(|v| v.chars().take(2).collect::<String>().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// "3 days ago" in `language` (`en`, `de`, `fr` or `es`; anything else
+/// gets English), counting from `now` back to `then`.
+pub fn relative(then: OffsetDateTime, now: OffsetDateTime, language: &str) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    let secs = (now - then).whole_seconds();
+    let (n, unit) = match secs {
+        s if s < MINUTE => return just_now(language).to_string(),
+        s if s < HOUR => (s / MINUTE, 0),
+        s if s < DAY => (s / HOUR, 1),
+        s if s < 14 * DAY => (s / DAY, 2),
+        s if s < 60 * DAY => (s / (7 * DAY), 3),
+        s if s < 365 * DAY => (s / (30 * DAY), 4),
+        s => (s / (365 * DAY), 5),
+    };
+    let (one, many) = units(language)[unit];
+    let unit = if n == 1 { one } else { many };
+    match language {
+        "de" => format!("vor {} {}", n, unit),
+        "fr" => format!("il y a {} {}", n, unit),
+        "es" => format!("hace {} {}", n, unit),
+        _ => format!("{} {} ago", n, unit),
+    }
+}
+
+fn just_now(language: &str) -> &'static str {
+    match language {
+        "de" => "gerade eben",
+        "fr" => "à l'instant",
+        "es" => "justo ahora",
+        _ => "just now",
+    }
+}
+
+/// Singular and plural of minute, hour, day, week, month and year.
+fn units(language: &str) -> [(&'static str, &'static str); 6] {
+    match language {
+        // Dative, as it follows "vor".
+        "de" => [("Minute", "Minuten"), ("Stunde", "Stunden"), ("Tag", "Tagen"), ("Woche", "Wochen"), ("Monat", "Monaten"), ("Jahr", "Jahren")],
+        "fr" => [("minute", "minutes"), ("heure", "heures"), ("jour", "jours"), ("semaine", "semaines"), ("mois", "mois"), ("an", "ans")],
+        "es" => [("minuto", "minutos"), ("hora", "horas"), ("día", "días"), ("semana", "semanas"), ("mes", "meses"), ("año", "años")],
+        _ => [("minute", "minutes"), ("hour", "hours"), ("day", "days"), ("week", "weeks"), ("month", "months"), ("year", "years")],
+    }
+}
@@ -0,0 +1,45 @@
+//! ```` ```mermaid ```` blocks: rendered to SVG by mermaid-cli (`mmdc`)
+//! when it is installed, then handed to the system viewer.
+
+use anyhow::{bail, Context, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Language tag of the blocks handled here.
+pub const MERMAID: &str = "mermaid";
+
+/// Renders `code` to an SVG in the temp dir and returns its path. The
+/// name comes from the source, so rendering the same block again reuses it.
+pub fn render(code: &[String]) -> Result<PathBuf> {
+    let source = code.join("\n");
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    let base = std::env::temp_dir().join(format!("lazynotes-diagram-{:016x}", hasher.finish()));
+    let (input, output) = (base.with_extension("mmd"), base.with_extension("svg"));
+    if output.is_file() {
+        return Ok(output);
+    }
+    std::fs::write(&input, &source).with_context(|| format!("Writing {}", input.display()))?;
+    let result = Command::new("mmdc")
+        .arg("-i")
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output();
+    let _ = std::fs::remove_file(&input);
+    let out = match result {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            bail!("mmdc not found; install @mermaid-js/mermaid-cli to render diagrams")
+        }
+        other => other.context("Running mmdc")?,
+    };
+    if !out.status.success() {
+        bail!("mmdc failed: {}", String::from_utf8_lossy(&out.stderr).trim());
+    }
+    Ok(output)
+}
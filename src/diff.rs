@@ -0,0 +1,83 @@
+//! Line diff for the "what did I change" view: a plain LCS over the lines
+//! between the common prefix and suffix, which is plenty for notes.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+    /// A run of unchanged lines folded away by [`with_context`].
+    Skipped(usize),
+}
+
+/// Above this many LCS cells the changed middle is shown as one removed
+/// block followed by one added block.
+const MAX_CELLS: usize = 4_000_000;
+
+pub fn diff_lines(old: &[String], new: &[String]) -> Vec<DiffLine> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (a, b) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut out: Vec<DiffLine> = old[..prefix].iter().cloned().map(DiffLine::Same).collect();
+    if a.len() * b.len() > MAX_CELLS {
+        out.extend(a.iter().cloned().map(DiffLine::Removed));
+        out.extend(b.iter().cloned().map(DiffLine::Added));
+    } else {
+        // lcs[i][j]: length of the LCS of a[i..] and b[j..].
+        let w = b.len() + 1;
+        let mut lcs = vec![0u32; (a.len() + 1) * w];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i * w + j] = if a[i] == b[j] { lcs[(i + 1) * w + j + 1] + 1 } else { lcs[(i + 1) * w + j].max(lcs[i * w + j + 1]) };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                out.push(DiffLine::Same(a[i].clone()));
+                i += 1;
+                j += 1;
+            } else if i < a.len() && (j == b.len() || lcs[(i + 1) * w + j] >= lcs[i * w + j + 1]) {
+                out.push(DiffLine::Removed(a[i].clone()));
+                i += 1;
+            } else {
+                out.push(DiffLine::Added(b[j].clone()));
+                j += 1;
+            }
+        }
+    }
+    out.extend(old[old.len() - suffix..].iter().cloned().map(DiffLine::Same));
+    out
+}
+
+/// Keeps `context` unchanged lines around each change and folds the rest.
+pub fn with_context(lines: Vec<DiffLine>, context: usize) -> Vec<DiffLine> {
+    let mut keep = vec![false; lines.len()];
+    for (i, line) in lines.iter().enumerate() {
+        if !matches!(line, DiffLine::Same(_)) {
+            let end = (i + context + 1).min(lines.len());
+            keep[i.saturating_sub(context)..end].iter_mut().for_each(|k| *k = true);
+        }
+    }
+    let mut out = Vec::new();
+    for (line, keep) in lines.into_iter().zip(keep) {
+        if keep {
+            out.push(line);
+        } else if let Some(DiffLine::Skipped(n)) = out.last_mut() {
+            *n += 1;
+        } else {
+            out.push(DiffLine::Skipped(1));
+        }
+    }
+    out
+}
+
+/// `(added, removed)` line counts.
+pub fn stats(lines: &[DiffLine]) -> (usize, usize) {
+    lines.iter().fold((0, 0), |(a, r), l| match l {
+        DiffLine::Added(_) => (a + 1, r),
+        DiffLine::Removed(_) => (a, r + 1),
+        _ => (a, r),
+    })
+}
@@ -0,0 +1,409 @@
+//! "Format note": a conservative built-in Markdown normalizer, or an
+//! external command configured as `formatter` that filters the note
+//! through stdin/stdout.
+
+use crate::markdown;
+use crate::process;
+use anyhow::{bail, Context, Result};
+use std::time::Duration;
+
+/// Formatters still running after this are killed.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Trims trailing whitespace (keeping two-space hard breaks), collapses
+/// runs of blank lines, puts blank lines around headings, re-indents
+/// nested lists under their parent's text and aligns table columns.
+/// Fenced code blocks are left untouched.
+pub fn format_markdown(lines: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::with_capacity(lines.len());
+    // Only a fence like the one that opened a block closes it, as in
+    // `markdown::code_blocks`.
+    let mut fence: Option<(char, usize)> = None;
+    let mut lists = ListIndenter::default();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = &lines[i];
+        if let Some(marker) = markdown::fence_marker(line)
+            && fence.is_none_or(|(c, n)| marker.0 == c && marker.1 >= n)
+        {
+            fence = if fence.is_some() { None } else { Some(marker) };
+            out.push(line.trim_end().to_string());
+            i += 1;
+            continue;
+        }
+        if fence.is_some() {
+            out.push(line.clone());
+            i += 1;
+            continue;
+        }
+
+        if let Some(end) = table_end(lines, i) {
+            out.extend(align_table(&lines[i..end]));
+            i = end;
+            continue;
+        }
+
+        let next_is_text = lines.get(i + 1).is_some_and(|l| !l.trim().is_empty());
+        let line = trim_trailing(line, next_is_text);
+        if line.is_empty() {
+            lists.reset_if_blank_run(&out);
+            if !out.last().is_some_and(|l| l.is_empty()) && !out.is_empty() {
+                out.push(String::new());
+            }
+        } else if is_heading(&line) {
+            lists = ListIndenter::default();
+            if out.last().is_some_and(|l| !l.is_empty()) {
+                out.push(String::new());
+            }
+            out.push(normalize_heading(&line));
+            if lines.get(i + 1).is_some_and(|l| !l.trim().is_empty()) {
+                out.push(String::new());
+            }
+        } else {
+            out.push(lists.reindent(line));
+        }
+        i += 1;
+    }
+    while out.last().is_some_and(|l| l.is_empty()) {
+        out.pop();
+    }
+    if out.is_empty() {
+        out.push(String::new());
+    }
+    out
+}
+
+/// Pipes the note through `command` (run by the shell) and returns its
+/// output lines. Empty output for a non-empty note is an error, so a
+/// broken formatter can't wipe the note on save.
+pub fn run_external(command: &str, lines: &[String]) -> Result<Vec<String>> {
+    let input = lines.join("\n");
+    let had_text = !input.trim().is_empty();
+    let output = process::run_piped(process::shell(command), input, TIMEOUT).with_context(|| format!("Formatter '{}'", command))?;
+    if !output.status.success() {
+        bail!("Formatter '{}' failed: {}", command, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    if had_text && text.trim().is_empty() {
+        bail!("Formatter '{}' printed nothing; the note was left as it was", command);
+    }
+    let mut out: Vec<String> = text.lines().map(str::to_string).collect();
+    if out.is_empty() {
+        out.push(String::new());
+    }
+    Ok(out)
+}
+
+fn trim_trailing(line: &str, next_is_text: bool) -> String {
+    let trimmed = line.trim_end();
+    // Two trailing spaces before more text are a Markdown hard break.
+    if next_is_text && !trimmed.is_empty() && line[trimmed.len()..].starts_with("  ") {
+        format!("{}  ", trimmed)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+fn is_heading(line: &str) -> bool {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let rest = &line[level..];
+    (1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' '))
+}
+
+/// `##   Title ##` -> `## Title`.
+fn normalize_heading(line: &str) -> String {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let title = line[level..].trim();
+    let title = title.trim_end_matches('#').trim_end();
+    if title.is_empty() {
+        "#".repeat(level)
+    } else {
+        format!("{} {}", "#".repeat(level), title)
+    }
+}
+
+/// Tracks open list items so nested ones are indented exactly to their
+/// parent's text, whatever mix of spaces and tabs they were typed with.
+#[derive(Default)]
+struct ListIndenter {
+    /// `(original indent, new text column)` per open level.
+    open: Vec<(usize, usize)>,
+}
+
+impl ListIndenter {
+    fn reindent(&mut self, line: String) -> String {
+        let indent = indent_width(&line);
+        let body = line.trim_start();
+        let Some(marker) = list_marker(body) else {
+            // Continuation text of an item keeps its parent's text column.
+            return match self.open.iter().rev().find(|(orig, _)| *orig < indent) {
+                Some(&(_, text_col)) => format!("{}{}", " ".repeat(text_col), body),
+                None => {
+                    self.open.clear();
+                    line
+                }
+            };
+        };
+        while self.open.last().is_some_and(|&(orig, _)| orig >= indent) {
+            self.open.pop();
+        }
+        let new_indent = self.open.last().map_or(0, |&(_, text_col)| text_col);
+        self.open.push((indent, new_indent + marker));
+        format!("{}{}", " ".repeat(new_indent), body)
+    }
+
+    /// Two blank lines in a row end any list.
+    fn reset_if_blank_run(&mut self, out: &[String]) {
+        if out.last().is_some_and(|l| l.is_empty()) {
+            self.open.clear();
+        }
+    }
+}
+
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// Width of a list marker and the space after it (`- `, `* `, `12. `).
+fn list_marker(body: &str) -> Option<usize> {
+    if ["- ", "* ", "+ "].iter().any(|m| body.starts_with(m)) {
+        return Some(2);
+    }
+    let digits = body.chars().take_while(char::is_ascii_digit).count();
+    let rest = &body[digits..];
+    (digits > 0 && (rest.starts_with(". ") || rest.starts_with(") "))).then_some(digits + 2)
+}
+
+/// End row (exclusive) of a pipe table starting at `start`: a header row
+/// followed by a delimiter row, then any further `|` rows.
+fn table_end(lines: &[String], start: usize) -> Option<usize> {
+    let is_row = |l: &String| l.trim_start().starts_with('|');
+    if !is_row(&lines[start]) || !lines.get(start + 1).is_some_and(|l| is_delimiter_row(l)) {
+        return None;
+    }
+    let mut end = start + 2;
+    while lines.get(end).is_some_and(is_row) {
+        end += 1;
+    }
+    Some(end)
+}
+
+fn is_delimiter_row(line: &str) -> bool {
+    let cells = split_cells(line);
+    !cells.is_empty()
+        && cells.iter().all(|c| {
+            let c = c.trim_matches(':');
+            !c.is_empty() && c.chars().all(|ch| ch == '-')
+        })
+}
+
+fn split_cells(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    line.split('|').map(|c| c.trim().to_string()).collect()
+}
+
+fn align_table(rows: &[String]) -> Vec<String> {
+    let cells: Vec<Vec<String>> = rows.iter().map(|r| split_cells(r)).collect();
+    let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+    let align: Vec<(bool, bool)> = (0..columns)
+        .map(|c| {
+            let d = cells[1].get(c).map_or("", String::as_str);
+            (d.starts_with(':'), d.len() > 1 && d.ends_with(':'))
+        })
+        .collect();
+    let widths: Vec<usize> = (0..columns)
+        .map(|c| {
+            cells
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != 1)
+                .map(|(_, row)| row.get(c).map_or(0, |s| s.chars().count()))
+                .max()
+                .unwrap_or(0)
+                .max(3)
+        })
+        .collect();
+
+    cells
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let parts: Vec<String> = (0..columns)
+                .map(|c| {
+                    let width = widths[c];
+                    if i == 1 {
+                        let (left, right) = align[c];
+                        let dashes = width - usize::from(left) - usize::from(right);
+                        format!("{}{}{}", if left { ":" } else { "" }, "-".repeat(dashes), if right { ":" } else { "" })
+                    } else {
+                        let cell = row.get(c).map_or("", String::as_str);
+                        let pad = width - cell.chars().count();
+                        match align[c] {
+                            (false, true) => format!("{}{}", " ".repeat(pad), cell),
+                            (true, true) => format!("{}{}{}", " ".repeat(pad / 2), cell, " ".repeat(pad - pad / 2)),
+                            _ => format!("{}{}", cell, " ".repeat(pad)),
+                        }
+                    }
+                })
+                .collect();
+            format!("| {} |", parts.join(" | "))
+        })
+        .collect()
+}
+
+/// Rows `(start, end)` of the paragraph around `row`: adjacent non-blank
+/// lines, where a list item starts a new one. None on rows that are
+/// never reflowed (see `verbatim_rows`).
+pub fn paragraph_bounds(lines: &[String], row: usize) -> Option<(usize, usize)> {
+    let fixed = verbatim_rows(lines);
+    if fixed[row] {
+        return None;
+    }
+    let mut start = row;
+    while start > 0 && !fixed[start - 1] && continues(&lines[start - 1], &lines[start]) {
+        start -= 1;
+    }
+    let mut end = row;
+    while end + 1 < lines.len() && !fixed[end + 1] && continues(&lines[end], &lines[end + 1]) {
+        end += 1;
+    }
+    Some((start, end))
+}
+
+/// Reflows every paragraph in rows `start..=end` of `lines` to `width`
+/// columns and returns the new rows, copying verbatim ones through
+/// unchanged. The whole note is needed to tell frontmatter and code apart.
+pub fn reflow_paragraphs(lines: &[String], start: usize, end: usize, width: usize) -> Vec<String> {
+    let fixed = verbatim_rows(lines);
+    let mut out = Vec::with_capacity(end + 1 - start);
+    let mut i = start;
+    while i <= end {
+        if fixed[i] {
+            out.push(lines[i].clone());
+            i += 1;
+            continue;
+        }
+        let mut last = i;
+        while last < end && !fixed[last + 1] && continues(&lines[last], &lines[last + 1]) {
+            last += 1;
+        }
+        out.extend(reflow(&lines[i..=last], width));
+        i = last + 1;
+    }
+    out
+}
+
+/// Rows reflow leaves alone: frontmatter, fenced code, code indented by
+/// four spaces, and the lines `is_verbatim` names.
+fn verbatim_rows(lines: &[String]) -> Vec<bool> {
+    let mut fixed = vec![false; lines.len()];
+    fixed[..markdown::body_start(lines)].fill(true);
+    for block in markdown::code_blocks(lines) {
+        fixed[block.start..=block.end.min(lines.len() - 1)].fill(true);
+    }
+    for row in 0..lines.len() {
+        // Indented code can't interrupt a paragraph, so it follows a blank
+        // line or more indented code.
+        let indented = |r: usize| indent_width(&lines[r]) >= 4 && !starts_item(&lines[r]);
+        let code = indented(row) && (row == 0 || lines[row - 1].trim().is_empty() || (fixed[row - 1] && indented(row - 1)));
+        fixed[row] |= code || is_verbatim(&lines[row]);
+    }
+    fixed
+}
+
+/// Greedily rewraps one paragraph to `width` columns. The first line keeps
+/// its indent, quote markers and list marker; the rest are indented to
+/// the text column. Words longer than `width` are not broken.
+pub fn reflow(lines: &[String], width: usize) -> Vec<String> {
+    let (first, cont) = wrap_prefixes(&lines[0]);
+    let words = lines.iter().enumerate().flat_map(|(i, line)| {
+        let skip = if i == 0 { first.len() } else { quote_prefix(line).len() };
+        line[skip..].split_whitespace()
+    });
+
+    let mut out = Vec::new();
+    let mut current = first.clone();
+    let mut prefix_len = first.len();
+    for word in words {
+        let has_words = current.len() > prefix_len;
+        if has_words && current.chars().count() + 1 + word.chars().count() > width {
+            out.push(std::mem::replace(&mut current, cont.clone()));
+            prefix_len = cont.len();
+        }
+        if current.len() > prefix_len {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    out.push(current);
+    out
+}
+
+/// Where to break `line` so it fits in `width` columns while typing:
+/// the byte index of the last space within the limit that is past the
+/// line's prefix.
+pub fn wrap_point(line: &str, width: usize) -> Option<usize> {
+    if line.chars().count() <= width {
+        return None;
+    }
+    let limit = line.char_indices().nth(width).map_or(line.len(), |(i, _)| i);
+    let prefix = wrap_prefixes(line).0.len();
+    // A space right at the limit is a fine place to break too.
+    let at = if line[limit..].starts_with(' ') { Some(limit) } else { line[..limit].rfind(' ') };
+    at.filter(|&i| i > prefix && !line[prefix..i].trim().is_empty())
+}
+
+/// Prefix of the first line of a paragraph (indent, `>` markers and list
+/// marker) and the matching prefix for its continuation lines.
+pub fn wrap_prefixes(line: &str) -> (String, String) {
+    let quote = quote_prefix(line);
+    let marker = list_marker(&line[quote.len()..]).unwrap_or(0);
+    let first = line[..quote.len() + marker].to_string();
+    let cont = format!("{}{}", quote, " ".repeat(marker));
+    (first, cont)
+}
+
+fn quote_prefix(line: &str) -> &str {
+    let end = line.find(|c: char| !c.is_whitespace() && c != '>').unwrap_or(line.len());
+    &line[..end]
+}
+
+/// Whether `next` joins the paragraph that `prev` is part of.
+fn continues(prev: &str, next: &str) -> bool {
+    let depth = |l: &str| quote_prefix(l).matches('>').count();
+    !starts_item(next) && depth(prev) == depth(next)
+}
+
+fn starts_item(line: &str) -> bool {
+    list_marker(&line[quote_prefix(line).len()..]).is_some()
+}
+
+fn is_fence(line: &str) -> bool {
+    let t = line.trim_start();
+    t.starts_with("```") || t.starts_with("~~~")
+}
+
+/// Lines that are never joined into a paragraph: blanks, headings, table
+/// rows, fences, and `---`/`***`/`___` rules or `===` setext underlines.
+fn is_verbatim(line: &str) -> bool {
+    let body = &line[quote_prefix(line).len()..];
+    body.is_empty() || is_heading(body) || body.starts_with('|') || is_fence(body) || is_rule(body)
+}
+
+fn is_rule(body: &str) -> bool {
+    let mut marks = body.chars().filter(|c| !c.is_whitespace());
+    let Some(first @ ('-' | '*' | '_' | '=')) = marks.next() else { return false };
+    let mut count = 1;
+    for c in marks {
+        if c != first {
+            return false;
+        }
+        count += 1;
+    }
+    count >= 3
+}
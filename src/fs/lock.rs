@@ -0,0 +1,42 @@
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::Path;
+
+/// Hidden, so the sidebar skips it; `commit_all` excludes it from git.
+pub const LOCK_FILE: &str = ".lazynotes.lock";
+
+/// Held for as long as the app runs. The OS drops the lock when the process
+/// exits, even on a crash, so there are no stale locks to clean up.
+pub struct VaultLock {
+    _file: File,
+}
+
+pub enum LockOutcome {
+    Acquired(VaultLock),
+    /// Another instance has the vault open; `pid` is what it wrote, if readable.
+    Held { pid: Option<u32> },
+}
+
+pub fn lock_vault(dir: &Path) -> Result<LockOutcome> {
+    let path = dir.join(LOCK_FILE);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("Open lock file {}", path.display()))?;
+    match file.try_lock() {
+        Ok(()) => {
+            file.set_len(0)?;
+            write!(file, "{}", std::process::id())?;
+            Ok(LockOutcome::Acquired(VaultLock { _file: file }))
+        }
+        Err(TryLockError::WouldBlock) => {
+            let pid = fs::read_to_string(&path).ok().and_then(|s| s.trim().parse().ok());
+            Ok(LockOutcome::Held { pid })
+        }
+        Err(TryLockError::Error(e)) => Err(e).with_context(|| format!("Lock {}", path.display())),
+    }
+}
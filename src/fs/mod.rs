@@ -1,3 +1,5 @@
+pub mod lock;
 pub mod ops;
 
-pub use ops::{ensure_notes_dir, read_note, write_note, rename_note, build_notes_tree, flatten_tree_for_sidebar, FlatNode};
+pub use lock::{lock_vault, LockOutcome, LOCK_FILE};
+pub use ops::{ensure_notes_dir, notes_dir_status, NotesDirStatus, read_note, is_text_file, write_note, rename_note, build_notes_tree, collect_files, collect_dirs, rescan_subtree, find_dir, flatten_tree_for_sidebar, FlatNode, NoteNode};
@@ -1,3 +1,5 @@
+use crate::config::Symlinks;
+use crate::logging::{self, log, Level};
 use anyhow::{Context, Result};
 use std::collections::HashSet;
 use std::fs;
@@ -10,10 +12,13 @@ pub enum NoteNode {
         name: String,
         path: PathBuf,
         children: Vec<NoteNode>,
+        /// Reached through a symlink.
+        link: bool,
     },
     File {
         title: String,
         path: PathBuf,
+        link: bool,
     },
 }
 
@@ -26,6 +31,52 @@ pub struct FlatNode {
     pub expanded: bool,
     pub last_in_parent: bool,
     pub last_ancestors: Vec<bool>,
+    pub link: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotesDirStatus {
+    Present,
+    /// Missing, but on an available filesystem, so creating it is safe (first run).
+    Creatable,
+    /// Missing and sitting on what looks like an unmounted removable or
+    /// network drive; creating it would shadow the real vault once mounted.
+    Unavailable,
+}
+
+const MOUNT_ROOTS: [&str; 5] = ["/media", "/mnt", "/Volumes", "/run/media", "/net"];
+
+pub fn notes_dir_status(dir: &Path) -> NotesDirStatus {
+    if dir.is_dir() {
+        return NotesDirStatus::Present;
+    }
+    // Walk upwards: reaching a mount root before any real mount point means
+    // the drive that should hold the vault is not mounted. Anything else,
+    // missing parents included, is left to `create_dir_all`.
+    for ancestor in dir.ancestors().skip(1) {
+        if MOUNT_ROOTS.iter().any(|root| ancestor == Path::new(root)) {
+            return NotesDirStatus::Unavailable;
+        }
+        if is_mount_point(ancestor) {
+            break;
+        }
+    }
+    NotesDirStatus::Creatable
+}
+
+#[cfg(unix)]
+fn is_mount_point(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let Some(parent) = path.parent() else { return true };
+    match (fs::metadata(path), fs::metadata(parent)) {
+        (Ok(a), Ok(b)) => a.dev() != b.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_mount_point(path: &Path) -> bool {
+    path.parent().is_none()
 }
 
 pub fn ensure_notes_dir(dir: &Path) -> Result<()> {
@@ -33,58 +84,89 @@ pub fn ensure_notes_dir(dir: &Path) -> Result<()> {
 }
 
 pub fn read_note(path: &Path) -> Result<String> {
-    let mut f = fs::File::open(path).with_context(|| format!("Open {}", path.display()))?;
-    let mut s = String::new();
-    f.read_to_string(&mut s)?;
-    Ok(s)
+    logging::timed("fs", format_args!("read path={:?}", path), || {
+        let mut f = fs::File::open(path).with_context(|| format!("Open {}", path.display()))?;
+        let mut s = String::new();
+        f.read_to_string(&mut s).with_context(|| format!("Read {}", path.display()))?;
+        Ok(s)
+    })
 }
 
-pub fn write_note(path: &Path, content: &str) -> Result<()> {
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
+/// Whether `path` looks like text: its first few KiB have no NUL bytes
+/// and are valid UTF-8.
+pub fn is_text_file(path: &Path) -> bool {
+    let Ok(file) = fs::File::open(path) else { return false };
+    let mut head = Vec::new();
+    if file.take(8192).read_to_end(&mut head).is_err() || head.contains(&0) {
+        return false;
+    }
+    // A character cut off by the 8 KiB limit is fine.
+    match std::str::from_utf8(&head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
     }
-    let mut f = fs::File::create(path).with_context(|| format!("Create {}", path.display()))?;
-    f.write_all(content.as_bytes())?;
-    Ok(())
+}
+
+pub fn write_note(path: &Path, content: &str) -> Result<()> {
+    logging::timed("fs", format_args!("write path={:?} bytes={}", path, content.len()), || {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("Create folder {}", parent.display()))?;
+        }
+        let mut f = fs::File::create(path).with_context(|| format!("Create {}", path.display()))?;
+        f.write_all(content.as_bytes()).with_context(|| format!("Write {}", path.display()))?;
+        Ok(())
+    })
 }
 
 pub fn rename_note(old: &Path, new: &Path) -> Result<()> {
     if old != new {
+        log!(Level::Debug, "fs", "rename from={:?} to={:?}", old, new);
         fs::rename(old, new).with_context(|| format!("Rename {} -> {}", old.display(), new.display()))?;
     }
     Ok(())
 }
 
-pub fn build_notes_tree(dir: &Path) -> Result<NoteNode> {
-    let mut children: Vec<NoteNode> = Vec::new();
+pub fn build_notes_tree(dir: &Path, symlinks: Symlinks) -> Result<NoteNode> {
+    build_dir(dir, symlinks, &mut Vec::new())
+}
 
-    if !dir.exists() {
-        ensure_notes_dir(dir)?;
-    }
+/// `chain` holds the resolved paths of the folders being read, so a
+/// symlink back to one of them is left out instead of recursing forever.
+fn build_dir(dir: &Path, symlinks: Symlinks, chain: &mut Vec<PathBuf>) -> Result<NoteNode> {
+    chain.push(fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf()));
+    let mut children: Vec<NoteNode> = Vec::new();
 
     for entry in fs::read_dir(dir).with_context(|| format!("Reading {}", dir.display()))? {
         let entry = entry?;
         let p = entry.path();
+        let link = entry.file_type().is_ok_and(|t| t.is_symlink());
+        if link && symlinks == Symlinks::Skip {
+            continue;
+        }
 
         if p.is_dir() {
-            if let Some(name) = p.file_name().and_then(|s| s.to_str()) {
-                if name.starts_with('.') {
-                    continue;
-                }
+            if let Some(name) = p.file_name().and_then(|s| s.to_str())
+                && name.starts_with('.')
+            {
+                continue;
             }
-            children.push(build_notes_tree(&p)?);
-        } else if p.is_file() {
-            if let Some(fname) = p.file_name().and_then(|s| s.to_str()) {
-                if fname.starts_with('.') {
-                } else {
-                    children.push(NoteNode::File {
-                        title: fname.to_string(),
-                        path: p.clone(),
-                    });
-                }
+            if link && fs::canonicalize(&p).is_ok_and(|target| chain.contains(&target)) {
+                log!(Level::Warn, "fs", "skipping symlink loop path={:?}", p);
+                continue;
             }
+            children.push(build_dir(&p, symlinks, chain)?);
+        } else if p.is_file()
+            && let Some(fname) = p.file_name().and_then(|s| s.to_str())
+            && !fname.starts_with('.')
+        {
+            children.push(NoteNode::File {
+                title: fname.to_string(),
+                path: p.clone(),
+                link,
+            });
         }
     }
+    chain.pop();
 
     children.sort_by(|a, b| match (a, b) {
         (NoteNode::Dir { name: an, .. }, NoteNode::Dir { name: bn, .. }) => an.to_lowercase().cmp(&bn.to_lowercase()),
@@ -103,9 +185,70 @@ pub fn build_notes_tree(dir: &Path) -> Result<NoteNode> {
         name,
         path: dir.to_path_buf(),
         children,
+        link: fs::symlink_metadata(dir).is_ok_and(|m| m.file_type().is_symlink()),
     })
 }
 
+/// Re-reads `dir` from disk and splices it into the tree, leaving every other
+/// subtree untouched. When `dir` is not in the tree yet (a freshly created
+/// folder), its closest known ancestor is rebuilt instead.
+pub fn rescan_subtree(node: &mut NoteNode, dir: &Path, symlinks: Symlinks) -> Result<()> {
+    let NoteNode::Dir { path, children, .. } = node else {
+        return Ok(());
+    };
+    if path.as_path() != dir {
+        let next = children
+            .iter_mut()
+            .find(|c| matches!(c, NoteNode::Dir { path, .. } if dir.starts_with(path)));
+        if let Some(child) = next {
+            return rescan_subtree(child, dir, symlinks);
+        }
+    }
+    let path = path.clone();
+    *node = logging::timed("fs", format_args!("rescan dir={:?}", path), || build_notes_tree(&path, symlinks))?;
+    Ok(())
+}
+
+/// The folder node at `dir` within `node`, if the tree has it.
+pub fn find_dir<'a>(node: &'a NoteNode, dir: &Path) -> Option<&'a NoteNode> {
+    let NoteNode::Dir { path, children, .. } = node else { return None };
+    if path.as_path() == dir {
+        return Some(node);
+    }
+    children
+        .iter()
+        .find(|c| matches!(c, NoteNode::Dir { path, .. } if dir.starts_with(path)))
+        .and_then(|child| find_dir(child, dir))
+}
+
+/// `node` and every folder under it, if it is a folder.
+pub fn collect_dirs(node: &NoteNode) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    collect_dirs_into(node, &mut out);
+    out
+}
+
+fn collect_dirs_into(node: &NoteNode, out: &mut Vec<PathBuf>) {
+    if let NoteNode::Dir { path, children, .. } = node {
+        out.push(path.clone());
+        children.iter().for_each(|c| collect_dirs_into(c, out));
+    }
+}
+
+/// Every file path under `node`, depth-first in sidebar order.
+pub fn collect_files(node: &NoteNode) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    collect_files_into(node, &mut out);
+    out
+}
+
+fn collect_files_into(node: &NoteNode, out: &mut Vec<PathBuf>) {
+    match node {
+        NoteNode::Dir { children, .. } => children.iter().for_each(|c| collect_files_into(c, out)),
+        NoteNode::File { path, .. } => out.push(path.clone()),
+    }
+}
+
 pub fn flatten_tree_for_sidebar(root: &NoteNode, expanded: &HashSet<PathBuf>) -> Vec<FlatNode> {
     let mut out = Vec::new();
     match root {
@@ -131,7 +274,7 @@ fn flatten_node(
     ancestors_last: &mut Vec<bool>,
 ) {
     match node {
-        NoteNode::Dir { name, path, children } => {
+        NoteNode::Dir { name, path, children, link } => {
             let is_expanded = expanded.contains(path);
             out.push(FlatNode {
                 name: name.clone(),
@@ -141,6 +284,7 @@ fn flatten_node(
                 expanded: is_expanded,
                 last_in_parent,
                 last_ancestors: ancestors_last.clone(),
+                link: *link,
             });
             if is_expanded {
                 ancestors_last.push(last_in_parent);
@@ -151,7 +295,7 @@ fn flatten_node(
                 ancestors_last.pop();
             }
         }
-        NoteNode::File { title, path } => {
+        NoteNode::File { title, path, link } => {
             out.push(FlatNode {
                 name: title.clone(),
                 depth,
@@ -160,6 +304,7 @@ fn flatten_node(
                 expanded: false,
                 last_in_parent,
                 last_ancestors: ancestors_last.clone(),
+                link: *link,
             });
         }
     }
@@ -1,97 +1,573 @@
-use anyhow::Result;
+use crate::graph::{self, GraphRow};
+use crate::logging::{self, log, Level};
+use anyhow::{bail, Context, Result};
+use ratatui::widgets::ListState;
 use std::path::Path;
 
 #[derive(Debug, Clone)]
 pub struct CommitInfo {
     pub hash: String,
+    /// Abbreviated, like `hash`; rewritten to the nearest listed ancestor
+    /// when the log is limited to the notes folder.
+    pub parents: Vec<String>,
     pub summary: String,
     pub author: String,
-    pub date: String,
-    pub changed_files: Vec<String>,
+    /// Author date as a unix timestamp; shown through `App::commit_date`.
+    pub time: i64,
+    pub changed_files: Vec<ChangedFile>,
 }
 
+#[derive(Debug, Clone)]
+pub struct ChangedFile {
+    /// First letter of git's status: `A`dded, `M`odified, `D`eleted,
+    /// `R`enamed, ...
+    pub status: char,
+    pub path: String,
+    /// Path before a rename or copy.
+    pub from: Option<String>,
+}
+
+/// `--pretty` format shared by the log calls, parsed by `parse_log`.
+const LOG_FORMAT: &str = "--pretty=format:%x1e%h%x1f%p%x1f%s%x1f%an%x1f%at";
+
+/// Loads the last `limit` commits with their changed files in a single
+/// `git log --name-status` call. Records are split on ASCII RS/US so summaries
+/// containing `|` survive. When `path` is a folder inside a larger repo,
+/// only commits touching it are listed, with file names relative to it.
 pub fn get_recent_commits(limit: usize, path: Option<&Path>) -> Result<Vec<CommitInfo>> {
     use std::process::Command;
     let mut cmd = Command::new("git");
     if let Some(p) = path {
         cmd.current_dir(p);
     }
-    cmd.arg("log").arg(format!("-n{}", limit)).arg("--pretty=format:%h|%s|%an|%ar");
-    let output = cmd.output()?;
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut commits: Vec<CommitInfo> = stdout
-        .lines()
-        .filter_map(|line| {
-            let mut parts = line.splitn(4, '|');
+    cmd.arg("log")
+        .arg(format!("-n{}", limit))
+        .arg("--name-status")
+        .arg("--relative")
+        .args(["--topo-order", "--parents"])
+        .arg(LOG_FORMAT)
+        .args(["--", "."]);
+    let output = logging::timed("git", format_args!("log limit={}", limit), || cmd.output()).context("Running git log")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // A vault that isn't a repo (yet) or has no commits is not an error.
+        if stderr.contains("not a git repository") || stderr.contains("does not have any commits") {
+            return Ok(Vec::new());
+        }
+        bail!("git log failed: {}", stderr.trim());
+    }
+    Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Commits in `@{upstream}` missing from HEAD. Only looks at what the last
+/// fetch brought in; `None` when the branch tracks nothing.
+fn commits_behind(path: Option<&Path>) -> Option<usize> {
+    let mut cmd = std::process::Command::new("git");
+    if let Some(p) = path {
+        cmd.current_dir(p);
+    }
+    let output = cmd.args(["rev-list", "--count", "HEAD..@{upstream}"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Splits `git log --name-status` output in the RS/US format used above.
+fn parse_log(stdout: &str) -> Vec<CommitInfo> {
+    stdout
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut lines = record.lines();
+            let mut parts = lines.next()?.splitn(5, '\x1f');
             Some(CommitInfo {
                 hash: parts.next()?.to_string(),
+                parents: parts.next()?.split_whitespace().map(String::from).collect(),
                 summary: parts.next()?.to_string(),
                 author: parts.next()?.to_string(),
-                date: parts.next()?.to_string(),
-                changed_files: Vec::new(),
+                time: parts.next()?.parse().ok()?,
+                changed_files: lines.filter_map(parse_status).collect(),
             })
         })
-        .collect();
-
-    for c in &mut commits {
-        let mut show_cmd = Command::new("git");
-        if let Some(p) = path {
-            show_cmd.current_dir(p);
-        }
-        show_cmd.arg("diff-tree").arg("--no-commit-id").arg("--name-only").arg("-r").arg(&c.hash);
-        if let Ok(out) = show_cmd.output() {
-            if out.status.success() {
-                let s = String::from_utf8_lossy(&out.stdout);
-                c.changed_files = s.lines().map(|l| l.to_string()).filter(|l| !l.is_empty()).collect();
-            }
-        }
-    }
+        .collect()
+}
 
-    Ok(commits)
+/// `M\tpath`, or `R100\told\tnew` for renames and copies.
+fn parse_status(line: &str) -> Option<ChangedFile> {
+    let mut fields = line.split('\t');
+    let status = fields.next()?.chars().next()?;
+    let first = fields.next()?.to_string();
+    Some(match fields.next() {
+        Some(to) => ChangedFile { status, path: to.to_string(), from: Some(first) },
+        None => ChangedFile { status, path: first, from: None },
+    })
 }
 
 use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+/// Error of the pre-load commands (if one failed), the loaded commits and
+/// how far the branch is behind its upstream.
+type LoadResult = (Option<String>, Result<Vec<CommitInfo>>, Option<usize>);
+
+/// Saves arriving closer together than this are folded into one refresh.
+const REFRESH_DEBOUNCE: Duration = Duration::from_millis(1500);
 
 pub struct GitSection {
     pub commits: Vec<CommitInfo>,
+    /// Graph cells beside each of `commits`.
+    pub graph: Vec<GraphRow>,
     pub selected: usize,
+    /// Row of the Changed Files pane, within the selected commit.
+    pub file_selected: usize,
+    /// Scroll state of the Commits and Changed Files lists, kept between
+    /// frames so the offset survives; the selection is synced at draw time.
+    pub list_state: ListState,
+    pub files_state: ListState,
     pub path: Option<PathBuf>,
+    /// Top level of the repository holding `path`, which git finds by
+    /// walking upwards; `None` outside any repository.
+    pub repo_root: Option<PathBuf>,
+    /// Identity passed as `-c user.name/user.email` so profiles can commit
+    /// under a different author than the global git config.
+    pub user_name: Option<String>,
+    pub user_email: Option<String>,
+    refresh_due: Option<Instant>,
+    /// Result channel of the background `git log` currently in flight,
+    /// along with the error of the commands run before it, if any failed.
+    pending: Option<Receiver<LoadResult>>,
+    pending_action: Option<&'static str>,
+    /// Outcome of the action (fetch, push, ...) behind the last applied load.
+    pub last_action: Option<ActionOutcome>,
+    /// Commits on the upstream branch, as of the last fetch, that HEAD
+    /// doesn't have; `None` without an upstream.
+    pub behind: Option<usize>,
+    /// Why the last load failed; the previous commit list is kept.
+    pub load_error: Option<anyhow::Error>,
+    /// Commands waiting for the TUI to hand back the terminal; see
+    /// [`ForegroundJob`].
+    pub foreground: Option<ForegroundJob>,
+}
+
+/// A signed commit: gpg's pinentry or ssh-keygen may ask for a passphrase
+/// on the terminal, so these run with the TUI suspended instead of on the
+/// worker thread.
+pub struct ForegroundJob {
+    pub label: &'static str,
+    commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ActionOutcome {
+    pub label: &'static str,
+    pub error: Option<String>,
 }
 
 impl GitSection {
+    /// Starts empty with a refresh already due, so the first `tick` after the
+    /// first frame kicks off loading in the background.
     pub fn new_for(path: Option<PathBuf>) -> Self {
-        let commits = get_recent_commits(30, path.as_deref()).unwrap_or_default();
-        Self { commits, selected: 0, path }
+        let repo_root = discover_repo(path.as_deref());
+        Self {
+            commits: Vec::new(),
+            graph: Vec::new(),
+            selected: 0,
+            file_selected: 0,
+            list_state: ListState::default(),
+            files_state: ListState::default(),
+            path,
+            repo_root,
+            user_name: None,
+            user_email: None,
+            refresh_due: Some(Instant::now()),
+            pending: None,
+            pending_action: None,
+            last_action: None,
+            behind: None,
+            load_error: None,
+            foreground: None,
+        }
+    }
+
+    /// A `git` command scoped to the notes repo with the profile identity applied.
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new("git");
+        if let Some(p) = &self.path {
+            cmd.arg("-C").arg(p);
+        }
+        if let Some(name) = &self.user_name {
+            cmd.arg("-c").arg(format!("user.name={}", name));
+        }
+        if let Some(email) = &self.user_email {
+            cmd.arg("-c").arg(format!("user.email={}", email));
+        }
+        cmd
+    }
+
+    /// Why the commit list is empty.
+    pub fn empty_reason(&self) -> String {
+        match (&self.repo_root, &self.path) {
+            (None, _) => "Notes folder is not in a git repository".into(),
+            (Some(root), Some(path)) if path.canonicalize().is_ok_and(|p| p != *root && p.starts_with(root)) => {
+                format!("No commits touch the notes folder yet (repository at {})", root.display())
+            }
+            _ => "No commits in the notes repository yet".into(),
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.pending.is_some() || self.refresh_due.is_some()
     }
 
     pub fn refresh(&mut self) {
-        self.commits = get_recent_commits(30, self.path.as_deref()).unwrap_or_default();
-        self.selected = 0;
+        self.spawn_load(None, Vec::new());
+    }
+
+    /// Reloads commits on a worker thread after running `pre` (e.g. a fetch)
+    /// in order, stopping at the first failing command. Results are picked
+    /// up by `tick`.
+    fn spawn_load(&mut self, label: Option<&'static str>, pre: Vec<Command>) {
+        self.spawn_load_after(label, pre, None);
+    }
+
+    /// Like `spawn_load`, reporting `error` (from commands already run) when
+    /// `pre` doesn't fail itself.
+    fn spawn_load_after(&mut self, label: Option<&'static str>, pre: Vec<Command>, error: Option<String>) {
+        let (tx, rx) = mpsc::channel();
+        let path = self.path.clone();
+        self.pending_action = label;
+        std::thread::spawn(move || {
+            let mut error = error;
+            for cmd in pre {
+                if let Err(e) = run_step(cmd) {
+                    error = Some(e);
+                    break;
+                }
+            }
+            let commits = get_recent_commits(30, path.as_deref());
+            let _ = tx.send((error, commits, commits_behind(path.as_deref())));
+        });
+        self.pending = Some(rx);
+    }
+
+    /// Schedules a refresh; repeated calls within the debounce window push
+    /// the deadline back so a burst of saves costs a single `git log`.
+    pub fn request_refresh(&mut self) {
+        self.refresh_due = Some(Instant::now() + REFRESH_DEBOUNCE);
+    }
+
+    /// Starts a due refresh and applies finished background loads. Returns
+    /// true when new commit data was applied.
+    pub fn tick(&mut self) -> bool {
+        if let Some(due) = self.refresh_due
+            && Instant::now() >= due
+            && self.pending.is_none()
+        {
+            self.refresh_due = None;
+            self.refresh();
+        }
+        let Some(rx) = &self.pending else { return false };
+        match rx.try_recv() {
+            Ok((error, commits, behind)) => {
+                self.behind = behind;
+                match commits {
+                    Ok(commits) => {
+                        self.graph = graph::layout(&commits);
+                        self.commits = commits;
+                        self.selected = 0;
+                        self.file_selected = 0;
+                    }
+                    Err(e) => self.load_error = Some(e),
+                }
+                self.pending = None;
+                self.last_action = self.pending_action.take().map(|label| ActionOutcome { label, error });
+                true
+            }
+            Err(TryRecvError::Empty) => false,
+            Err(TryRecvError::Disconnected) => {
+                self.pending = None;
+                false
+            }
+        }
     }
 
     pub fn fetch_and_refresh(&mut self) {
-        use std::process::Command;
-        if let Some(p) = &self.path {
-            let _ = Command::new("git").arg("-C").arg(p).arg("fetch").output();
-        } else {
-            let _ = Command::new("git").arg("fetch").output();
+        self.run_and_refresh("Fetch", &[&["fetch"]]);
+    }
+
+    pub fn push(&mut self) {
+        self.run_and_refresh("Push", &[&["push"]]);
+    }
+
+    pub fn pull(&mut self) {
+        self.run_and_refresh("Pull", &[&["pull", "--ff-only"]]);
+    }
+
+    /// Rebases local commits onto the upstream ones, setting uncommitted
+    /// edits aside meanwhile.
+    pub fn pull_rebase(&mut self) {
+        self.run_and_refresh("Pull", &[&["pull", "--rebase", "--autostash"]]);
+    }
+
+    /// Shelves the vault's uncommitted changes, new notes included.
+    pub fn stash(&mut self) {
+        let [lock, history] = excludes();
+        self.run_and_refresh("Stash", &[&["stash", "push", "--include-untracked", "--", ".", &lock, &history]]);
+    }
+
+    /// Brings back the stash entry `reference` (`stash@{n}`); `pop` also
+    /// drops it once applied.
+    pub fn unstash(&mut self, reference: &str, pop: bool) {
+        let action = if pop { "pop" } else { "apply" };
+        self.run_and_refresh("Unstash", &[&["stash", action, reference]]);
+    }
+
+    /// `(stash@{n}, description)` of each stash entry, newest first.
+    pub fn stashes(&self) -> Result<Vec<(String, String)>> {
+        let output = self.command().args(["stash", "list", "--format=%gd%x1f%gs"]).output().context("Running git stash")?;
+        if !output.status.success() {
+            bail!("git stash list failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|l| l.split_once('\x1f'))
+            .map(|(r, d)| (r.to_string(), d.to_string()))
+            .collect())
+    }
+
+    /// Stages everything in the vault and commits it with `message`.
+    pub fn commit_all(&mut self, message: &str) {
+        let [lock, history] = excludes();
+        self.run_commit("Commit", &[&["add", "-A", "--", ".", &lock, &history], &["commit", "-m", message]]);
+    }
+
+    /// Commits just `paths` (absolute or repo-relative), leaving anything
+    /// else that is staged or modified alone.
+    pub fn commit_paths(&mut self, message: &str, paths: &[PathBuf]) {
+        let paths: Vec<String> = paths.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+        let mut add = vec!["add", "--"];
+        add.extend(paths.iter().map(String::as_str));
+        let mut commit = vec!["commit", "-m", message, "--"];
+        commit.extend(paths.iter().map(String::as_str));
+        self.run_commit("Commit", &[&add, &commit]);
+    }
+
+    /// Rewrites the last commit with `message`, folding in whatever is staged.
+    pub fn amend(&mut self, message: &str) {
+        self.run_commit("Amend", &[&["commit", "--amend", "-m", message]]);
+    }
+
+    /// Revisions of `file`, newest first, following renames. Each entry's
+    /// only changed file is the note's repo-relative path at that commit.
+    pub fn file_history(&self, file: &Path) -> Result<Vec<CommitInfo>> {
+        let output = self
+            .command()
+            .args(["log", "--follow", "--name-status", LOG_FORMAT, "--"])
+            .arg(file)
+            .output()
+            .context("Running git log")?;
+        if !output.status.success() {
+            bail!("git log failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Content of the repo-relative `path` at `hash`.
+    pub fn show_file(&self, hash: &str, path: &str) -> Result<String> {
+        let output = self
+            .command()
+            .arg("show")
+            .arg(format!("{}:{}", hash, path))
+            .output()
+            .context("Running git show")?;
+        if !output.status.success() {
+            bail!("git show {}:{} failed: {}", hash, path, String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// `(name, fetch URL)` of each configured remote.
+    pub fn remotes(&self) -> Result<Vec<(String, String)>> {
+        let output = self.command().args(["remote", "-v"]).output().context("Running git remote")?;
+        if !output.status.success() {
+            bail!("git remote failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|l| l.ends_with("(fetch)"))
+            .filter_map(|l| {
+                let mut parts = l.split_whitespace();
+                Some((parts.next()?.to_string(), parts.next()?.to_string()))
+            })
+            .collect())
+    }
+
+    /// Points `name` at `url`, adding the remote if it doesn't exist.
+    pub fn set_remote(&self, name: &str, url: &str) -> Result<()> {
+        let exists = self.remotes()?.iter().any(|(n, _)| n == name);
+        let action = if exists { "set-url" } else { "add" };
+        let output = self.command().args(["remote", action, name, url]).output().context("Running git remote")?;
+        if !output.status.success() {
+            bail!("git remote {} failed: {}", action, String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(())
+    }
+
+    /// Commits everything uncommitted in the vault with `message`, on the
+    /// calling thread. Returns whether there was anything to commit.
+    pub fn commit_session(&self, message: &str) -> Result<bool> {
+        if self.repo_root.is_none() {
+            return Ok(false);
+        }
+        let [lock, history] = excludes();
+        let status = self.command().args(["status", "--porcelain", "--", ".", &lock, &history]).output().context("Running git status")?;
+        if !status.status.success() || status.stdout.is_empty() {
+            return Ok(false);
+        }
+        for args in [&["add", "-A", "--", ".", &lock, &history][..], &["commit", "-m", message]] {
+            let mut cmd = self.command();
+            cmd.args(args).stdin(Stdio::inherit());
+            run_step(cmd).map_err(|e| anyhow::anyhow!("git {} failed: {}", args[0], e))?;
+        }
+        Ok(true)
+    }
+
+    /// Full message of HEAD, for editing before an amend.
+    pub fn head_message(&self) -> Result<String> {
+        let output = self.command().args(["log", "-1", "--format=%B"]).output().context("Running git log")?;
+        if !output.status.success() {
+            bail!("No commit to amend: {}", String::from_utf8_lossy(&output.stderr).trim());
         }
-        self.refresh();
+        Ok(String::from_utf8_lossy(&output.stdout).trim_end().to_string())
     }
 
-    pub fn selected_changed_files(&self) -> Vec<String> {
+    /// Whether the repo config asks for signed commits (`commit.gpgsign`,
+    /// which also covers `gpg.format = ssh`).
+    fn signs_commits(&self) -> bool {
+        self.command()
+            .args(["config", "--get", "--type=bool", "commit.gpgsign"])
+            .output()
+            .is_ok_and(|out| String::from_utf8_lossy(&out.stdout).trim() == "true")
+    }
+
+    /// Commits in the background, or queues a [`ForegroundJob`] when the
+    /// commit will be signed.
+    fn run_commit(&mut self, label: &'static str, commands: &[&[&str]]) {
+        if !self.signs_commits() {
+            self.run_and_refresh(label, commands);
+            return;
+        }
+        // pinentry-curses finds the terminal through GPG_TTY.
+        let tty = std::env::var_os("GPG_TTY").or_else(|| {
+            let out = Command::new("tty").stdin(Stdio::inherit()).output().ok()?;
+            out.status.success().then(|| String::from_utf8_lossy(&out.stdout).trim().into())
+        });
+        let commands = commands
+            .iter()
+            .map(|args| {
+                let mut cmd = self.command();
+                cmd.args(*args).stdin(Stdio::inherit());
+                if let Some(tty) = &tty {
+                    cmd.env("GPG_TTY", tty);
+                }
+                cmd
+            })
+            .collect();
+        self.foreground = Some(ForegroundJob { label, commands });
+    }
+
+    /// Runs a queued job on the calling thread (the terminal must be out of
+    /// raw mode) and then refreshes the commit list as usual.
+    pub fn run_foreground(&mut self, job: ForegroundJob) {
+        let mut error = None;
+        for cmd in job.commands {
+            if let Err(e) = run_step(cmd) {
+                error = Some(e);
+                break;
+            }
+        }
+        self.spawn_load_after(Some(job.label), Vec::new(), error);
+    }
+
+    /// Runs each argument list as a git command in the background, then
+    /// reloads the commit list.
+    fn run_and_refresh(&mut self, label: &'static str, commands: &[&[&str]]) {
+        let pre = commands
+            .iter()
+            .map(|args| {
+                let mut cmd = self.command();
+                cmd.args(*args);
+                cmd
+            })
+            .collect();
+        self.spawn_load(Some(label), pre);
+    }
+
+    pub fn selected_changed_files(&self) -> Vec<ChangedFile> {
         if self.commits.is_empty() { return Vec::new(); }
         self.commits.get(self.selected).map(|c| c.changed_files.clone()).unwrap_or_default()
     }
 
     pub fn select_next(&mut self) {
         if !self.commits.is_empty() {
-            self.selected = (self.selected + 1).min(self.commits.len() - 1);
+            self.select((self.selected + 1).min(self.commits.len() - 1));
         }
     }
     pub fn select_prev(&mut self) {
         if !self.commits.is_empty() {
-            self.selected = self.selected.saturating_sub(1);
+            self.select(self.selected.saturating_sub(1));
+        }
+    }
+
+    /// Selects commit `index`, starting its file list from the top.
+    pub fn select(&mut self, index: usize) {
+        if index != self.selected {
+            self.selected = index;
+            self.file_selected = 0;
+        }
+    }
+}
+
+/// Runs one git command, returning the first line of its error output on
+/// failure.
+fn run_step(mut cmd: Command) -> std::result::Result<(), String> {
+    let args: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy().into_owned()).collect();
+    let output = logging::timed("git", format_args!("run args={:?}", args), || cmd.output());
+    if let Ok(out) = &output {
+        log!(Level::Debug, "git", "exit status={}", out.status);
+    }
+    match output {
+        Ok(out) if out.status.success() => Ok(()),
+        Ok(out) => {
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            let msg = if stderr.trim().is_empty() { stdout } else { stderr };
+            Err(msg.lines().next().unwrap_or("git failed").trim().to_string())
         }
+        Err(e) => Err(e.to_string()),
     }
 }
+
+/// `git rev-parse --show-toplevel` from `path`, canonicalized like git
+/// reports it.
+fn discover_repo(path: Option<&Path>) -> Option<PathBuf> {
+    let mut cmd = Command::new("git");
+    if let Some(p) = path {
+        cmd.current_dir(p);
+    }
+    let out = cmd.args(["rev-parse", "--show-toplevel"]).output().ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let root = PathBuf::from(String::from_utf8_lossy(&out.stdout).trim());
+    log!(Level::Debug, "git", "repo root={:?}", root);
+    Some(root)
+}
+
+/// Pathspecs that keep the vault lock and the local history snapshots out
+/// of what gets staged or stashed.
+fn excludes() -> [String; 2] {
+    [format!(":!{}", crate::fs::LOCK_FILE), format!(":!{}", crate::snapshots::DIR)]
+}
@@ -0,0 +1,78 @@
+//! Lane layout for the commit graph drawn beside the Commits pane, in the
+//! spirit of `git log --graph`: one column per line of history, `*` for
+//! the commit, `/` and `\` where lines join or fork.
+
+use crate::git::CommitInfo;
+
+/// Graph cells of one commit, one char per lane: `commit` sits on the
+/// summary line, `link` on the line below it, leading to the next commit.
+#[derive(Debug, Clone, Default)]
+pub struct GraphRow {
+    pub commit: Vec<char>,
+    pub link: Vec<char>,
+}
+
+/// Lays out `commits`, newest first. Parents outside the list keep their
+/// lane open to the bottom.
+pub fn layout(commits: &[CommitInfo]) -> Vec<GraphRow> {
+    // The commit each lane is waiting for.
+    let mut lanes: Vec<Option<&str>> = Vec::new();
+    let mut rows = Vec::with_capacity(commits.len());
+    for commit in commits {
+        let hash = commit.hash.as_str();
+        let col = match lanes.iter().position(|l| *l == Some(hash)) {
+            Some(col) => col,
+            None => free_lane(&mut lanes),
+        };
+
+        let mut row = GraphRow::default();
+        for (i, lane) in lanes.iter_mut().enumerate() {
+            row.commit.push(match *lane {
+                _ if i == col => '*',
+                // Another child's line ends here.
+                Some(h) if h == hash => {
+                    *lane = None;
+                    if i > col { '/' } else { '\\' }
+                }
+                Some(_) => '|',
+                None => ' ',
+            });
+        }
+
+        let mut parents = commit.parents.iter().map(String::as_str);
+        lanes[col] = parents.next();
+        let mut forks = Vec::new();
+        for parent in parents {
+            if !lanes.contains(&Some(parent)) {
+                let lane = free_lane(&mut lanes);
+                lanes[lane] = Some(parent);
+                forks.push(lane);
+            }
+        }
+        while lanes.last() == Some(&None) {
+            lanes.pop();
+        }
+
+        row.link = lanes
+            .iter()
+            .enumerate()
+            .map(|(i, lane)| match lane {
+                _ if forks.contains(&i) => if i > col { '\\' } else { '/' },
+                Some(_) => '|',
+                None => ' ',
+            })
+            .collect();
+        rows.push(row);
+    }
+    rows
+}
+
+fn free_lane(lanes: &mut Vec<Option<&str>>) -> usize {
+    match lanes.iter().position(Option::is_none) {
+        Some(i) => i,
+        None => {
+            lanes.push(None);
+            lanes.len() - 1
+        }
+    }
+}
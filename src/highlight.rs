@@ -0,0 +1,46 @@
+//! Syntax highlighting for fenced code blocks in the read-only views of a
+//! note, using syntect's bundled syntaxes and themes.
+
+use crate::markdown::code_blocks;
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+
+/// Loaded on first use; parsing the bundled syntaxes takes a moment.
+fn assets() -> &'static (SyntaxSet, ThemeSet) {
+    static ASSETS: OnceLock<(SyntaxSet, ThemeSet)> = OnceLock::new();
+    ASSETS.get_or_init(|| (SyntaxSet::load_defaults_newlines(), ThemeSet::load_defaults()))
+}
+
+/// Highlighted lines of the fenced blocks in `rows` whose language tag
+/// syntect knows, by row; `None` for the fences, other rows and blocks in
+/// unknown languages.
+pub fn code_blocks_highlighted(rows: &[String], light: bool) -> Vec<Option<Line<'static>>> {
+    let mut out = vec![None; rows.len()];
+    let blocks = code_blocks(rows);
+    if blocks.is_empty() {
+        return out;
+    }
+    let (syntaxes, themes) = assets();
+    let theme = &themes.themes[if light { "InspiredGitHub" } else { "base16-ocean.dark" }];
+    for block in blocks {
+        let Some(syntax) = (!block.lang.is_empty()).then(|| syntaxes.find_syntax_by_token(&block.lang)).flatten() else { continue };
+        let mut hl = HighlightLines::new(syntax, theme);
+        for row in block.body() {
+            let line = format!("{}\n", rows[row]);
+            let Ok(ranges) = hl.highlight_line(&line, syntaxes) else { break };
+            let spans: Vec<Span<'static>> = ranges
+                .into_iter()
+                .map(|(style, text)| {
+                    let fg = style.foreground;
+                    Span::styled(text.trim_end_matches('\n').to_string(), Style::default().fg(Color::Rgb(fg.r, fg.g, fg.b)))
+                })
+                .collect();
+            out[row] = Some(Line::from(spans));
+        }
+    }
+    out
+}
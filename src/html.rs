@@ -0,0 +1,18 @@
+//! Markdown to HTML, for pasting notes into mail and wikis.
+
+use pulldown_cmark::{html, Options, Parser};
+
+/// HTML for `markdown` with the GitHub extensions notes use: tables, task
+/// lists, strikethrough, footnotes and `[[wikilinks]]`. Frontmatter is
+/// left out.
+pub fn to_html(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_WIKILINKS
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+    let mut out = String::new();
+    html::push_html(&mut out, Parser::new_ext(markdown, options));
+    out
+}
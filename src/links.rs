@@ -0,0 +1,474 @@
+//! Wikilinks (`[[target]]`, `[[target|label]]`, `[[target#heading]]`) and
+//! the frontmatter `aliases:` that let several names resolve to one note.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::fs::{read_note, write_note};
+use crate::markdown;
+use crate::search::relative_to;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wikilink {
+    /// Byte range of the whole `[[...]]`.
+    pub start: usize,
+    pub end: usize,
+    /// The note part, without `#heading` or `|label`.
+    pub target: String,
+    /// The `#heading` part, without the `#`.
+    pub heading: Option<String>,
+}
+
+/// Where to put the cursor in a note being opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Anchor {
+    /// 1-based, as in `note.md:42`.
+    Line(usize),
+    Heading(String),
+}
+
+pub fn wikilinks(line: &str) -> Vec<Wikilink> {
+    let mut links = Vec::new();
+    let mut from = 0;
+    while let Some(open) = line[from..].find("[[").map(|i| from + i) {
+        let Some(close) = line[open + 2..].find("]]").map(|i| open + 2 + i) else { break };
+        let inner = &line[open + 2..close];
+        let target = inner.split(['|', '#']).next().unwrap_or_default().trim();
+        if !target.is_empty() && !inner.contains("[[") {
+            let heading = inner.split('|').next().and_then(|t| t.split_once('#')).map(|(_, h)| h.trim().to_string()).filter(|h| !h.is_empty());
+            links.push(Wikilink { start: open, end: close + 2, target: target.to_string(), heading });
+        }
+        from = close + 2;
+    }
+    links
+}
+
+/// Links to a heading of the same note, `[[#Heading]]` or `[#Heading]`,
+/// as `(start, end, heading)` with the byte range of the whole link.
+pub fn heading_links(line: &str) -> Vec<(usize, usize, String)> {
+    let mut out = Vec::new();
+    let mut from = 0;
+    while let Some(open) = line[from..].find('[').map(|i| from + i) {
+        let double = line[open + 1..].starts_with("[#");
+        let start = open + if double { 3 } else { 2 };
+        if !double && !line[open + 1..].starts_with('#') {
+            from = open + 1;
+            continue;
+        }
+        let close = if double { "]]" } else { "]" };
+        let Some(end) = line[start..].find(close).map(|i| start + i) else { break };
+        let heading = line[start..end].split('|').next().unwrap_or_default().trim();
+        if !heading.is_empty() && !heading.contains('[') {
+            out.push((open, end + close.len(), heading.to_string()));
+        }
+        from = end + close.len();
+    }
+    out
+}
+
+/// `aliases:` (or `alias:`) from a leading `---` frontmatter block, either
+/// inline (`aliases: [a, b]`, `alias: a`) or as a `- item` list.
+pub fn aliases(content: &str) -> Vec<String> {
+    let mut lines = content.lines();
+    if lines.next().map(str::trim_end) != Some("---") {
+        return Vec::new();
+    }
+    let mut out = Vec::new();
+    let mut in_list = false;
+    for line in lines {
+        let trimmed = line.trim();
+        if trimmed == "---" || trimmed == "..." {
+            break;
+        }
+        if in_list {
+            if let Some(item) = trimmed.strip_prefix("- ") {
+                push_alias(&mut out, item);
+                continue;
+            }
+            in_list = false;
+        }
+        let Some((key, value)) = line.split_once(':') else { continue };
+        if !matches!(key.trim(), "aliases" | "alias") || line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        let value = value.trim();
+        if value.is_empty() {
+            in_list = true;
+        } else if let Some(inner) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            inner.split(',').for_each(|a| push_alias(&mut out, a));
+        } else {
+            push_alias(&mut out, value);
+        }
+    }
+    out
+}
+
+fn push_alias(out: &mut Vec<String>, raw: &str) {
+    let alias = raw.trim().trim_matches(['"', '\'']).trim();
+    if !alias.is_empty() {
+        out.push(alias.to_string());
+    }
+}
+
+/// Name → note lookup over the vault. File names win over aliases, and
+/// among equal names the shortest path wins so results are stable.
+#[derive(Debug, Default)]
+pub struct LinkIndex {
+    root: PathBuf,
+    notes: Vec<PathBuf>,
+    stems: HashMap<String, PathBuf>,
+    aliases: HashMap<String, PathBuf>,
+    by_path: HashMap<PathBuf, Vec<String>>,
+}
+
+impl LinkIndex {
+    pub fn build(files: &[PathBuf], root: &Path) -> Self {
+        let mut index = LinkIndex { root: root.to_path_buf(), ..Default::default() };
+        let mut notes: Vec<&PathBuf> = files.iter().filter(|p| is_note(p)).collect();
+        notes.sort_by_key(|p| (p.as_os_str().len(), p.as_path()));
+        for path in notes {
+            index.notes.push(path.clone());
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                index.stems.entry(stem.to_lowercase()).or_insert_with(|| path.clone());
+            }
+            let Ok(content) = read_note(path) else { continue };
+            let names = aliases(&content);
+            for alias in &names {
+                index.aliases.entry(alias.to_lowercase()).or_insert_with(|| path.clone());
+            }
+            if !names.is_empty() {
+                index.by_path.insert(path.clone(), names);
+            }
+        }
+        index
+    }
+
+    /// Resolves a link target: a vault-relative path (`dir/note`), a file
+    /// name, or an alias, compared case-insensitively with or without `.md`.
+    pub fn resolve(&self, target: &str) -> Option<&PathBuf> {
+        let target = target.trim();
+        let name = target.strip_suffix(".md").unwrap_or(target).to_lowercase();
+        if name.contains('/') {
+            let wanted = format!("{}.md", name.trim_start_matches('/'));
+            return self.notes.iter().find(|p| relative_to(p, &self.root).to_string_lossy().to_lowercase() == wanted);
+        }
+        self.stems.get(&name).or_else(|| self.aliases.get(&name))
+    }
+
+    pub fn aliases_of(&self, path: &Path) -> &[String] {
+        self.by_path.get(path).map_or(&[], Vec::as_slice)
+    }
+}
+
+pub fn is_note(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "md")
+}
+
+/// Points links at `old` to `new` after a rename or a move: wikilinks
+/// that `index` resolves to `old` (by name or path, not alias) and
+/// relative Markdown links (`[text](old.md)`) from `file`. Headings and
+/// labels are kept, and so are folder parts unless the note changed
+/// folders; fenced code is skipped. Returns the number of links rewritten.
+pub fn rewrite_links(lines: &mut [String], file: &Path, old: &Path, new: &Path, index: &LinkIndex) -> usize {
+    let (Some(old_stem), Some(new_stem)) = (old.file_stem().and_then(|s| s.to_str()), new.file_stem().and_then(|s| s.to_str()))
+    else {
+        return 0;
+    };
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let moved = old.parent() != new.parent();
+    let mut count = 0;
+    let fenced = markdown::fenced_rows(lines);
+    for (line, _) in lines.iter_mut().zip(fenced).filter(|(_, fenced)| !fenced) {
+        let mut edits: Vec<(usize, usize, String)> = Vec::new();
+        for link in wikilinks(line) {
+            let by_name = link.target.rsplit('/').next().is_some_and(|n| n.trim_end_matches(".md").eq_ignore_ascii_case(old_stem));
+            if by_name && index.resolve(&link.target).is_some_and(|p| p == old) {
+                let at = link.start + 2 + line[link.start + 2..].find(&link.target).unwrap_or(0);
+                if moved && link.target.contains('/') {
+                    let lead = if link.target.starts_with('/') { "/" } else { "" };
+                    let path = relative_to(new, &index.root).with_extension("");
+                    edits.push((at, at + link.target.trim_end_matches(".md").len(), format!("{}{}", lead, path.display())));
+                    continue;
+                }
+                let name_at = at + link.target.rfind('/').map_or(0, |i| i + 1);
+                let name_end = name_at + link.target[name_at - at..].trim_end_matches(".md").len();
+                edits.push((name_at, name_end, new_stem.to_string()));
+            }
+        }
+        for (start, end) in markdown_targets(line) {
+            let end = path_end(line, start, end);
+            let target = &line[start..end];
+            if markdown_link_path(dir, target, &index.root).is_none_or(|p| p != normalize(old)) {
+                continue;
+            }
+            if moved {
+                let path = if target.starts_with('/') { Path::new("/").join(relative_to(new, &index.root)) } else { relative_path(dir, new) };
+                edits.push((start, end, encode_link(target, &path.to_string_lossy())));
+                continue;
+            }
+            let name_at = start + target.rfind('/').map_or(0, |i| i + 1);
+            edits.push((name_at, end, encode_link(target, &format!("{}.md", new_stem))));
+        }
+        edits.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+        for (start, end, with) in edits {
+            // A move leaves bare `[[name]]` links as they were.
+            if line[start..end] != with {
+                line.replace_range(start..end, &with);
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// Keeps the relative Markdown links of a note that moved from the folder
+/// `old_dir` to `new_dir` pointing at the same notes. Returns the number
+/// of links rewritten.
+pub fn rebase_links(lines: &mut [String], old_dir: &Path, new_dir: &Path, root: &Path) -> usize {
+    let mut count = 0;
+    let fenced = markdown::fenced_rows(lines);
+    for (line, _) in lines.iter_mut().zip(fenced).filter(|(_, fenced)| !fenced) {
+        let mut edits = Vec::new();
+        for (start, end) in markdown_targets(line) {
+            let end = path_end(line, start, end);
+            let target = &line[start..end];
+            if target.starts_with('/') {
+                continue;
+            }
+            if let Some(to) = markdown_link_path(old_dir, target, root) {
+                edits.push((start, end, encode_link(target, &relative_path(new_dir, &to).to_string_lossy())));
+            }
+        }
+        for (start, end, with) in edits.into_iter().rev() {
+            if line[start..end] != with {
+                line.replace_range(start..end, &with);
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// `path` written as a Markdown link target, with spaces as `%20` when
+/// `target` (the link it replaces) used them or they'd end the link.
+fn encode_link(target: &str, path: &str) -> String {
+    if target.contains("%20") || path.contains(' ') { path.replace(' ', "%20") } else { path.to_string() }
+}
+
+/// `to` relative to the folder `from`, climbing out with `..` as needed.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let (from, to) = (normalize(from), normalize(to));
+    let common = from.components().zip(to.components()).take_while(|(a, b)| a == b).count();
+    let mut out: PathBuf = from.components().skip(common).map(|_| Component::ParentDir).collect();
+    out.extend(to.components().skip(common));
+    out
+}
+
+/// Runs [`rewrite_links`] over every note in `files` on disk, skipping
+/// `except` (the renamed note, which the caller updates in memory).
+/// Returns `(links, notes)` changed.
+pub fn rewrite_vault_links(files: &[PathBuf], except: &Path, old: &Path, new: &Path, index: &LinkIndex) -> Result<(usize, usize)> {
+    let (mut links, mut notes) = (0, 0);
+    for path in files.iter().filter(|p| is_note(p) && p.as_path() != except && p.as_path() != old) {
+        let content = read_note(path)?;
+        let mut lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+        let n = rewrite_links(&mut lines, path, old, new, index);
+        if n > 0 {
+            write_note(path, &lines.join("\n"))?;
+            links += n;
+            notes += 1;
+        }
+    }
+    Ok((links, notes))
+}
+
+/// Byte ranges of the targets in `[text](target)` links.
+pub fn markdown_targets(line: &str) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut from = 0;
+    while let Some(open) = line[from..].find("](").map(|i| from + i + 2) {
+        let Some(close) = line[open..].find(')').map(|i| open + i) else { break };
+        let end = line[open..close].find(' ').map_or(close, |i| open + i);
+        if end > open {
+            out.push((open, end));
+        }
+        from = close;
+    }
+    out
+}
+
+/// Where the link target at `start..end` of `line` ends without its
+/// `#heading` fragment.
+fn path_end(line: &str, start: usize, end: usize) -> usize {
+    line[start..end].find('#').map_or(end, |i| start + i)
+}
+
+/// Where a relative `[text](target)` link to a `.md` file points; `None`
+/// for URLs and other files. A `#heading` fragment is ignored.
+fn markdown_link_path(dir: &Path, target: &str, root: &Path) -> Option<PathBuf> {
+    let decoded = target[..path_end(target, 0, target.len())].replace("%20", " ");
+    if !decoded.ends_with(".md") || decoded.contains("://") {
+        return None;
+    }
+    let resolved = match decoded.strip_prefix('/') {
+        Some(abs) => root.join(abs),
+        None => dir.join(&decoded),
+    };
+    Some(normalize(&resolved))
+}
+
+#[derive(Debug, Clone)]
+pub enum LinkIssue {
+    /// A link at `row`/`col` (bytes) whose target is not a note in the vault.
+    Broken { path: PathBuf, row: usize, col: usize, target: String },
+    /// A note that no other note links to.
+    Orphan(PathBuf),
+}
+
+impl LinkIssue {
+    pub fn path(&self) -> &Path {
+        match self {
+            LinkIssue::Broken { path, .. } | LinkIssue::Orphan(path) => path,
+        }
+    }
+}
+
+/// Broken links across the vault, then orphaned notes, each sorted by path.
+pub fn diagnose(files: &[PathBuf], index: &LinkIndex) -> Vec<LinkIssue> {
+    let mut broken = Vec::new();
+    let mut linked: HashSet<PathBuf> = HashSet::new();
+    for path in files.iter().filter(|p| is_note(p)) {
+        let Ok(content) = read_note(path) else { continue };
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let lines: Vec<String> = content.lines().map(str::to_string).collect();
+        let fenced = markdown::fenced_rows(&lines);
+        for (row, line) in lines.iter().enumerate().filter(|(row, _)| !fenced[*row]) {
+            let wiki = wikilinks(line).into_iter().map(|l| (l.start, l.target.clone(), index.resolve(&l.target).cloned()));
+            let markdown = markdown_targets(line).into_iter().filter_map(|(start, end)| {
+                let target = markdown_link_path(dir, &line[start..end], &index.root)?;
+                let found = index.notes.iter().find(|n| normalize(n) == target).cloned();
+                Some((start, line[start..end].to_string(), found))
+            });
+            for (col, target, found) in wiki.chain(markdown) {
+                match found {
+                    Some(to) if to != *path => {
+                        linked.insert(to);
+                    }
+                    Some(_) => {}
+                    None => broken.push(LinkIssue::Broken { path: path.clone(), row, col, target }),
+                }
+            }
+        }
+    }
+    broken.sort_by(|a, b| a.path().cmp(b.path()));
+    let mut orphans: Vec<PathBuf> = index.notes.iter().filter(|n| !linked.contains(*n)).cloned().collect();
+    orphans.sort();
+    broken.extend(orphans.into_iter().map(LinkIssue::Orphan));
+    broken
+}
+
+/// Drops `.` and folds `..` without touching the filesystem.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    fn vault(name: &str, notes: &[(&str, &str)]) -> (PathBuf, Vec<PathBuf>) {
+        let root = std::env::temp_dir().join(format!("lazynotes-test-links-{}-{}", name, std::process::id()));
+        let mut files = Vec::new();
+        for (path, content) in notes {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, content).unwrap();
+            files.push(path);
+        }
+        (root, files)
+    }
+
+    #[test]
+    fn rewrite_links_follows_a_rename() {
+        let root = PathBuf::from("/vault");
+        let (old, new) = (root.join("notes/my note.md"), root.join("notes/your note.md"));
+        let file = root.join("other/ref.md");
+        let index = LinkIndex::build(&[old.clone(), file.clone()], &root);
+        let mut text = lines(concat!(
+            "[[my note]], [[my note|the plan]] and [[notes/my note#Plan]]\n",
+            "[a](../notes/my%20note.md) [b](../notes/my%20note.md#plan) [c](https://x.y/my%20note.md)\n",
+            "~~~~\n",
+            "```\n",
+            "[[my note]] [a](../notes/my%20note.md)\n",
+            "~~~~\n",
+        ));
+        assert_eq!(rewrite_links(&mut text, &file, &old, &new, &index), 5);
+        assert_eq!(text[0], "[[your note]], [[your note|the plan]] and [[notes/your note#Plan]]");
+        assert_eq!(text[1], "[a](../notes/your%20note.md) [b](../notes/your%20note.md#plan) [c](https://x.y/my%20note.md)");
+        assert_eq!(text[4], "[[my note]] [a](../notes/my%20note.md)");
+    }
+
+    #[test]
+    fn rewrite_links_follows_a_move() {
+        let root = PathBuf::from("/vault");
+        let (old, new) = (root.join("notes/plan.md"), root.join("archive/2024/plan.md"));
+        let file = root.join("notes/ref.md");
+        let index = LinkIndex::build(&[old.clone(), file.clone()], &root);
+        let mut text = lines("[[plan]] [[notes/plan|p]] [[/notes/plan#Goals]] [p](plan.md#goals) [q](/notes/plan.md)");
+        assert_eq!(rewrite_links(&mut text, &file, &old, &new, &index), 4);
+        assert_eq!(text[0], "[[plan]] [[archive/2024/plan|p]] [[/archive/2024/plan#Goals]] [p](../archive/2024/plan.md#goals) [q](/archive/2024/plan.md)");
+
+        let mut own = lines("[r](ref.md) [up](../top.md) [web](https://x.y/a.md)");
+        assert_eq!(rebase_links(&mut own, &root.join("notes"), &root.join("archive/2024"), &root), 2);
+        assert_eq!(own[0], "[r](../../notes/ref.md) [up](../../top.md) [web](https://x.y/a.md)");
+    }
+
+    #[test]
+    fn rewrite_vault_links_writes_changed_notes_only() {
+        let (root, mut files) = vault("vault", &[
+            ("old.md", "# Old"),
+            ("a.md", "See [[old]].\n```\n[[old]]\n```\n"),
+            ("sub/b.md", "Up: [o](../old.md)"),
+            ("c.md", "Nothing here"),
+        ]);
+        let index = LinkIndex::build(&files, &root);
+        let new = root.join("new.md");
+        std::fs::rename(&files[0], &new).unwrap();
+        files[0] = new.clone();
+        assert_eq!(rewrite_vault_links(&files, &new, &root.join("old.md"), &new, &index).unwrap(), (2, 2));
+        assert_eq!(std::fs::read_to_string(root.join("a.md")).unwrap(), "See [[new]].\n```\n[[old]]\n```\n");
+        assert_eq!(std::fs::read_to_string(root.join("sub/b.md")).unwrap(), "Up: [o](../new.md)");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn diagnose_reports_broken_links_and_orphans() {
+        let (root, files) = vault("diagnose", &[
+            ("a.md", "[[b]] [x](missing.md) [y](b.md#top)\n```\n[[nowhere]]\n```"),
+            ("b.md", "[[a|back]]"),
+            ("lonely.md", "[[lonely]]"),
+        ]);
+        let index = LinkIndex::build(&files, &root);
+        let issues = diagnose(&files, &index);
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert!(matches!(&issues[0], LinkIssue::Broken { row: 0, col: 10, target, .. } if target == "missing.md"));
+        assert!(matches!(&issues[1], LinkIssue::Orphan(path) if *path == root.join("lonely.md")));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
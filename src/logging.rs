@@ -0,0 +1,136 @@
+//! Opt-in debug log (`--debug` or `RUST_LOG`). Lines are `key=value`
+//! records appended to `lazynotes.log` in the config dir, and the most
+//! recent ones are kept in memory for the in-app viewer.
+
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+/// Lines kept for the log viewer.
+const RECENT_LINES: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    /// Parses a `RUST_LOG`-style value: a bare level or the one given for
+    /// `lazynotes=` among comma-separated directives. `trace` maps to debug.
+    pub fn from_env(value: &str) -> Option<Level> {
+        let directive = value
+            .split(',')
+            .find_map(|d| d.strip_prefix("lazynotes="))
+            .or_else(|| value.split(',').find(|d| !d.contains('=')))?;
+        match directive.trim().to_ascii_lowercase().as_str() {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" | "trace" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        })
+    }
+}
+
+struct Logger {
+    level: Level,
+    path: PathBuf,
+    file: Mutex<File>,
+    recent: Mutex<VecDeque<String>>,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Opens the log file and turns logging on for the rest of the process.
+pub fn init(level: Level) -> Result<()> {
+    let dir = crate::config::config_dir();
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("lazynotes.log");
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Open log file {}", path.display()))?;
+    let _ = LOGGER.set(Logger { level, path, file: Mutex::new(file), recent: Mutex::new(VecDeque::new()) });
+    write(Level::Info, "app", format_args!("start version={}", env!("CARGO_PKG_VERSION")));
+    Ok(())
+}
+
+pub fn enabled(level: Level) -> bool {
+    LOGGER.get().is_some_and(|l| level <= l.level)
+}
+
+pub fn path() -> Option<PathBuf> {
+    LOGGER.get().map(|l| l.path.clone())
+}
+
+/// Use the `log!` macro instead, which skips formatting when disabled.
+pub fn write(level: Level, target: &str, args: fmt::Arguments) {
+    let Some(logger) = LOGGER.get() else { return };
+    if level > logger.level {
+        return;
+    }
+    let now = time::OffsetDateTime::now_local().unwrap_or_else(|_| time::OffsetDateTime::now_utc());
+    let stamp = now
+        .format(&time::macros::format_description!("[hour]:[minute]:[second].[subsecond digits:3]"))
+        .unwrap_or_default();
+    let line = format!("{} {:<5} {} {}", stamp, level, target, args);
+    if let Ok(mut file) = logger.file.lock() {
+        let _ = writeln!(file, "{}", line);
+    }
+    if let Ok(mut recent) = logger.recent.lock() {
+        if recent.len() == RECENT_LINES {
+            recent.pop_front();
+        }
+        recent.push_back(line);
+    }
+}
+
+/// The in-memory tail of the log, oldest first.
+pub fn recent() -> Vec<String> {
+    LOGGER
+        .get()
+        .and_then(|l| l.recent.lock().ok().map(|r| r.iter().cloned().collect()))
+        .unwrap_or_default()
+}
+
+/// Runs `f` and logs how long it took as `<what> ms=<elapsed>`.
+pub fn timed<T>(target: &str, what: fmt::Arguments, f: impl FnOnce() -> T) -> T {
+    if !enabled(Level::Debug) {
+        return f();
+    }
+    let start = Instant::now();
+    let value = f();
+    let ms = start.elapsed().as_secs_f64() * 1000.0;
+    write(Level::Debug, target, format_args!("{} ms={:.2}", what, ms));
+    value
+}
+
+/// `log!(Level::Debug, "git", "cmd={:?}", args)`
+macro_rules! log {
+    ($level:expr, $target:expr, $($arg:tt)*) => {
+        if $crate::logging::enabled($level) {
+            $crate::logging::write($level, $target, format_args!($($arg)*));
+        }
+    };
+}
+pub(crate) use log;
@@ -1,16 +1,136 @@
 mod app;
+mod attachments;
+mod capture;
+mod cat;
+mod case;
+mod bench;
+mod cli;
+mod clipboard;
+mod completions;
 mod config;
+mod crypt;
+mod datefmt;
+mod diagram;
+mod diff;
+mod format;
 mod fs;
+mod graph;
+mod html;
+mod highlight;
+mod links;
+mod logging;
+mod markdown;
+mod menu;
+mod math;
+mod merge;
+mod process;
+mod query;
+mod replace;
+mod runbook;
+mod search;
+mod searches;
+mod session;
+mod signals;
+mod snapshots;
+mod stats;
 mod ui;
 mod git;
+mod theme;
 
 use anyhow::Result;
 use app::App;
+use cli::{Cli, Command, LockedChoice, MissingDirChoice};
 use config::Config;
+use fs::{ensure_notes_dir, lock_vault, notes_dir_status, LockOutcome, NotesDirStatus};
 
 fn main() -> Result<()> {
-    let config = Config::load_or_create()?;
+    let cli = Cli::parse()?;
+    if cli.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    if cli.version {
+        println!("lazynotes {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+    match cli.command {
+        Some(Command::Bench { files }) => return bench::run(files),
+        Some(Command::Completions { shell }) => {
+            print!("{}", completions::script(&shell)?);
+            return Ok(());
+        }
+        Some(Command::Complete { what }) => {
+            for candidate in completions::candidates(&what)? {
+                println!("{}", candidate);
+            }
+            return Ok(());
+        }
+        Some(Command::Capture { .. } | Command::Cat { .. } | Command::Query { .. }) | None => {}
+    }
+
+    let log_level = match std::env::var("RUST_LOG") {
+        _ if cli.debug => Some(logging::Level::Debug),
+        Ok(value) => logging::Level::from_env(&value),
+        Err(_) => None,
+    };
+    if let Some(level) = log_level {
+        logging::init(level)?;
+    }
+
+    let mut config = Config::load_or_create()?;
+    let capturing = matches!(cli.command, Some(Command::Capture { .. } | Command::Cat { .. } | Command::Query { .. }));
+    let profile = match cli.profile.or_else(|| config.default_profile.clone()) {
+        Some(name) => Some(name),
+        // These run from scripts and hotkeys; never stop to ask.
+        None if !config.profiles.is_empty() && !capturing => cli::prompt_profile(&config.profile_names())?,
+        None => None,
+    };
+    if let Some(name) = profile {
+        config = config.with_profile(&name)?;
+    }
+    if let Some(Command::Capture { text }) = cli.command {
+        let path = capture::run(&config, text)?;
+        println!("Captured to {}", path.display());
+        return Ok(());
+    }
+    if let Some(Command::Cat { note, html }) = &cli.command {
+        return cat::run(&config, note, *html);
+    }
+    if let Some(Command::Query { query, text, json }) = &cli.command {
+        return query::run(&config, *query, text.as_deref(), *json);
+    }
+
+    while notes_dir_status(&config.notes_path()) == NotesDirStatus::Unavailable {
+        match cli::prompt_missing_notes_dir(&config.notes_path())? {
+            MissingDirChoice::Retry => {}
+            MissingDirChoice::ChangePath(path) => config.notes_dir = path,
+            MissingDirChoice::Quit => return Ok(()),
+        }
+    }
+
+    ensure_notes_dir(&config.notes_path())?;
+    let mut read_only = false;
+    let _lock = match lock_vault(&config.notes_path())? {
+        LockOutcome::Acquired(lock) => Some(lock),
+        LockOutcome::Held { pid } => match cli::prompt_vault_locked(&config.notes_path(), pid)? {
+            LockedChoice::ReadOnly => {
+                read_only = true;
+                None
+            }
+            LockedChoice::OpenAnyway => None,
+            LockedChoice::Quit => return Ok(()),
+        },
+    };
+
+    let pull = config.pull_on_launch;
     let mut app = App::new(config)?;
+    app.read_only = read_only;
+    // A read-only instance leaves the working tree to the one holding the lock.
+    if !read_only {
+        app.pull_on_launch(pull);
+    }
+    if let Some(note) = &cli.note {
+        app.open_from_cli(note)?;
+    }
     app.run()
 }
-
@@ -0,0 +1,344 @@
+//! Small line-based Markdown helpers; no full parser, just what the panes need.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    pub row: usize,
+    pub level: usize,
+    pub title: String,
+}
+
+/// ATX headings (`#` .. `######`) outside fenced code blocks.
+pub fn headings(lines: &[String]) -> Vec<Heading> {
+    let mut out = Vec::new();
+    let fenced = fenced_rows(lines);
+    for (row, line) in lines.iter().enumerate() {
+        if fenced[row] {
+            continue;
+        }
+        let trimmed = line.trim_start();
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        if !(1..=6).contains(&level) {
+            continue;
+        }
+        let rest = &trimmed[level..];
+        if !rest.is_empty() && !rest.starts_with(' ') {
+            continue;
+        }
+        let title = rest.trim().trim_end_matches('#').trim_end().to_string();
+        out.push(Heading { row, level, title });
+    }
+    out
+}
+
+/// Row of the first heading titled `name`, ignoring case.
+pub fn find_heading(lines: &[String], name: &str) -> Option<usize> {
+    let name = name.trim().to_lowercase();
+    headings(lines).into_iter().find(|h| h.title.to_lowercase() == name).map(|h| h.row)
+}
+
+/// Index of the heading the given row falls under, if any.
+pub fn current_heading(headings: &[Heading], row: usize) -> Option<usize> {
+    headings.iter().rposition(|h| h.row <= row)
+}
+
+/// Exclusive end row of the section started by `headings[idx]`: the next
+/// heading of the same or a higher level, or the end of the buffer.
+pub fn section_end(headings: &[Heading], idx: usize, total_rows: usize) -> usize {
+    let level = headings[idx].level;
+    headings[idx + 1..]
+        .iter()
+        .find(|h| h.level <= level)
+        .map_or(total_rows, |h| h.row)
+}
+
+/// Whether `row` is hidden by the fold `(heading_row, end)`; the heading
+/// itself stays visible as the placeholder.
+pub fn in_fold((start, end): (usize, usize), row: usize) -> bool {
+    row > start && row < end
+}
+
+/// First row after the leading `---` frontmatter, or 0 without one.
+pub fn body_start(lines: &[String]) -> usize {
+    if lines.first().is_some_and(|l| l.trim_end() == "---")
+        && let Some(end) = lines.iter().skip(1).position(|l| matches!(l.trim_end(), "---" | "..."))
+    {
+        return end + 2;
+    }
+    0
+}
+
+/// Slides of a note for presentation mode, as `(start, end)` row ranges:
+/// split at `---` rules when the note has any, else at level 1 and 2
+/// headings. Frontmatter and blank slides are left out.
+pub fn slides(lines: &[String]) -> Vec<(usize, usize)> {
+    let body = body_start(lines);
+
+    let mut rules = Vec::new();
+    let mut in_fence = false;
+    for (row, line) in lines.iter().enumerate().skip(body) {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        // After text, `---` underlines a heading instead.
+        if !in_fence && trimmed == "---" && (row == body || lines[row - 1].trim().is_empty()) {
+            rules.push(row);
+        }
+    }
+    let mut bounds: Vec<(usize, usize)> = Vec::new();
+    if rules.is_empty() {
+        let mut start = body;
+        for h in headings(lines).iter().filter(|h| h.row >= body && h.level <= 2) {
+            bounds.push((start, h.row));
+            start = h.row;
+        }
+        bounds.push((start, lines.len()));
+    } else {
+        let mut start = body;
+        for row in rules {
+            bounds.push((start, row));
+            start = row + 1;
+        }
+        bounds.push((start, lines.len()));
+    }
+    bounds.retain(|&(start, end)| lines[start..end].iter().any(|l| !l.trim().is_empty()));
+    bounds
+}
+
+/// `content` with `tag` added to the frontmatter `tags:`, in whichever of
+/// the inline (`[a, b]`, `a, b`) or `- item` list forms it already uses;
+/// a frontmatter is created when missing. `None` when the tag is there.
+pub fn add_tag(content: &str, tag: &str) -> Option<String> {
+    let mut lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+    let close = match lines[0].trim_end() {
+        "---" => lines.iter().skip(1).position(|l| matches!(l.trim_end(), "---" | "...")).map(|i| i + 1),
+        _ => None,
+    };
+    let Some(close) = close else {
+        return Some(format!("---\ntags: [{}]\n---\n{}", tag, content));
+    };
+    let clean = |item: &str| item.trim().trim_matches(['"', '\'']).trim_start_matches('#').to_string();
+    let Some(row) = (1..close).find(|&i| lines[i].split_once(':').is_some_and(|(key, _)| key == "tags")) else {
+        lines.insert(close, format!("tags: [{}]", tag));
+        return Some(lines.join("\n"));
+    };
+    let value = lines[row].split_once(':').map_or("", |(_, v)| v.trim()).to_string();
+    if value.is_empty() {
+        let items: Vec<usize> = (row + 1..close).take_while(|&i| lines[i].trim_start().starts_with("- ")).collect();
+        if items.iter().any(|&i| clean(&lines[i].trim_start()[2..]) == tag) {
+            return None;
+        }
+        let indent = items.first().map_or("  ", |&i| &lines[i][..lines[i].len() - lines[i].trim_start().len()]).to_string();
+        lines.insert(row + 1 + items.len(), format!("{}- {}", indent, tag));
+    } else if let Some(inner) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        if inner.split(',').any(|t| clean(t) == tag) {
+            return None;
+        }
+        let sep = if inner.trim().is_empty() { "" } else { ", " };
+        lines[row] = format!("tags: [{}{}{}]", inner.trim(), sep, tag);
+    } else {
+        if value.split([',', ' ']).any(|t| clean(t) == tag) {
+            return None;
+        }
+        let sep = if value.contains(',') { ", " } else { " " };
+        lines[row] = format!("tags: {}{}{}", value, sep, tag);
+    }
+    Some(lines.join("\n"))
+}
+
+/// A `[^label]` footnote reference, or the definition of one when it opens
+/// the line followed by `:`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Footnote {
+    /// Byte range of the `[^label]`.
+    pub start: usize,
+    pub end: usize,
+    pub label: String,
+    pub definition: bool,
+}
+
+pub fn footnotes(line: &str) -> Vec<Footnote> {
+    let mut out = Vec::new();
+    let mut from = 0;
+    while let Some(start) = line[from..].find("[^").map(|i| from + i) {
+        let Some(close) = line[start + 2..].find(']').map(|i| start + 2 + i) else { break };
+        let label = &line[start + 2..close];
+        if !label.is_empty() && !label.contains([' ', '[']) {
+            let definition = start == 0 && line[close + 1..].starts_with(':');
+            out.push(Footnote { start, end: close + 1, label: label.to_string(), definition });
+        }
+        from = close + 1;
+    }
+    out
+}
+
+/// Where the footnote at `(row, col)` leads, as `(row, col)`: a reference
+/// goes to its definition, a definition back to its first reference.
+pub fn footnote_jump(lines: &[String], row: usize, col: usize) -> Option<(usize, usize)> {
+    let here = footnotes(&lines[row]).into_iter().find(|f| (f.start..=f.end).contains(&col))?;
+    lines.iter().enumerate().find_map(|(r, line)| {
+        footnotes(line)
+            .into_iter()
+            .find(|f| f.label == here.label && f.definition != here.definition)
+            .map(|f| (r, f.start))
+    })
+}
+
+/// Obsidian callout types, with their aliases folded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalloutKind {
+    Note,
+    Tip,
+    Success,
+    Question,
+    Warning,
+    Danger,
+    Example,
+    Quote,
+}
+
+impl CalloutKind {
+    /// Unknown types look like notes, as in Obsidian.
+    fn parse(name: &str) -> Self {
+        match name {
+            "abstract" | "summary" | "tldr" | "tip" | "hint" | "important" => CalloutKind::Tip,
+            "success" | "check" | "done" => CalloutKind::Success,
+            "question" | "help" | "faq" => CalloutKind::Question,
+            "warning" | "caution" | "attention" => CalloutKind::Warning,
+            "failure" | "fail" | "missing" | "danger" | "error" | "bug" => CalloutKind::Danger,
+            "example" => CalloutKind::Example,
+            "quote" | "cite" => CalloutKind::Quote,
+            _ => CalloutKind::Note,
+        }
+    }
+}
+
+/// The kind and title of a `> [!type] Title` line opening a callout; the
+/// title defaults to the type, capitalized.
+pub fn callout(line: &str) -> Option<(CalloutKind, String)> {
+    let rest = line.trim_start().strip_prefix('>')?.trim_start().strip_prefix("[!")?;
+    let (name, after) = rest.split_once(']')?;
+    // `-` and `+` after the type only say whether Obsidian folds it.
+    let title = after.trim_start_matches(['-', '+']).trim();
+    let name = name.trim().to_lowercase();
+    let title = if title.is_empty() {
+        let mut chars = name.chars();
+        chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+    } else {
+        title.to_string()
+    };
+    Some((CalloutKind::parse(&name), title))
+}
+
+/// A fenced code block: `start` and `end` are the rows of its fences
+/// (`end` is the row count when the block is never closed).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    pub start: usize,
+    pub end: usize,
+    /// The language tag after the opening fence, possibly empty.
+    pub lang: String,
+}
+
+impl CodeBlock {
+    /// Rows between the fences.
+    pub fn body(&self) -> std::ops::Range<usize> {
+        self.start + 1..self.end
+    }
+}
+
+pub fn code_blocks(lines: &[String]) -> Vec<CodeBlock> {
+    let mut out = Vec::new();
+    let mut open: Option<(usize, (char, usize), String)> = None;
+    for (row, line) in lines.iter().enumerate() {
+        let Some(fence) = fence_marker(line) else { continue };
+        match open.take() {
+            Some((start, (c, n), lang)) if fence.0 == c && fence.1 >= n => out.push(CodeBlock { start, end: row, lang }),
+            Some(other) => open = Some(other),
+            None => {
+                let lang = line.trim_start()[fence.1..].split_whitespace().next().unwrap_or_default().to_string();
+                open = Some((row, fence, lang));
+            }
+        }
+    }
+    if let Some((start, _, lang)) = open {
+        out.push(CodeBlock { start, end: lines.len(), lang });
+    }
+    out
+}
+
+/// The fence character and length of `line` when it is a code fence:
+/// three or more backticks or tildes. A block is closed by a fence of
+/// the same character at least as long as the one that opened it.
+pub fn fence_marker(line: &str) -> Option<(char, usize)> {
+    let trimmed = line.trim_start();
+    let c = trimmed.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = trimmed.chars().take_while(|&x| x == c).count();
+    (len >= 3).then_some((c, len))
+}
+
+/// Whether each row of `lines` is part of a fenced code block, fences
+/// included.
+pub fn fenced_rows(lines: &[String]) -> Vec<bool> {
+    let mut out = vec![false; lines.len()];
+    for block in code_blocks(lines) {
+        out[block.start..(block.end + 1).min(lines.len())].fill(true);
+    }
+    out
+}
+
+/// The code block `row` is in, fences included.
+pub fn code_block_at(lines: &[String], row: usize) -> Option<CodeBlock> {
+    code_blocks(lines).into_iter().find(|b| (b.start..=b.end).contains(&row))
+}
+
+/// Tags of a note: the frontmatter `tags:` (inline or as a list) and
+/// `#tag` words in the body, without the `#`, deduplicated in order.
+pub fn tags(content: &str) -> Vec<String> {
+    let lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let body = body_start(&lines);
+    let mut out: Vec<String> = Vec::new();
+    let mut push = |tag: &str| {
+        let tag = tag.trim().trim_matches(['"', '\'']).trim_start_matches('#');
+        if !tag.is_empty() && !out.iter().any(|t| t == tag) {
+            out.push(tag.to_string());
+        }
+    };
+    let mut in_list = false;
+    for line in lines.iter().take(body.saturating_sub(1)).skip(1) {
+        if in_list && let Some(item) = line.trim_start().strip_prefix("- ") {
+            push(item);
+            continue;
+        }
+        in_list = false;
+        let Some(("tags", value)) = line.split_once(':') else { continue };
+        let value = value.trim();
+        match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            _ if value.is_empty() => in_list = true,
+            Some(inner) => inner.split(',').for_each(&mut push),
+            None => value.split(',').for_each(&mut push),
+        }
+    }
+    let mut in_fence = false;
+    for line in &lines[body.min(lines.len())..] {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        if in_fence || (trimmed.starts_with('#') && trimmed.trim_start_matches('#').starts_with(' ')) {
+            continue;
+        }
+        for (i, _) in line.match_indices('#') {
+            let before = line[..i].chars().next_back();
+            if before.is_some_and(|c| !c.is_whitespace()) {
+                continue;
+            }
+            let word: String = line[i + 1..].chars().take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/')).collect();
+            // `#1` is an issue number, not a tag.
+            if word.chars().any(|c| !c.is_ascii_digit()) {
+                push(&word);
+            }
+        }
+    }
+    out
+}
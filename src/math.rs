@@ -0,0 +1,169 @@
+//! TeX math in the read-only views: finding `$inline$` spans and `$$`
+//! blocks, and optionally turning simple expressions into Unicode.
+
+/// Byte ranges of the `$...$` spans in `line`, dollars included. Follows
+/// pandoc: no space just inside the dollars, no digit right after the
+/// closing one, so prices don't count.
+pub fn inline_spans(line: &str) -> Vec<(usize, usize)> {
+    let bytes = line.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            i += 2;
+            continue;
+        }
+        if bytes[i] != b'$' || bytes.get(i + 1).is_none_or(|&b| b == b'$' || b == b' ') {
+            i += 1;
+            continue;
+        }
+        let close = (i + 1..bytes.len()).find(|&j| bytes[j] == b'$' && bytes[j - 1] != b'\\');
+        match close {
+            Some(j) if bytes[j - 1] != b' ' && !bytes.get(j + 1).is_some_and(u8::is_ascii_digit) => {
+                out.push((i, j + 1));
+                i = j + 1;
+            }
+            _ => i += 1,
+        }
+    }
+    out
+}
+
+/// Whether each row is inside a `$$` display block, delimiters included.
+pub fn block_rows(rows: &[String]) -> Vec<bool> {
+    let mut out = vec![false; rows.len()];
+    let mut open = false;
+    for (row, line) in rows.iter().enumerate() {
+        let trimmed = line.trim();
+        let delimiters = trimmed.matches("$$").count();
+        if open || trimmed.starts_with("$$") {
+            out[row] = true;
+        }
+        if delimiters % 2 == 1 {
+            open = !open;
+        }
+    }
+    out
+}
+
+const SYMBOLS: &[(&str, &str)] = &[
+    ("alpha", "α"), ("beta", "β"), ("gamma", "γ"), ("delta", "δ"), ("epsilon", "ε"), ("varepsilon", "ε"),
+    ("zeta", "ζ"), ("eta", "η"), ("theta", "θ"), ("iota", "ι"), ("kappa", "κ"), ("lambda", "λ"), ("mu", "μ"),
+    ("nu", "ν"), ("xi", "ξ"), ("pi", "π"), ("rho", "ρ"), ("sigma", "σ"), ("tau", "τ"), ("phi", "φ"),
+    ("varphi", "φ"), ("chi", "χ"), ("psi", "ψ"), ("omega", "ω"), ("Gamma", "Γ"), ("Delta", "Δ"),
+    ("Theta", "Θ"), ("Lambda", "Λ"), ("Pi", "Π"), ("Sigma", "Σ"), ("Phi", "Φ"), ("Psi", "Ψ"), ("Omega", "Ω"),
+    ("sum", "∑"), ("prod", "∏"), ("int", "∫"), ("oint", "∮"), ("partial", "∂"), ("nabla", "∇"),
+    ("infty", "∞"), ("pm", "±"), ("mp", "∓"), ("times", "×"), ("div", "÷"), ("cdot", "·"), ("circ", "∘"),
+    ("le", "≤"), ("leq", "≤"), ("ge", "≥"), ("geq", "≥"), ("ne", "≠"), ("neq", "≠"), ("approx", "≈"),
+    ("equiv", "≡"), ("sim", "∼"), ("propto", "∝"), ("in", "∈"), ("notin", "∉"), ("subset", "⊂"),
+    ("subseteq", "⊆"), ("supset", "⊃"), ("cup", "∪"), ("cap", "∩"), ("emptyset", "∅"), ("forall", "∀"),
+    ("exists", "∃"), ("neg", "¬"), ("land", "∧"), ("lor", "∨"), ("to", "→"), ("rightarrow", "→"),
+    ("leftarrow", "←"), ("Rightarrow", "⇒"), ("Leftarrow", "⇐"), ("iff", "⇔"), ("mapsto", "↦"),
+    ("ldots", "…"), ("cdots", "⋯"), ("sqrt", "√"), ("mathbb{R}", "ℝ"), ("mathbb{N}", "ℕ"),
+    ("mathbb{Z}", "ℤ"), ("mathbb{Q}", "ℚ"), ("mathbb{C}", "ℂ"),
+];
+
+fn superscript(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '⁰', '1' => '¹', '2' => '²', '3' => '³', '4' => '⁴', '5' => '⁵', '6' => '⁶', '7' => '⁷',
+        '8' => '⁸', '9' => '⁹', '+' => '⁺', '-' => '⁻', '=' => '⁼', '(' => '⁽', ')' => '⁾', 'n' => 'ⁿ', 'i' => 'ⁱ',
+        _ => return None,
+    })
+}
+
+fn subscript(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '₀', '1' => '₁', '2' => '₂', '3' => '₃', '4' => '₄', '5' => '₅', '6' => '₆', '7' => '₇',
+        '8' => '₈', '9' => '₉', '+' => '₊', '-' => '₋', '=' => '₌', '(' => '₍', ')' => '₎',
+        'a' => 'ₐ', 'e' => 'ₑ', 'i' => 'ᵢ', 'j' => 'ⱼ', 'k' => 'ₖ', 'n' => 'ₙ', 'x' => 'ₓ',
+        _ => return None,
+    })
+}
+
+/// `{group}` or a single char at the start of `s`, and the rest.
+fn argument(s: &str) -> (&str, &str) {
+    if let Some(inner) = s.strip_prefix('{') {
+        let mut depth = 1;
+        for (i, c) in inner.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return (&inner[..i], &inner[i + 1..]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        return (inner, "");
+    }
+    let len = s.chars().next().map_or(0, char::len_utf8);
+    s.split_at(len)
+}
+
+/// Best-effort Unicode for simple TeX: Greek letters and common symbols,
+/// `^`/`_` with digits and a few letters, `\frac{a}{b}` as `a/b`. Anything
+/// it can't map is kept as written.
+pub fn to_unicode(tex: &str) -> String {
+    let mut out = String::new();
+    let mut rest = tex;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '\\' => {
+                let name_len = rest[1..].find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len() - 1);
+                let name = &rest[1..1 + name_len];
+                if name == "frac" {
+                    let (num, after) = argument(&rest[1 + name_len..]);
+                    let (den, after) = argument(after);
+                    out.push_str(&format!("{}/{}", to_unicode(num), to_unicode(den)));
+                    rest = after;
+                    continue;
+                }
+                let symbol = SYMBOLS.iter().find(|(n, _)| rest[1..].starts_with(n) && (*n == name || n.contains('{')));
+                match symbol {
+                    Some((n, s)) => {
+                        out.push_str(s);
+                        rest = &rest[1 + n.len()..];
+                    }
+                    None if name.is_empty() => {
+                        // `\{`, `\,` and the like: keep the char, drop spacing.
+                        let next = rest[1..].chars().next();
+                        if let Some(next) = next.filter(|c| !matches!(c, ',' | ';' | '!' | ' ')) {
+                            out.push(next);
+                        }
+                        rest = &rest[1 + next.map_or(0, char::len_utf8)..];
+                    }
+                    None => {
+                        out.push_str(&rest[..1 + name_len]);
+                        rest = &rest[1 + name_len..];
+                        if rest.starts_with('{') {
+                            let (arg, after) = argument(rest);
+                            out.push_str(&format!("{{{}}}", to_unicode(arg)));
+                            rest = after;
+                        }
+                    }
+                }
+            }
+            '^' | '_' => {
+                let (arg, after) = argument(&rest[1..]);
+                let map = if c == '^' { superscript } else { subscript };
+                let converted: Option<String> = arg.chars().map(map).collect();
+                match converted {
+                    Some(s) => out.push_str(&s),
+                    None => {
+                        out.push(c);
+                        out.push_str(&to_unicode(arg));
+                    }
+                }
+                rest = after;
+            }
+            '{' | '}' => rest = &rest[1..],
+            _ => {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,173 @@
+//! Leader-key menus (Ctrl+G): one key per entry, submenus nest.
+
+use crate::case::Case;
+
+#[derive(Debug)]
+pub struct Menu {
+    pub title: &'static str,
+    pub entries: &'static [MenuEntry],
+}
+
+#[derive(Debug)]
+pub struct MenuEntry {
+    pub key: char,
+    pub label: &'static str,
+    pub action: MenuAction,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum MenuAction {
+    Submenu(&'static Menu),
+    Commit,
+    Amend,
+    History,
+    Push,
+    Pull,
+    Fetch,
+    NewNote,
+    Save,
+    /// Moves focus to the Title pane; saving under the new title renames.
+    Rename,
+    Delete,
+    Reveal,
+    SearchVault,
+    ShowLog,
+    ShowErrors,
+    Stats,
+    Scratch,
+    /// Line cleanups on the selected lines, or the whole note.
+    SortLines,
+    UniqueLines,
+    ReverseLines,
+    Format,
+    Reflow,
+    /// Converts the selection or the word under the cursor.
+    Case(Case),
+    InsertDate,
+    ReplaceInVault,
+    /// Moves the cursor's heading section, or the rest of the note, into a
+    /// new note and leaves a wikilink behind.
+    ExtractFromCursor,
+    /// Diff of the buffer against the file on disk.
+    DiffSaved,
+    /// Diff of the buffer against the note in `HEAD`.
+    DiffCommitted,
+    /// Snapshots from `.history/`, for vaults without git.
+    LocalHistory,
+    /// Same for the selection, then opens the new note.
+    ExtractSelection,
+    /// Broken links and notes nothing links to.
+    LinkReport,
+    Remotes,
+    /// `git stash` of the uncommitted changes, after saving the buffer.
+    Stash,
+    /// Stash list to apply or pop entries from.
+    Stashes,
+    /// Files the open note links to in `assets/`.
+    Attachments,
+    /// Attachments across the vault that no note links to.
+    OrphanAttachments,
+    /// Copies a file into the vault and links it at the cursor.
+    ImportFile,
+    /// Shows the note full-screen, one slide at a time.
+    Present,
+    /// Files deleted this session, to restore.
+    RecentlyDeleted,
+    /// Word count, reading time and most used words of the open note.
+    NoteInfo,
+    /// Copies the lines of the fenced code block around the cursor.
+    CopyCodeBlock,
+    /// Runs the code block around the cursor and puts its output below it.
+    RunCodeBlock,
+    /// Renders the mermaid block around the cursor and opens the image.
+    OpenDiagram,
+    /// Copies the selection, or the whole note, rendered as HTML.
+    CopyHtml,
+}
+
+impl Menu {
+    pub fn entry(&self, key: char) -> Option<&MenuEntry> {
+        self.entries.iter().find(|e| e.key == key)
+    }
+}
+
+pub static ROOT: Menu = Menu {
+    title: "Menu",
+    entries: &[
+        MenuEntry { key: 'g', label: "Git", action: MenuAction::Submenu(&GIT) },
+        MenuEntry { key: 'f', label: "File", action: MenuAction::Submenu(&FILE) },
+        MenuEntry { key: 't', label: "Text", action: MenuAction::Submenu(&TEXT) },
+        MenuEntry { key: '/', label: "Search vault", action: MenuAction::SearchVault },
+        MenuEntry { key: 'r', label: "Replace in vault", action: MenuAction::ReplaceInVault },
+        MenuEntry { key: 'b', label: "Broken links & orphans", action: MenuAction::LinkReport },
+        MenuEntry { key: 'a', label: "Orphaned attachments", action: MenuAction::OrphanAttachments },
+        MenuEntry { key: 's', label: "Stats", action: MenuAction::Stats },
+        MenuEntry { key: 'x', label: "Scratch buffer", action: MenuAction::Scratch },
+        MenuEntry { key: 'l', label: "Debug log", action: MenuAction::ShowLog },
+        MenuEntry { key: 'e', label: "Errors", action: MenuAction::ShowErrors },
+    ],
+};
+
+pub static GIT: Menu = Menu {
+    title: "Git",
+    entries: &[
+        MenuEntry { key: 'c', label: "Commit all changes", action: MenuAction::Commit },
+        MenuEntry { key: 'a', label: "Amend last commit", action: MenuAction::Amend },
+        MenuEntry { key: 'h', label: "History of this note", action: MenuAction::History },
+        MenuEntry { key: 'd', label: "Diff with last commit", action: MenuAction::DiffCommitted },
+        MenuEntry { key: 'p', label: "Push", action: MenuAction::Push },
+        MenuEntry { key: 'P', label: "Pull", action: MenuAction::Pull },
+        MenuEntry { key: 'f', label: "Fetch", action: MenuAction::Fetch },
+        MenuEntry { key: 's', label: "Stash changes", action: MenuAction::Stash },
+        MenuEntry { key: 'S', label: "Stashes", action: MenuAction::Stashes },
+        MenuEntry { key: 'r', label: "Remotes", action: MenuAction::Remotes },
+    ],
+};
+
+pub static FILE: Menu = Menu {
+    title: "File",
+    entries: &[
+        MenuEntry { key: 'n', label: "New note", action: MenuAction::NewNote },
+        MenuEntry { key: 's', label: "Save", action: MenuAction::Save },
+        MenuEntry { key: 'c', label: "Unsaved changes (diff)", action: MenuAction::DiffSaved },
+        MenuEntry { key: 'r', label: "Rename (edit title)", action: MenuAction::Rename },
+        MenuEntry { key: 'd', label: "Delete note", action: MenuAction::Delete },
+        MenuEntry { key: 'u', label: "Recently deleted", action: MenuAction::RecentlyDeleted },
+        MenuEntry { key: 'x', label: "Extract from here to new note", action: MenuAction::ExtractFromCursor },
+        MenuEntry { key: 'e', label: "Extract selection to new note", action: MenuAction::ExtractSelection },
+        MenuEntry { key: '.', label: "Reveal in sidebar", action: MenuAction::Reveal },
+        MenuEntry { key: 'l', label: "Local history", action: MenuAction::LocalHistory },
+        MenuEntry { key: 'a', label: "Attachments", action: MenuAction::Attachments },
+        MenuEntry { key: 'i', label: "Import file", action: MenuAction::ImportFile },
+        MenuEntry { key: 'p', label: "Present", action: MenuAction::Present },
+        MenuEntry { key: 'w', label: "Note info (words, reading time)", action: MenuAction::NoteInfo },
+    ],
+};
+
+pub static TEXT: Menu = Menu {
+    title: "Text",
+    entries: &[
+        MenuEntry { key: 's', label: "Sort lines", action: MenuAction::SortLines },
+        MenuEntry { key: 'u', label: "Remove duplicate lines", action: MenuAction::UniqueLines },
+        MenuEntry { key: 'r', label: "Reverse lines", action: MenuAction::ReverseLines },
+        MenuEntry { key: 'w', label: "Reflow paragraph", action: MenuAction::Reflow },
+        MenuEntry { key: 'f', label: "Format note", action: MenuAction::Format },
+        MenuEntry { key: 'c', label: "Case", action: MenuAction::Submenu(&CASE) },
+        MenuEntry { key: 'd', label: "Insert date/time", action: MenuAction::InsertDate },
+        MenuEntry { key: 'b', label: "Copy code block", action: MenuAction::CopyCodeBlock },
+        MenuEntry { key: 'x', label: "Run code block", action: MenuAction::RunCodeBlock },
+        MenuEntry { key: 'm', label: "Open diagram (mermaid)", action: MenuAction::OpenDiagram },
+        MenuEntry { key: 'h', label: "Copy as HTML", action: MenuAction::CopyHtml },
+    ],
+};
+
+pub static CASE: Menu = Menu {
+    title: "Case",
+    entries: &[
+        MenuEntry { key: 'u', label: "UPPERCASE", action: MenuAction::Case(Case::Upper) },
+        MenuEntry { key: 'l', label: "lowercase", action: MenuAction::Case(Case::Lower) },
+        MenuEntry { key: 't', label: "Title Case", action: MenuAction::Case(Case::Title) },
+        MenuEntry { key: 'k', label: "kebab-case", action: MenuAction::Case(Case::Kebab) },
+        MenuEntry { key: 's', label: "snake_case", action: MenuAction::Case(Case::Snake) },
+    ],
+};
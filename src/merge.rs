@@ -0,0 +1,122 @@
+//! Three-way merge of the buffer ("mine") and the file on disk ("theirs")
+//! against the version both started from ("base"). Changes that don't
+//! overlap are taken automatically; overlapping ones become hunks the user
+//! resolves one by one.
+
+use crate::diff::{diff_lines, DiffLine};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Mine,
+    Theirs,
+    /// Mine followed by theirs.
+    Both,
+}
+
+#[derive(Debug, Clone)]
+pub enum Region {
+    Stable(Vec<String>),
+    Conflict { base: Vec<String>, mine: Vec<String>, theirs: Vec<String>, choice: Choice },
+}
+
+/// A replacement of `base[start..end]` by `lines`.
+#[derive(Debug, Clone)]
+struct Chunk {
+    start: usize,
+    end: usize,
+    lines: Vec<String>,
+}
+
+fn chunks(base: &[String], other: &[String]) -> Vec<Chunk> {
+    let mut out: Vec<Chunk> = Vec::new();
+    let mut at = 0;
+    let mut open: Option<Chunk> = None;
+    for line in diff_lines(base, other) {
+        match line {
+            DiffLine::Same(_) | DiffLine::Skipped(_) => {
+                out.extend(open.take());
+                at += 1;
+            }
+            DiffLine::Removed(_) => {
+                open.get_or_insert(Chunk { start: at, end: at, lines: Vec::new() }).end += 1;
+                at += 1;
+            }
+            DiffLine::Added(l) => open.get_or_insert(Chunk { start: at, end: at, lines: Vec::new() }).lines.push(l),
+        }
+    }
+    out.extend(open);
+    out
+}
+
+/// `base[start..end]` with the given chunks (all inside that range) applied.
+fn apply(base: &[String], start: usize, end: usize, chunks: &[&Chunk]) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut at = start;
+    for c in chunks {
+        out.extend_from_slice(&base[at..c.start]);
+        out.extend(c.lines.iter().cloned());
+        at = c.end;
+    }
+    out.extend_from_slice(&base[at..end]);
+    out
+}
+
+pub fn merge(base: &[String], mine: &[String], theirs: &[String]) -> Vec<Region> {
+    let mut all: Vec<(bool, Chunk)> = chunks(base, mine).into_iter().map(|c| (true, c)).collect();
+    all.extend(chunks(base, theirs).into_iter().map(|c| (false, c)));
+    all.sort_by_key(|(_, c)| (c.start, c.end));
+
+    let mut regions = Vec::new();
+    let mut at = 0;
+    let mut i = 0;
+    while i < all.len() {
+        // Grow a group while the next chunk overlaps or touches it.
+        let (start, mut end) = (all[i].1.start, all[i].1.end);
+        let mut j = i + 1;
+        while j < all.len() && (all[j].1.start < end || all[j].1.start == start || (all[j].1.start == end && all[j].1.start == all[j].1.end)) {
+            end = end.max(all[j].1.end);
+            j += 1;
+        }
+        let group = &all[i..j];
+        if start > at {
+            regions.push(Region::Stable(base[at..start].to_vec()));
+        }
+        let side = |m: bool| -> Vec<&Chunk> { group.iter().filter(|(is_mine, _)| *is_mine == m).map(|(_, c)| c).collect() };
+        let (m, t) = (side(true), side(false));
+        let mine_text = apply(base, start, end, &m);
+        let theirs_text = apply(base, start, end, &t);
+        if t.is_empty() || mine_text == theirs_text {
+            regions.push(Region::Stable(mine_text));
+        } else if m.is_empty() {
+            regions.push(Region::Stable(theirs_text));
+        } else {
+            regions.push(Region::Conflict { base: base[start..end].to_vec(), mine: mine_text, theirs: theirs_text, choice: Choice::Mine });
+        }
+        at = end;
+        i = j;
+    }
+    if at < base.len() {
+        regions.push(Region::Stable(base[at..].to_vec()));
+    }
+    regions
+}
+
+pub fn conflicts(regions: &[Region]) -> usize {
+    regions.iter().filter(|r| matches!(r, Region::Conflict { .. })).count()
+}
+
+/// The merged text with every conflict resolved by its current choice.
+pub fn resolve(regions: &[Region]) -> Vec<String> {
+    let mut out = Vec::new();
+    for region in regions {
+        match region {
+            Region::Stable(lines) => out.extend(lines.iter().cloned()),
+            Region::Conflict { mine, theirs, choice, .. } => match choice {
+                Choice::Mine => out.extend(mine.iter().cloned()),
+                Choice::Theirs => out.extend(theirs.iter().cloned()),
+                Choice::Both => out.extend(mine.iter().chain(theirs).cloned()),
+            },
+        }
+    }
+    out
+}
@@ -0,0 +1,61 @@
+//! Running helper programs: formatters, code blocks and gpg all pipe text
+//! through a child process and collect what it prints.
+
+use anyhow::{bail, Context, Result};
+use std::io::{Read, Write};
+use std::process::{Command, Output, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+pub fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(not(unix))]
+pub fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+/// Runs `cmd` with `input` on stdin and returns its exit status, stdout
+/// and stderr. A child still running after `timeout` is killed.
+///
+/// stdin is written and stdout/stderr are read on their own threads, so a
+/// child that prints a lot before it has read all of its input can't
+/// fill a pipe and leave both sides waiting on each other.
+pub fn run_piped(mut cmd: Command, input: String, timeout: Duration) -> Result<Output> {
+    let mut child = cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let mut stdin = child.stdin.take().context("No stdin pipe")?;
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() > timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("took longer than {}s and was stopped", timeout.as_secs());
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    // A child that exits without reading all of its input is not an error.
+    let _ = writer.join();
+    Ok(Output { status, stdout: stdout.join().unwrap_or_default(), stderr: stderr.join().unwrap_or_default() })
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut out = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut out);
+        }
+        out
+    })
+}
@@ -0,0 +1,127 @@
+//! `lazynotes list`, `search` and `tasks`: the vault's notes, matching
+//! lines and open tasks for scripts and launchers, as `path:line: text`
+//! lines or, with `--json`, a JSON array.
+
+use crate::config::Config;
+use crate::fs::{build_notes_tree, collect_files, notes_dir_status, NotesDirStatus};
+use crate::links::is_note;
+use crate::search::{relative_to, search_vault, Matcher, SearchOptions};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Search hits reported at most.
+const SEARCH_LIMIT: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Query {
+    List,
+    Search,
+    Tasks,
+}
+
+/// One result. `line` (1-based) and `snippet` are left out of `list`.
+#[derive(Debug, Serialize)]
+struct Entry {
+    /// Relative to the notes directory.
+    path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snippet: Option<String>,
+    tags: Vec<String>,
+    /// Seconds since the Unix epoch.
+    mtime: Option<u64>,
+}
+
+fn notes(config: &Config) -> Result<(PathBuf, Vec<PathBuf>)> {
+    let root = config.notes_path();
+    if notes_dir_status(&root) == NotesDirStatus::Unavailable {
+        bail!("Notes directory {} is not available (unmounted?)", root.display());
+    }
+    let files = collect_files(&build_notes_tree(&root, config.symlinks)?).into_iter().filter(|p| is_note(p)).collect();
+    Ok((root, files))
+}
+
+fn mtime(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+/// Tags and modification time of a note, worked out once per file
+/// however many of its lines are reported.
+type Meta = (Vec<String>, Option<u64>);
+
+fn meta(path: &Path, content: &str) -> Meta {
+    (crate::markdown::tags(content), mtime(path))
+}
+
+fn entry(path: &Path, root: &Path, at: Option<(usize, String)>, (tags, mtime): &Meta) -> Entry {
+    let (line, snippet) = at.map_or((None, None), |(row, text)| (Some(row + 1), Some(text)));
+    Entry {
+        path: relative_to(path, root).to_path_buf(),
+        line,
+        snippet,
+        tags: tags.clone(),
+        mtime: *mtime,
+    }
+}
+
+fn entries(config: &Config, query: Query, text: Option<&str>) -> Result<Vec<Entry>> {
+    let (root, files) = notes(config)?;
+    let read = |p: &Path| std::fs::read_to_string(p).unwrap_or_default();
+    Ok(match query {
+        Query::List => files.iter().map(|p| entry(p, &root, None, &meta(p, &read(p)))).collect(),
+        Query::Search => {
+            let matcher = Matcher::new(text.unwrap_or_default(), SearchOptions::default())?;
+            let mut metas: HashMap<PathBuf, Meta> = HashMap::new();
+            search_vault(&files, &matcher, SEARCH_LIMIT)
+                .into_iter()
+                .map(|hit| {
+                    let meta = metas.entry(hit.path.clone()).or_insert_with(|| meta(&hit.path, &read(&hit.path)));
+                    entry(&hit.path, &root, Some((hit.row, hit.line.trim().to_string())), meta)
+                })
+                .collect()
+        }
+        Query::Tasks => {
+            let mut out = Vec::new();
+            for path in &files {
+                let content = read(path);
+                let mut note_meta = None;
+                for (row, line) in content.lines().enumerate() {
+                    let trimmed = line.trim_start();
+                    if let Some(task) = ["- [ ] ", "* [ ] ", "+ [ ] "].into_iter().find_map(|p| trimmed.strip_prefix(p)) {
+                        let meta = note_meta.get_or_insert_with(|| meta(path, &content));
+                        out.push(entry(path, &root, Some((row, task.trim().to_string())), meta));
+                    }
+                }
+            }
+            out
+        }
+    })
+}
+
+/// Prints the results of `query`; `text` is the search query.
+pub fn run(config: &Config, query: Query, text: Option<&str>, json: bool) -> Result<()> {
+    let entries = entries(config, query, text)?;
+    let out = if json {
+        format!("{}\n", serde_json::to_string_pretty(&entries)?)
+    } else {
+        entries
+            .iter()
+            .map(|e| match (e.line, &e.snippet) {
+                (Some(line), Some(snippet)) => format!("{}:{}: {}\n", e.path.display(), line, snippet),
+                _ => format!("{}\n", e.path.display()),
+            })
+            .collect()
+    };
+    let mut stdout = std::io::stdout().lock();
+    // A closed pipe (`| head`) is the reader being done, not an error.
+    match stdout.write_all(out.as_bytes()).and_then(|_| stdout.flush()) {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        other => Ok(other?),
+    }
+}
@@ -0,0 +1,94 @@
+//! Vault-wide find and replace: collect every match with its proposed
+//! replacement for review, then rewrite only the accepted ones.
+
+use crate::crypt;
+use crate::fs::{read_note, write_note};
+use crate::search::Matcher;
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct ReplaceMatch {
+    pub path: PathBuf,
+    pub row: usize,
+    pub start: usize,
+    pub end: usize,
+    pub line: String,
+    pub replacement: String,
+    pub accepted: bool,
+}
+
+impl ReplaceMatch {
+    /// The line as it reads after this one replacement.
+    pub fn preview(&self) -> String {
+        format!("{}{}{}", &self.line[..self.start], self.replacement, &self.line[self.end..])
+    }
+}
+
+/// Every match of `matcher` in `files`, up to `limit`. With `expand`,
+/// `$1`/`${name}` in `with` refer to the regex's capture groups.
+pub fn find_matches(files: &[PathBuf], matcher: &Matcher, with: &str, expand: bool, limit: usize) -> Vec<ReplaceMatch> {
+    let mut out = Vec::new();
+    if matcher.is_empty() {
+        return out;
+    }
+    for path in files {
+        let Ok(content) = std::fs::read_to_string(path) else { continue };
+        // Rewriting armored text would leave a note nobody can decrypt.
+        if content.contains('\0') || crypt::is_sealed(&content) {
+            continue;
+        }
+        for (row, line) in content.lines().enumerate() {
+            for (start, end) in matcher.find_in_line(line) {
+                let replacement = match matcher {
+                    Matcher::Regex(re) if expand => {
+                        let mut dst = String::new();
+                        if let Some(caps) = re.captures_at(line, start) {
+                            caps.expand(with, &mut dst);
+                        }
+                        dst
+                    }
+                    _ => with.to_string(),
+                };
+                out.push(ReplaceMatch { path: path.clone(), row, start, end, line: line.to_string(), replacement, accepted: true });
+                if out.len() >= limit {
+                    return out;
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Rewrites the accepted matches on disk and returns the files changed
+/// and the number of matches written. Lines that changed since the
+/// preview, and notes encrypted since, are left alone.
+pub fn apply(matches: &[ReplaceMatch]) -> Result<(Vec<PathBuf>, usize)> {
+    let mut by_file: BTreeMap<&Path, Vec<&ReplaceMatch>> = BTreeMap::new();
+    for m in matches.iter().filter(|m| m.accepted) {
+        by_file.entry(&m.path).or_default().push(m);
+    }
+    let mut changed = Vec::new();
+    let mut written = 0;
+    for (path, mut file_matches) in by_file {
+        let content = read_note(path)?;
+        if crypt::is_sealed(&content) {
+            continue;
+        }
+        let mut lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+        file_matches.retain(|m| lines.get(m.row).map(|l| l.trim_end_matches('\r')) == Some(m.line.as_str()));
+        if file_matches.is_empty() {
+            continue;
+        }
+        // Right to left so earlier offsets on the same line stay valid.
+        file_matches.sort_by_key(|m| std::cmp::Reverse((m.row, m.start)));
+        written += file_matches.len();
+        for m in file_matches {
+            lines[m.row].replace_range(m.start..m.end, &m.replacement);
+        }
+        write_note(path, &lines.join("\n"))?;
+        changed.push(path.to_path_buf());
+    }
+    Ok((changed, written))
+}
@@ -0,0 +1,76 @@
+//! "Run code block": feeds a fenced block to the command configured for
+//! its language in `run_blocks` and writes what it printed into an
+//! `output` block right below it, so notes can double as runbooks.
+
+use crate::markdown::CodeBlock;
+use crate::process;
+use anyhow::{anyhow, Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Duration;
+
+/// Language tag of the block that holds a block's output.
+pub const OUTPUT: &str = "output";
+
+/// Blocks still running after this are killed.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A block running on a worker thread so the UI keeps drawing; the
+/// event loop collects it with `finished`.
+pub struct Job {
+    /// The note the block is in, to put the output back into.
+    pub note: Option<PathBuf>,
+    pub block: CodeBlock,
+    /// The block's code when it started, to find it again if rows moved.
+    pub code: Vec<String>,
+    rx: Receiver<Result<(bool, Vec<String>)>>,
+}
+
+impl Job {
+    pub fn spawn(command: String, note: Option<PathBuf>, block: CodeBlock, code: Vec<String>, dir: PathBuf) -> Job {
+        let (tx, rx) = mpsc::channel();
+        let input = code.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(run(&command, &input, &dir));
+        });
+        Job { note, block, code, rx }
+    }
+
+    /// What `run` returned, once the block is done.
+    pub fn finished(&self) -> Option<Result<(bool, Vec<String>)>> {
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(anyhow!("The block runner stopped"))),
+        }
+    }
+}
+
+/// Runs `code` through `command` (by the shell) in `dir`, returning the
+/// combined stdout and stderr lines and whether it exited successfully.
+pub fn run(command: &str, code: &[String], dir: &Path) -> Result<(bool, Vec<String>)> {
+    let mut cmd = process::shell(&format!("{} 2>&1", command));
+    cmd.current_dir(dir);
+    let input = format!("{}\n", code.join("\n"));
+    let output = process::run_piped(cmd, input, TIMEOUT).with_context(|| format!("'{}'", command))?;
+    let lines = String::from_utf8_lossy(&output.stdout).lines().map(str::to_string).collect();
+    Ok((output.status.success(), lines))
+}
+
+/// `lines` with `output` in an `output` block right after `block`,
+/// replacing the one a previous run left there.
+pub fn insert_output(lines: &mut Vec<String>, block: &CodeBlock, output: Vec<String>) {
+    let at = block.end + 1;
+    let old = crate::markdown::code_blocks(lines)
+        .into_iter()
+        .find(|b| b.start == at && b.lang == OUTPUT && b.end < lines.len())
+        .map_or(at..at, |b| at..b.end + 1);
+    // The fence outlasts any backtick run in the output, so printed
+    // fences can't close the block early.
+    let longest = output.iter().flat_map(|l| l.split(|c| c != '`')).map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    let mut new = vec![format!("{}{}", fence, OUTPUT)];
+    new.extend(output);
+    new.push(fence);
+    lines.splice(old, new);
+}
@@ -0,0 +1,181 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Toggles shared by the in-note find and the vault search (Alt+R, Alt+C,
+/// Alt+W in either prompt).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchOptions {
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+}
+
+impl SearchOptions {
+    /// Prompt badge such as `[.* Aa \b]`; empty with every toggle off.
+    pub fn indicator(self) -> String {
+        let flags: Vec<&str> = [(self.regex, ".*"), (self.case_sensitive, "Aa"), (self.whole_word, "\\b")]
+            .into_iter()
+            .filter_map(|(on, flag)| on.then_some(flag))
+            .collect();
+        if flags.is_empty() { String::new() } else { format!("[{}] ", flags.join(" ")) }
+    }
+}
+
+/// A query compiled with its options. The default options keep the plain
+/// Unicode case-insensitive substring search.
+pub enum Matcher {
+    Plain(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn new(query: &str, options: SearchOptions) -> Result<Self, regex::Error> {
+        if options == SearchOptions::default() {
+            return Ok(Matcher::Plain(query.to_string()));
+        }
+        let pattern = if options.regex { query.to_string() } else { regex::escape(query) };
+        let pattern = if options.whole_word { format!(r"\b(?:{})\b", pattern) } else { pattern };
+        RegexBuilder::new(&pattern).case_insensitive(!options.case_sensitive).build().map(Matcher::Regex)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Matcher::Plain(q) => q.is_empty(),
+            Matcher::Regex(re) => re.as_str().is_empty(),
+        }
+    }
+
+    /// Byte ranges of the non-overlapping, non-empty matches in `line`.
+    pub fn find_in_line(&self, line: &str) -> Vec<(usize, usize)> {
+        match self {
+            Matcher::Plain(query) => find_in_line(line, query),
+            Matcher::Regex(re) => re.find_iter(line).filter(|m| !m.is_empty()).map(|m| (m.start(), m.end())).collect(),
+        }
+    }
+}
+
+/// One matching line from a vault-wide search.
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub path: PathBuf,
+    pub row: usize,
+    pub start: usize,
+    pub end: usize,
+    pub line: String,
+}
+
+/// Byte ranges of every non-overlapping, case-insensitive occurrence of
+/// `query` in `line`.
+pub fn find_in_line(line: &str, query: &str) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    if query.is_empty() {
+        return out;
+    }
+    if query.is_ascii() {
+        // ASCII lowercasing keeps byte offsets intact, so plain `find` works.
+        let hay = line.to_ascii_lowercase();
+        let needle = query.to_ascii_lowercase();
+        let mut from = 0;
+        while let Some(i) = hay[from..].find(&needle) {
+            out.push((from + i, from + i + needle.len()));
+            from += i + needle.len();
+        }
+        return out;
+    }
+    let mut from = 0;
+    while from < line.len() {
+        let Some((start, end)) = line[from..]
+            .char_indices()
+            .find_map(|(i, _)| match_at(line, from + i, query).map(|end| (from + i, end)))
+        else {
+            break;
+        };
+        out.push((start, end));
+        from = end;
+    }
+    out
+}
+
+fn match_at(line: &str, start: usize, query: &str) -> Option<usize> {
+    let mut hay = line[start..].char_indices();
+    for q in query.chars() {
+        let (_, h) = hay.next()?;
+        if !h.to_lowercase().eq(q.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(hay.next().map(|(i, _)| start + i).unwrap_or(line.len()))
+}
+
+/// All matches in a buffer as `(row, start, end)`.
+pub fn find_in_lines(lines: &[String], matcher: &Matcher) -> Vec<(usize, usize, usize)> {
+    lines
+        .iter()
+        .enumerate()
+        .flat_map(|(row, line)| matcher.find_in_line(line).into_iter().map(move |(s, e)| (row, s, e)))
+        .collect()
+}
+
+/// Searches every readable text note in `files`; binary or non-UTF-8 files
+/// are skipped. Stops after `limit` hits.
+pub fn search_vault(files: &[PathBuf], matcher: &Matcher, limit: usize) -> Vec<SearchHit> {
+    let mut hits = Vec::new();
+    if matcher.is_empty() {
+        return hits;
+    }
+    for path in files {
+        let Ok(content) = std::fs::read_to_string(path) else { continue };
+        // Armored text of encrypted notes is noise, not hits.
+        if content.contains('\0') || crate::crypt::is_sealed(&content) {
+            continue;
+        }
+        for (row, line) in content.lines().enumerate() {
+            if let Some(&(start, end)) = matcher.find_in_line(line).first() {
+                hits.push(SearchHit { path: path.clone(), row, start, end, line: line.to_string() });
+                if hits.len() >= limit {
+                    return hits;
+                }
+            }
+        }
+    }
+    hits
+}
+
+/// Display path of a hit relative to the vault root.
+pub fn relative_to<'a>(path: &'a Path, root: &Path) -> &'a Path {
+    path.strip_prefix(root).unwrap_or(path)
+}
+
+/// Subsequence fuzzy match: every query char must appear in order. Higher is
+/// better; consecutive runs and matches at word starts score extra.
+pub fn fuzzy_score(candidate: &str, query: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    let mut score = 0i64;
+    let mut run = 0i64;
+    let mut prev: Option<char> = None;
+    let mut q = query.chars().flat_map(char::to_lowercase).peekable();
+    for c in candidate.chars() {
+        let Some(&want) = q.peek() else { break };
+        let lc = c.to_lowercase().next().unwrap_or(c);
+        if lc == want {
+            q.next();
+            run += 1;
+            score += 1 + run * 2;
+            if prev.is_none_or(|p| !p.is_alphanumeric()) {
+                score += 5;
+            }
+        } else {
+            run = 0;
+        }
+        prev = Some(c);
+    }
+    if q.peek().is_some() {
+        return None;
+    }
+    // Prefer shorter candidates among equal matches.
+    Some(score * 100 - candidate.len() as i64)
+}
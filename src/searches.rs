@@ -0,0 +1,52 @@
+//! Vault-search history and saved ("smart folder") searches, kept in
+//! `searches.toml` in the config dir so they survive restarts.
+
+use crate::search::SearchOptions;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Queries kept in the history.
+const HISTORY_LEN: usize = 50;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Searches {
+    /// Newest first.
+    pub history: Vec<String>,
+    /// Name -> query.
+    pub saved: BTreeMap<String, String>,
+    /// Last used toggles of the find and vault-search prompts.
+    pub options: SearchOptions,
+}
+
+impl Searches {
+    /// A missing or unreadable file just means no history yet.
+    pub fn load() -> Self {
+        std::fs::read_to_string(path())
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = path();
+        let content = toml::to_string_pretty(self)?;
+        std::fs::write(&path, content).with_context(|| format!("Save searches to {}", path.display()))
+    }
+
+    pub fn remember(&mut self, query: &str) {
+        let query = query.trim();
+        if query.is_empty() {
+            return;
+        }
+        self.history.retain(|q| q != query);
+        self.history.insert(0, query.to_string());
+        self.history.truncate(HISTORY_LEN);
+    }
+}
+
+fn path() -> PathBuf {
+    crate::config::config_dir().join("searches.toml")
+}
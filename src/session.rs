@@ -0,0 +1,65 @@
+//! UI state restored on the next launch, kept in `session.toml` in the
+//! config dir per vault: for now the folders left expanded in the sidebar.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Vault path -> its state.
+    pub vaults: BTreeMap<String, VaultState>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VaultState {
+    /// Expanded folders relative to the vault, sorted.
+    pub expanded: Vec<PathBuf>,
+}
+
+impl Session {
+    /// A missing or unreadable file just means nothing to restore.
+    pub fn load() -> Self {
+        std::fs::read_to_string(path())
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = path();
+        let content = toml::to_string_pretty(self)?;
+        std::fs::write(&path, content).with_context(|| format!("Save session to {}", path.display()))
+    }
+
+    /// Folders left expanded in `root`, as full paths; always holds `root`.
+    pub fn expanded(&self, root: &Path) -> HashSet<PathBuf> {
+        let mut out: HashSet<PathBuf> = self
+            .vaults
+            .get(&root.to_string_lossy().into_owned())
+            .map(|v| v.expanded.iter().map(|rel| root.join(rel)).collect())
+            .unwrap_or_default();
+        out.insert(root.to_path_buf());
+        out
+    }
+
+    /// Folders removed since are dropped.
+    pub fn set_expanded(&mut self, root: &Path, dirs: &HashSet<PathBuf>) {
+        let mut expanded: Vec<PathBuf> = dirs
+            .iter()
+            .filter(|d| d.is_dir())
+            .filter_map(|d| d.strip_prefix(root).ok())
+            .filter(|rel| !rel.as_os_str().is_empty())
+            .map(Path::to_path_buf)
+            .collect();
+        expanded.sort();
+        self.vaults.entry(root.to_string_lossy().into_owned()).or_default().expanded = expanded;
+    }
+}
+
+fn path() -> PathBuf {
+    crate::config::config_dir().join("session.toml")
+}
@@ -0,0 +1,63 @@
+//! Unix signals for the TUI: SIGTERM/SIGINT/SIGHUP are turned into flags the
+//! event loop polls, so the terminal is always restored on the way out.
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// SIGTERM or SIGINT: quit, offering to save first.
+    Terminate,
+    /// SIGHUP: the terminal is gone, so there is nobody to ask.
+    Hangup,
+}
+
+#[derive(Default)]
+pub struct Signals {
+    terminate: Arc<AtomicBool>,
+    hangup: Arc<AtomicBool>,
+}
+
+impl Signals {
+    #[cfg(unix)]
+    pub fn install() -> Result<Self> {
+        use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+        use signal_hook::flag::register;
+
+        let signals = Self::default();
+        register(SIGTERM, Arc::clone(&signals.terminate))?;
+        register(SIGINT, Arc::clone(&signals.terminate))?;
+        register(SIGHUP, Arc::clone(&signals.hangup))?;
+        Ok(signals)
+    }
+
+    #[cfg(not(unix))]
+    pub fn install() -> Result<Self> {
+        Ok(Self::default())
+    }
+
+    /// Returns and clears the most urgent pending signal.
+    pub fn take(&self) -> Option<Signal> {
+        if self.hangup.swap(false, Ordering::Relaxed) {
+            Some(Signal::Hangup)
+        } else if self.terminate.swap(false, Ordering::Relaxed) {
+            Some(Signal::Terminate)
+        } else {
+            None
+        }
+    }
+}
+
+/// Stops the process as the shell's Ctrl+Z would; returns once it is resumed
+/// with `fg`. The caller leaves raw mode before and re-enters it after.
+#[cfg(unix)]
+pub fn suspend() -> Result<()> {
+    signal_hook::low_level::raise(signal_hook::consts::SIGTSTP)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn suspend() -> Result<()> {
+    Ok(())
+}
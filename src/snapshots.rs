@@ -0,0 +1,130 @@
+//! Local history: copies of notes kept under `.history/` in the vault,
+//! taken on save at most every `snapshot_interval` minutes. Works without
+//! git; the oldest snapshots are dropped once the store exceeds its cap.
+
+use crate::datefmt;
+use crate::fs::{read_note, write_note};
+use crate::search::relative_to;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub const DIR: &str = ".history";
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+impl Snapshot {
+    /// `YYYY-MM-DD HH:MM:SS`, read back from the file name.
+    pub fn label(&self) -> String {
+        let stem = self.path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        match (stem.get(0..4), stem.get(4..6), stem.get(6..8), stem.get(9..11), stem.get(11..13), stem.get(13..15)) {
+            (Some(y), Some(mo), Some(d), Some(h), Some(mi), Some(s)) => format!("{}-{}-{} {}:{}:{}", y, mo, d, h, mi, s),
+            _ => stem.to_string(),
+        }
+    }
+
+    pub fn read(&self) -> Result<String> {
+        read_note(&self.path)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalHistory {
+    root: PathBuf,
+    interval: Duration,
+    max_bytes: u64,
+}
+
+impl LocalHistory {
+    pub fn new(notes_dir: &Path, interval_mins: u64, max_mb: u64) -> Self {
+        Self {
+            root: notes_dir.to_path_buf(),
+            interval: Duration::from_secs(interval_mins * 60),
+            max_bytes: max_mb * 1024 * 1024,
+        }
+    }
+
+    /// `.history/<note path>/`, one folder per note.
+    fn note_dir(&self, note: &Path) -> PathBuf {
+        self.root.join(DIR).join(relative_to(note, &self.root))
+    }
+
+    /// Snapshots of `note`, newest first.
+    pub fn list(&self, note: &Path) -> Vec<Snapshot> {
+        let Ok(entries) = std::fs::read_dir(self.note_dir(note)) else { return Vec::new() };
+        let mut out: Vec<Snapshot> = entries
+            .flatten()
+            .filter(|e| e.path().extension().is_some_and(|x| x == "md"))
+            .map(|e| Snapshot { path: e.path(), size: e.metadata().map_or(0, |m| m.len()) })
+            .collect();
+        out.sort_by(|a, b| b.path.cmp(&a.path));
+        out
+    }
+
+    /// Stores `content` as a new snapshot of `note` unless it matches the
+    /// newest one or, without `force`, that one is younger than the
+    /// interval. Returns whether a snapshot was written.
+    pub fn snapshot(&self, note: &Path, content: &str, force: bool) -> Result<bool> {
+        if let Some(newest) = self.list(note).first() {
+            let young = std::fs::metadata(&newest.path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|t| SystemTime::now().duration_since(t).unwrap_or_default() < self.interval);
+            if (young && !force) || newest.read().is_ok_and(|c| c == content) {
+                return Ok(false);
+            }
+        }
+        let store = self.root.join(DIR);
+        if !store.is_dir() {
+            std::fs::create_dir_all(&store).with_context(|| format!("Creating {}", store.display()))?;
+            // Keep snapshots out of the vault's git repo, if it has one.
+            std::fs::write(store.join(".gitignore"), "*\n")?;
+        }
+        let dir = self.note_dir(note);
+        std::fs::create_dir_all(&dir).with_context(|| format!("Creating {}", dir.display()))?;
+        let name = datefmt::strftime(datefmt::now(), "%Y%m%d-%H%M%S");
+        write_note(&dir.join(format!("{}.md", name)), content)?;
+        self.prune()?;
+        Ok(true)
+    }
+
+    /// Deletes the oldest snapshots across the vault until the store fits
+    /// in its cap.
+    fn prune(&self) -> Result<()> {
+        let mut files = Vec::new();
+        collect(&self.root.join(DIR), &mut files);
+        let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+        if total <= self.max_bytes {
+            return Ok(());
+        }
+        files.sort_by_key(|(modified, _, _)| *modified);
+        for (_, size, path) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+            std::fs::remove_file(&path).with_context(|| format!("Pruning {}", path.display()))?;
+            total -= size;
+            if let Some(dir) = path.parent() {
+                // Only succeeds once the folder is empty.
+                let _ = std::fs::remove_dir(dir);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn collect(dir: &Path, out: &mut Vec<(SystemTime, u64, PathBuf)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(meta) = entry.metadata() else { continue };
+        if meta.is_dir() {
+            collect(&path, out);
+        } else if path.extension().is_some_and(|x| x == "md") {
+            out.push((meta.modified().unwrap_or(SystemTime::UNIX_EPOCH), meta.len(), path));
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Vault statistics for the Stats view: per-day edit activity for the
+//! heatmap, from git history when there is some, otherwise file mtimes.
+//! Notes named `YYYY-MM-DD.md` count as daily notes for the streak. Also
+//! the per-note counts of the Note info popup.
+
+use crate::git::GitSection;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+use time::{Date, Duration, OffsetDateTime, UtcOffset};
+
+/// Days covered by the heatmap: a year of full weeks.
+pub const HEATMAP_DAYS: i64 = 53 * 7;
+
+#[derive(Debug, Clone)]
+pub struct Stats {
+    pub notes: usize,
+    pub today: Date,
+    /// Edits per day within the heatmap range.
+    pub activity: BTreeMap<Date, usize>,
+    /// Where `activity` came from, for the view's caption.
+    pub source: &'static str,
+    pub streak: Streak,
+}
+
+/// Runs of consecutive days that have a daily note.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Streak {
+    /// Ends today, or yesterday while today's note is still unwritten.
+    pub current: usize,
+    pub longest: usize,
+}
+
+impl Streak {
+    pub fn from_days(days: &BTreeSet<Date>, today: Date) -> Self {
+        let mut longest = 0;
+        let mut run = 0;
+        let mut prev: Option<Date> = None;
+        for &day in days {
+            run = if prev.is_some_and(|p| p.next_day() == Some(day)) { run + 1 } else { 1 };
+            longest = longest.max(run);
+            prev = Some(day);
+        }
+
+        let mut current = 0;
+        let mut day = if days.contains(&today) { Some(today) } else { today.previous_day() };
+        while let Some(d) = day.filter(|d| days.contains(d)) {
+            current += 1;
+            day = d.previous_day();
+        }
+        Self { current, longest }
+    }
+}
+
+impl Stats {
+    pub fn collect(git: &GitSection, files: &[PathBuf]) -> Self {
+        let offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+        let today = OffsetDateTime::now_utc().to_offset(offset).date();
+        let since = today - Duration::days(HEATMAP_DAYS);
+        let notes = files.iter().filter(|p| p.extension().is_some_and(|e| e == "md")).count();
+
+        let (activity, source) = match git_activity(git, since) {
+            Some(days) if !days.is_empty() => (days, "commits"),
+            _ => (mtime_activity(files, since, offset), "file modification times"),
+        };
+        let daily: BTreeSet<Date> = files
+            .iter()
+            .filter(|p| p.extension().is_some_and(|e| e == "md"))
+            .filter_map(|p| parse_day(&p.file_stem()?.to_string_lossy()))
+            .collect();
+        let streak = Streak::from_days(&daily, today);
+        Self { notes, today, activity, source, streak }
+    }
+
+    pub fn active_days(&self) -> usize {
+        self.activity.len()
+    }
+
+    pub fn count(&self, day: Date) -> usize {
+        self.activity.get(&day).copied().unwrap_or(0)
+    }
+
+    /// Heat level 0-4 of `day`, scaled to the busiest day.
+    pub fn level(&self, day: Date) -> usize {
+        let count = self.count(day);
+        let max = self.activity.values().copied().max().unwrap_or(0);
+        if count == 0 || max == 0 {
+            return 0;
+        }
+        (1 + (count - 1) * 4 / max).min(4)
+    }
+}
+
+/// Silent-reading speed the reading time is estimated with.
+const WORDS_PER_MINUTE: usize = 200;
+
+/// Keywords and letters listed in the Note info popup.
+const TOP: usize = 10;
+
+/// Common English words left out of the keywords.
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "also", "and", "are", "because", "been", "but", "can", "could", "does", "for", "from", "had", "has",
+    "have", "her", "his", "how", "into", "its", "just", "more", "not", "now", "only", "other", "our", "over", "should",
+    "some", "such", "than", "that", "the", "their", "them", "then", "there", "these", "they", "this", "those", "very",
+    "was", "were", "what", "when", "which", "while", "who", "will", "with", "would", "you", "your",
+];
+
+/// Counts for one note; the frontmatter is left out.
+#[derive(Debug, Clone, Default)]
+pub struct NoteStats {
+    pub words: usize,
+    pub chars: usize,
+    pub chars_no_spaces: usize,
+    pub lines: usize,
+    /// Most used words of three letters or more, stop words aside, with counts.
+    pub keywords: Vec<(String, usize)>,
+    /// Most used letters, case-folded.
+    pub letters: Vec<(char, usize)>,
+}
+
+impl NoteStats {
+    pub fn of(lines: &[String]) -> Self {
+        let body = &lines[crate::markdown::body_start(lines)..];
+        let mut stats = NoteStats { lines: body.len(), ..Self::default() };
+        let mut words: HashMap<String, usize> = HashMap::new();
+        let mut letters: HashMap<char, usize> = HashMap::new();
+        for line in body {
+            stats.chars += line.chars().count();
+            stats.chars_no_spaces += line.chars().filter(|c| !c.is_whitespace()).count();
+            for c in line.chars().filter(|c| c.is_alphabetic()) {
+                *letters.entry(c.to_lowercase().next().unwrap_or(c)).or_insert(0) += 1;
+            }
+            for word in line.split(|c: char| !c.is_alphanumeric() && c != '\'').map(|w| w.trim_matches('\'')) {
+                if !word.chars().any(char::is_alphanumeric) {
+                    continue;
+                }
+                stats.words += 1;
+                let word = word.to_lowercase();
+                if word.chars().count() >= 3 && !STOP_WORDS.contains(&word.as_str()) && !word.chars().all(|c| c.is_numeric()) {
+                    *words.entry(word).or_insert(0) += 1;
+                }
+            }
+        }
+        stats.keywords = top(words);
+        stats.letters = top(letters);
+        stats
+    }
+
+    /// Whole minutes at [`WORDS_PER_MINUTE`], at least one for any text.
+    pub fn reading_minutes(&self) -> usize {
+        self.words.div_ceil(WORDS_PER_MINUTE)
+    }
+}
+
+/// The [`TOP`] highest counts, ties in key order.
+fn top<K: Ord>(counts: HashMap<K, usize>) -> Vec<(K, usize)> {
+    let mut all: Vec<(K, usize)> = counts.into_iter().collect();
+    all.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    all.truncate(TOP);
+    all
+}
+
+fn git_activity(git: &GitSection, since: Date) -> Option<BTreeMap<Date, usize>> {
+    let output = git
+        .command()
+        .args(["log", "--format=%ad", "--date=short"])
+        .arg(format!("--since={}", since))
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let mut days = BTreeMap::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some(day) = parse_day(line.trim()) {
+            *days.entry(day).or_insert(0) += 1;
+        }
+    }
+    Some(days)
+}
+
+/// Parses `YYYY-MM-DD`.
+pub fn parse_day(s: &str) -> Option<Date> {
+    let mut parts = s.splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month: u8 = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+    Date::from_calendar_date(year, time::Month::try_from(month).ok()?, day).ok()
+}
+
+/// Each file counts once, on the day it was last modified.
+fn mtime_activity(files: &[PathBuf], since: Date, offset: UtcOffset) -> BTreeMap<Date, usize> {
+    let mut days = BTreeMap::new();
+    for path in files {
+        let Ok(modified) = std::fs::metadata(path).and_then(|m| m.modified()) else { continue };
+        let day = OffsetDateTime::from(modified).to_offset(offset).date();
+        if day > since {
+            *days.entry(day).or_insert(0) += 1;
+        }
+    }
+    days
+}
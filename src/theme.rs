@@ -0,0 +1,175 @@
+use crate::markdown::CalloutKind;
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    /// Focused borders, list highlight background.
+    pub accent: Color,
+    /// Text drawn on top of `accent`.
+    pub on_accent: Color,
+    pub dir_icon: Color,
+    /// Palette tuned for a light terminal background.
+    pub light: bool,
+}
+
+impl Theme {
+    pub const NAMES: [&'static str; 4] = ["default", "ocean", "amber", "rose"];
+
+    /// Looks up a theme by name; unknown names fall back to `default`.
+    pub fn named(name: &str, background: Background) -> Self {
+        if background.resolve() == Background::Light {
+            let (accent, dir_icon) = match name {
+                "ocean" => (Color::Blue, Color::Cyan),
+                "amber" => (Color::Indexed(130), Color::Indexed(94)),
+                "rose" => (Color::Magenta, Color::Indexed(125)),
+                _ => (Color::Green, Color::Blue),
+            };
+            return Self { accent, on_accent: Color::White, dir_icon, light: true };
+        }
+        match name {
+            "ocean" => Self { accent: Color::Cyan, on_accent: Color::Black, dir_icon: Color::LightBlue, light: false },
+            "amber" => Self { accent: Color::Yellow, on_accent: Color::Black, dir_icon: Color::LightYellow, light: false },
+            "rose" => Self { accent: Color::Magenta, on_accent: Color::White, dir_icon: Color::LightMagenta, light: false },
+            _ => Self { accent: Color::Green, on_accent: Color::Black, dir_icon: Color::Yellow, light: false },
+        }
+    }
+
+    /// Maps the bright/yellow colors used for key hints and markers to
+    /// darker ones that stay readable on a light background.
+    pub fn readable(&self, color: Color) -> Color {
+        if !self.light {
+            return color;
+        }
+        match color {
+            Color::LightRed => Color::Red,
+            Color::LightGreen => Color::Green,
+            Color::LightYellow | Color::Yellow => Color::Indexed(130),
+            Color::LightBlue => Color::Blue,
+            Color::LightMagenta => Color::Magenta,
+            Color::LightCyan => Color::Cyan,
+            other => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Background {
+    /// Guess from `COLORFGBG`, falling back to dark.
+    #[default]
+    Auto,
+    Dark,
+    Light,
+}
+
+impl Background {
+    pub fn resolve(self) -> Background {
+        match self {
+            Background::Auto => detect_background().unwrap_or(Background::Dark),
+            other => other,
+        }
+    }
+}
+
+/// Reads `COLORFGBG` (`fg;bg` or `fg;default;bg`, set by rxvt, Konsole,
+/// iTerm2 and others). Background colors 7 and 9-15 are the light ones.
+fn detect_background() -> Option<Background> {
+    let value = std::env::var("COLORFGBG").ok()?;
+    let bg: u8 = value.rsplit(';').next()?.parse().ok()?;
+    Some(if bg == 7 || (9..=15).contains(&bg) { Background::Light } else { Background::Dark })
+}
+
+/// Glyphs used in the sidebar tree; `emoji` renders double-width on some
+/// terminals, so `nerd` and `ascii` keep the columns aligned there.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IconSet {
+    #[default]
+    Emoji,
+    Nerd,
+    Ascii,
+}
+
+impl IconSet {
+    pub fn dir(self, expanded: bool) -> &'static str {
+        match (self, expanded) {
+            (IconSet::Emoji, false) => "📁 ",
+            (IconSet::Emoji, true) => "📂 ",
+            (IconSet::Nerd, false) => "\u{f07b} ",
+            (IconSet::Nerd, true) => "\u{f07c} ",
+            (IconSet::Ascii, false) => "+ ",
+            (IconSet::Ascii, true) => "- ",
+        }
+    }
+
+    pub fn file(self, image: bool) -> &'static str {
+        match (self, image) {
+            (IconSet::Emoji, false) => "📄 ",
+            (IconSet::Emoji, true) => "🖼️ ",
+            (IconSet::Nerd, false) => "\u{f15c} ",
+            (IconSet::Nerd, true) => "\u{f1c5} ",
+            (IconSet::Ascii, _) => "* ",
+        }
+    }
+
+    /// Notes with `encrypted: true` frontmatter.
+    pub fn lock(self) -> &'static str {
+        match self {
+            IconSet::Emoji => "🔒 ",
+            IconSet::Nerd => "\u{f023} ",
+            IconSet::Ascii => "# ",
+        }
+    }
+
+    /// Marks entries reached through a symlink, after their name.
+    pub fn link(self) -> &'static str {
+        match self {
+            IconSet::Emoji => " ↪",
+            IconSet::Nerd => " \u{f0c1}",
+            IconSet::Ascii => " ->",
+        }
+    }
+
+    /// Saved searches listed under the tree.
+    pub fn search(self) -> &'static str {
+        match self {
+            IconSet::Emoji => "🔍 ",
+            IconSet::Nerd => "\u{f002} ",
+            IconSet::Ascii => "? ",
+        }
+    }
+
+    /// Before the title of a callout in the previews.
+    pub fn callout(self, kind: CalloutKind) -> &'static str {
+        match (self, kind) {
+            (IconSet::Emoji, CalloutKind::Note) => "📝 ",
+            (IconSet::Emoji, CalloutKind::Tip) => "💡 ",
+            (IconSet::Emoji, CalloutKind::Success) => "✅ ",
+            (IconSet::Emoji, CalloutKind::Question) => "❓ ",
+            (IconSet::Emoji, CalloutKind::Warning) => "⚠️ ",
+            (IconSet::Emoji, CalloutKind::Danger) => "⛔ ",
+            (IconSet::Emoji, CalloutKind::Example) => "📋 ",
+            (IconSet::Emoji, CalloutKind::Quote) => "💬 ",
+            (IconSet::Nerd, CalloutKind::Note) => "\u{f040} ",
+            (IconSet::Nerd, CalloutKind::Tip) => "\u{f0eb} ",
+            (IconSet::Nerd, CalloutKind::Success) => "\u{f00c} ",
+            (IconSet::Nerd, CalloutKind::Question) => "\u{f128} ",
+            (IconSet::Nerd, CalloutKind::Warning) => "\u{f071} ",
+            (IconSet::Nerd, CalloutKind::Danger) => "\u{f00d} ",
+            (IconSet::Nerd, CalloutKind::Example) => "\u{f03a} ",
+            (IconSet::Nerd, CalloutKind::Quote) => "\u{f10d} ",
+            (IconSet::Ascii, CalloutKind::Warning | CalloutKind::Danger) => "! ",
+            (IconSet::Ascii, CalloutKind::Question) => "? ",
+            (IconSet::Ascii, _) => "* ",
+        }
+    }
+
+    /// Tree guides as `(vertical, branch, last branch)`.
+    pub fn guides(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            IconSet::Ascii => ("| ", "|- ", "`- "),
+            _ => ("│ ", "├─ ", "└─ "),
+        }
+    }
+}
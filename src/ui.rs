@@ -1,26 +1,64 @@
-use crate::app::{App, Focus};
+use crate::app::{clamp_to_char_boundary, App, Focus};
+use crate::theme::{IconSet, Theme};
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::prelude::*;
 use ratatui::widgets::*;
 use ratatui::text::{Line, Span};
 use ratatui::style::{Style, Modifier, Color};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Below this the screen only asks for a bigger terminal.
+const MIN_WIDTH: u16 = 24;
+const MIN_HEIGHT: u16 = 7;
+/// Narrower than this, the left column is only shown while it has focus,
+/// and then in place of the note.
+const NARROW_WIDTH: u16 = 70;
+/// Shorter than this, the outer frame goes and the footer loses its border.
+const SHORT_HEIGHT: u16 = 18;
 
 pub fn draw(frame: &mut Frame, app: &mut App) {
     let size = frame.size();
+    // Not drawn this frame unless a pane says otherwise.
+    app.changed_files_area = Rect::default();
 
-    let outer_block = Block::default()
-        .borders(Borders::ALL)
-        .border_type(BorderType::Rounded)
-        .title(
-            ratatui::widgets::block::Title::from("lazynotes")
-                .alignment(Alignment::Center)
-        )
-        .title_style(Style::default().add_modifier(Modifier::BOLD));
-    frame.render_widget(outer_block, size);
+    if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+        let message = Paragraph::new(format!("Terminal too small\n({}x{}, need {}x{})", size.width, size.height, MIN_WIDTH, MIN_HEIGHT))
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+        frame.render_widget(message, size);
+        return;
+    }
+    if let Some(crate::app::Modal::Present { slides, index }) = &app.modal {
+        draw_presentation(frame, size, app, slides[*index], (*index, slides.len()));
+        return;
+    }
+    let compact = size.height < SHORT_HEIGHT;
+    let history = matches!(app.modal, Some(crate::app::Modal::History { .. } | crate::app::Modal::LocalHistory { .. }));
+    let left_focused = matches!(app.focus, Focus::Sidebar | Focus::Commits | Focus::ChangedFiles);
+    let columns = if size.width >= NARROW_WIDTH || history {
+        [Constraint::Percentage(30), Constraint::Percentage(70)]
+    } else if left_focused {
+        [Constraint::Percentage(100), Constraint::Length(0)]
+    } else {
+        [Constraint::Length(0), Constraint::Percentage(100)]
+    };
+
+    if !compact {
+        let outer_block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title(
+                ratatui::widgets::block::Title::from(if app.read_only { "lazynotes [read-only]" } else { "lazynotes" })
+                    .alignment(Alignment::Center)
+            )
+            .title_style(Style::default().add_modifier(Modifier::BOLD));
+        frame.render_widget(outer_block, size);
+    }
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .margin(1)
-        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
+        .margin(if compact { 0 } else { 1 })
+        .constraints(columns)
         .split(size);
 
     let left_vertical = Layout::default()
@@ -30,78 +68,263 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
 
     let middle_vertical = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(3), Constraint::Min(1), Constraint::Length(3)])
+        .constraints([Constraint::Length(3), Constraint::Min(3), Constraint::Length(if compact { 1 } else { 3 })])
         .split(chunks[1]);
 
-    draw_sidebar(frame, left_vertical[0], app);
-    draw_changed_files(frame, left_vertical[1], app);
-    draw_commit_list(frame, left_vertical[2], app);
+    // The history browser takes over the left column so Content stays
+    // visible next to it.
+    match &app.modal {
+        Some(crate::app::Modal::History { revisions, selected, .. }) => {
+            let entries: Vec<(String, String)> =
+                revisions.iter().map(|c| (format!("{} {}", c.hash, c.summary), format!("{} • {}", c.author, app.commit_date(c)))).collect();
+            draw_history_list(frame, chunks[0], "History (Enter:Restore  Esc:Back)", &entries, *selected, app);
+        }
+        Some(crate::app::Modal::LocalHistory { snapshots, selected, .. }) => {
+            let entries: Vec<(String, String)> = snapshots.iter().map(|s| (s.label(), format!("{} bytes", s.size))).collect();
+            draw_history_list(frame, chunks[0], "Local history (Enter:Restore  Esc:Back)", &entries, *selected, app);
+        }
+        _ if chunks[0].width == 0 => {}
+        _ => {
+            draw_sidebar(frame, left_vertical[0], app);
+            draw_changed_files(frame, left_vertical[1], app);
+            draw_commit_list(frame, left_vertical[2], app);
+        }
+    }
+
+    if chunks[1].width > 0 {
+        draw_right_panel(frame, middle_vertical[0], middle_vertical[1], app);
+        draw_footer(frame, middle_vertical[2], app, compact);
+        if app.preview && matches!(app.focus, Focus::Sidebar) && app.modal.is_none() {
+            draw_preview(frame, middle_vertical[1], app);
+        }
+    }
 
-    draw_right_panel(frame, middle_vertical[0], middle_vertical[1], app);
-    draw_footer(frame, middle_vertical[2], app);
+    // Popups go last so no pane paints over them.
+    if let Some(modal) = &app.modal {
+        draw_modal(frame, modal, app);
+    }
+}
+
+/// One slide over the whole screen: headings bold in the accent color,
+/// the rest as written, with the position in the bottom border.
+fn draw_presentation(frame: &mut Frame, area: Rect, app: &App, (start, end): (usize, usize), (index, total): (usize, usize)) {
+    let rows = &app.lines[start..end];
+    let first = rows.iter().position(|l| !l.trim().is_empty()).unwrap_or(0);
+    let last = rows.iter().rposition(|l| !l.trim().is_empty()).map_or(0, |i| i + 1);
+    let heading = Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD);
+    let headings = crate::markdown::headings(rows);
+    let mut rendered = render_rows(rows, &app.theme, app.icons, app.math_unicode);
+    let mut text: Vec<Line> = Vec::new();
+    for (row, line) in rows.iter().enumerate().take(last).skip(first) {
+        match headings.iter().find(|h| h.row == row) {
+            Some(h) => {
+                text.push(Line::styled(h.title.clone(), heading));
+                text.push(Line::default());
+            }
+            None => text.push(rendered[row].take().unwrap_or_else(|| Line::raw(line.as_str()))),
+        }
+    }
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(ratatui::widgets::block::Title::from(app.title.as_str()).alignment(Alignment::Center))
+        .title(
+            ratatui::widgets::block::Title::from(format!(" {}/{} ", index + 1, total))
+                .position(ratatui::widgets::block::Position::Bottom)
+                .alignment(Alignment::Right),
+        )
+        .title(
+            ratatui::widgets::block::Title::from(" ←/→:Slide  Esc:Exit ")
+                .position(ratatui::widgets::block::Position::Bottom)
+                .alignment(Alignment::Left),
+        )
+        .padding(ratatui::widgets::Padding::new(area.width / 10, area.width / 10, area.height / 8, 0));
+    frame.render_widget(Paragraph::new(Text::from(text)).block(block).wrap(Wrap { trim: false }), area);
 }
 
 fn draw_sidebar(frame: &mut Frame, area: Rect, app: &mut App) {
     use ratatui::widgets::{List, ListItem, Block, Borders};
 
+    let dirs: Vec<PathBuf> = app.sidebar_items.iter().filter(|it| it.is_dir).map(|it| it.path.clone()).collect();
+    let counts: HashMap<PathBuf, (usize, usize)> = dirs.into_iter().filter_map(|d| app.folder_count(&d).map(|count| (d, count))).collect();
+
     let items: Vec<ListItem> = app
         .sidebar_items
         .iter()
         .map(|it| {
             let mut spans: Vec<Span> = Vec::new();
+            let (vertical, branch, last_branch) = app.icons.guides();
             
             if it.depth == 0 {
             } else {
-                for (_level, anc_last) in it.last_ancestors.iter().enumerate() {
+                for anc_last in it.last_ancestors.iter() {
                     if *anc_last {
                         spans.push(Span::raw("  "));
                     } else {
-                        spans.push(Span::raw("│ "));
+                        spans.push(Span::raw(vertical));
                     }
                 }
                 
-                let branch = if it.last_in_parent { "└─ " } else { "├─ " };
+                let branch = if it.last_in_parent { last_branch } else { branch };
                 spans.push(Span::raw(branch));
             }
             if it.is_dir {
-                let icon = if it.expanded { "📂 " } else { "📁 " };
-                spans.push(Span::styled(icon, Style::default().fg(Color::Yellow)));
+                let icon = app.icons.dir(it.expanded);
+                spans.push(Span::styled(icon, Style::default().fg(app.theme.dir_icon)));
                 spans.push(Span::raw(format!("{}/", it.name)));
+                if it.link {
+                    spans.push(Span::styled(app.icons.link(), Style::default().add_modifier(Modifier::DIM)));
+                }
+                if let Some(&(notes, words)) = counts.get(&it.path) {
+                    let words = if words >= 1000 { format!("{:.1}k", words as f64 / 1000.0) } else { words.to_string() };
+                    spans.push(Span::styled(format!("  {} notes · {} words", notes, words), Style::default().add_modifier(Modifier::DIM)));
+                }
             } else {
-                let icon = match it.path.extension().and_then(|e| e.to_str()).map(|s| s.to_lowercase()) {
-                    Some(ext) if ["png", "jpg", "jpeg", "gif", "svg", "webp", "bmp"].contains(&ext.as_str()) => "🖼️ ",
-                    _ => "📄 ",
-                };
+                let image = it
+                    .path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|ext| crate::attachments::IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+                let icon = if app.encrypted.contains(&it.path) { app.icons.lock() } else { app.icons.file(image) };
                 spans.push(Span::raw(icon));
-                spans.push(Span::raw(it.name.clone()));
+                if app.marked.contains(&it.path) {
+                    spans.push(Span::styled(format!("✓ {}", it.name), Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD)));
+                } else {
+                    spans.push(Span::raw(it.name.clone()));
+                }
+                if it.link {
+                    spans.push(Span::styled(app.icons.link(), Style::default().add_modifier(Modifier::DIM)));
+                }
             }
 
             ListItem::new(Line::from(spans))
         })
+        .chain(app.searches.saved.iter().map(|(name, query)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(app.icons.search(), Style::default().fg(app.theme.accent)),
+                Span::raw(name.clone()),
+                Span::styled(format!("  {}", query), Style::default().add_modifier(Modifier::DIM)),
+            ]))
+        }))
         .collect();
 
     let block = Block::default()
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
-        .title("[1]Files")
+        .title(match app.marked.len() {
+            0 => "[1]Files".to_string(),
+            n => format!("[1]Files {} marked (d/m/t/a, Esc:Clear)", n),
+        })
         .title_style(Style::default().add_modifier(Modifier::BOLD))
-        .border_style(if matches!(app.focus, Focus::Sidebar) { Style::default().fg(Color::Green).add_modifier(Modifier::BOLD) } else { Style::default() });
+        .border_style(if matches!(app.focus, Focus::Sidebar) { Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD) } else { Style::default() });
 
     let list = List::new(items)
         .block(block)
-        .highlight_style(Style::default().fg(Color::Black).bg(Color::Green).add_modifier(Modifier::BOLD));
+        .highlight_style(Style::default().fg(app.theme.on_accent).bg(app.theme.accent).add_modifier(Modifier::BOLD));
 
     frame.render_stateful_widget(list, area, &mut app.sidebar_state);
-
-    
-    if let Some(modal) = &app.modal {
-        draw_modal(frame, modal, app);
-    }
 }
 
-fn draw_modal(frame: &mut Frame, modal: &crate::app::Modal, _app: &App) {
+fn draw_modal(frame: &mut Frame, modal: &crate::app::Modal, app: &App) {
     use ratatui::widgets::{Block, Borders, Paragraph};
-    
+
+    // The find prompt lives in the footer so the matches stay visible.
+    if matches!(modal, crate::app::Modal::Find { .. } | crate::app::Modal::GotoLine { .. } | crate::app::Modal::MacroRegister { .. } | crate::app::Modal::History { .. } | crate::app::Modal::LocalHistory { .. }) {
+        return;
+    }
+    if let crate::app::Modal::VaultSearch { query, hits, selected, .. } = modal {
+        draw_vault_search(frame, query, hits, *selected, app);
+        return;
+    }
+    if let crate::app::Modal::Outline { selected } = modal {
+        draw_outline(frame, *selected, app);
+        return;
+    }
+    if let crate::app::Modal::Stats(stats) = modal {
+        draw_stats(frame, stats, app);
+        return;
+    }
+    if let crate::app::Modal::NoteInfo(stats) = modal {
+        draw_note_info(frame, stats, app);
+        return;
+    }
+    if matches!(modal, crate::app::Modal::Errors) {
+        draw_errors(frame, app);
+        return;
+    }
+    if let crate::app::Modal::Log { scroll } = modal {
+        draw_log(frame, *scroll, app);
+        return;
+    }
+    if let crate::app::Modal::Menu { menu } = modal {
+        draw_menu(frame, menu, app);
+        return;
+    }
+    if let crate::app::Modal::Marks { setting, selected } = modal {
+        draw_marks(frame, *setting, *selected, app);
+        return;
+    }
+    if let crate::app::Modal::Yanks { selected } = modal {
+        draw_yanks(frame, *selected, app);
+        return;
+    }
+    if let crate::app::Modal::Merge { regions, selected, .. } = modal {
+        draw_merge(frame, regions, *selected, app);
+        return;
+    }
+    if let crate::app::Modal::Diff { base, rows, scroll } = modal {
+        let (added, removed) = crate::diff::stats(rows);
+        let (against, other) = match base {
+            crate::app::DiffBase::Saved => ("saved file", "last commit"),
+            crate::app::DiffBase::Committed => ("last commit", "saved file"),
+        };
+        let title = format!("Changes vs {} (+{} -{})  Tab:vs {}  Esc:Close", against, added, removed, other);
+        draw_diff(frame, &title, rows, *scroll, app);
+        return;
+    }
+    if let crate::app::Modal::CommitDiff { hash, file, rows, scroll } = modal {
+        let (added, removed) = crate::diff::stats(rows);
+        let name = match &file.from {
+            Some(from) => format!("{} → {}", from, file.path),
+            None => file.path.clone(),
+        };
+        let title = format!("{} in {} (+{} -{})  Esc:Close", name, hash, added, removed);
+        draw_diff(frame, &title, rows, *scroll, app);
+        return;
+    }
+    if let crate::app::Modal::LinkReport { issues, selected } = modal {
+        draw_link_report(frame, issues, *selected, app);
+        return;
+    }
+    if let crate::app::Modal::Attachments { items, selected, vault, renaming } = modal {
+        draw_attachments(frame, items, *selected, *vault, renaming.as_deref(), app);
+        return;
+    }
+    if let crate::app::Modal::Deleted { selected } = modal {
+        draw_deleted(frame, *selected, app);
+        return;
+    }
+    if let crate::app::Modal::Stashes { stashes, selected } = modal {
+        draw_stashes(frame, stashes, *selected, app);
+        return;
+    }
+    if let crate::app::Modal::Remotes { remotes, selected, form } = modal {
+        draw_remotes(frame, remotes, *selected, form.as_ref(), app);
+        return;
+    }
+    if let crate::app::Modal::Replace { pattern, with, editing_with, matches, selected, previewing, commit } = modal {
+        draw_replace(frame, pattern, with, *editing_with, matches, *selected, *previewing, *commit, app);
+        return;
+    }
+    if let crate::app::Modal::InsertDate { selected } = modal {
+        draw_insert_date(frame, *selected, app);
+        return;
+    }
+    if let crate::app::Modal::NotePicker { query, matches, selected, .. } = modal {
+        draw_note_picker(frame, query, matches, *selected, app);
+        return;
+    }
+
     let area = frame.size();
     let w = (area.width as f32 * 0.5) as u16;
     let h = 7u16;
@@ -112,14 +335,123 @@ fn draw_modal(frame: &mut Frame, modal: &crate::app::Modal, _app: &App) {
     let title = match modal {
         crate::app::Modal::ConfirmDelete { .. } => "Confirm Delete",
         crate::app::Modal::InputName { .. } => "New Note Name",
+        crate::app::Modal::NameSearch { .. } => "Save Search",
+        crate::app::Modal::ExtractNote { .. } => "Extract to New Note",
+        crate::app::Modal::InsertLink { .. } => "Insert Link (Tab switches field)",
+        crate::app::Modal::CommitMessage { amend: false, .. } => "Commit Message",
+        crate::app::Modal::CommitMessage { amend: true, .. } => "Amend Last Commit",
+        crate::app::Modal::CommitForm { .. } => "Commit (Tab switches field)",
+        crate::app::Modal::Passphrase { .. } => "Encrypted Note",
+        crate::app::Modal::ImportFile { .. } => "Import File (Tab completes)",
+        crate::app::Modal::Batch { action, .. } => match action {
+            crate::app::BatchAction::Delete => "Delete Marked",
+            crate::app::BatchAction::Move => "Move Marked",
+            crate::app::BatchAction::Tag => "Tag Marked",
+            crate::app::BatchAction::Archive => "Archive Marked",
+        },
+        crate::app::Modal::ConfirmQuit => "Unsaved Changes",
+        crate::app::Modal::Find { .. } | crate::app::Modal::VaultSearch { .. } | crate::app::Modal::NotePicker { .. } | crate::app::Modal::Outline { .. } | crate::app::Modal::Marks { .. } | crate::app::Modal::MacroRegister { .. } | crate::app::Modal::Menu { .. } | crate::app::Modal::Log { .. } | crate::app::Modal::Errors | crate::app::Modal::History { .. } | crate::app::Modal::LocalHistory { .. } | crate::app::Modal::Diff { .. } | crate::app::Modal::CommitDiff { .. } | crate::app::Modal::Merge { .. } | crate::app::Modal::Stats(_) | crate::app::Modal::Yanks { .. } | crate::app::Modal::InsertDate { .. } | crate::app::Modal::GotoLine { .. } | crate::app::Modal::Replace { .. } | crate::app::Modal::LinkReport { .. } | crate::app::Modal::Remotes { .. } | crate::app::Modal::Stashes { .. } | crate::app::Modal::Attachments { .. } | crate::app::Modal::Present { .. } | crate::app::Modal::Deleted { .. } | crate::app::Modal::NoteInfo(_) => "Search",
     };
 
     let block = Block::default().borders(Borders::ALL).title(title).border_type(ratatui::widgets::BorderType::Rounded);
+    frame.render_widget(Clear, rect);
     frame.render_widget(block, rect);
 
     let text = match modal {
         crate::app::Modal::ConfirmDelete { path } => vec![Line::from(Span::raw(format!("Delete {}? (y/n)", path.file_name().and_then(|s| s.to_str()).unwrap_or(""))))],
     crate::app::Modal::InputName { current, .. } => vec![Line::from(Span::raw(format!("Name: {}", current)))],
+        crate::app::Modal::NameSearch { query, name } => vec![
+            Line::from(Span::raw(format!("Name: {}", name))),
+            Line::styled(format!("Shows up in the sidebar and searches for \"{}\"", query), Style::default().add_modifier(Modifier::DIM)),
+        ],
+        crate::app::Modal::ConfirmQuit => vec![Line::from(Span::raw("Save before quitting? (y/n, Esc cancels)"))],
+        crate::app::Modal::ExtractNote { name, from, to, open } => vec![
+            Line::from(Span::raw(format!("Name: {}", name))),
+            Line::styled(
+                format!(
+                    "Moves lines {}-{} and leaves a [[link]] behind{}",
+                    from.0 + 1,
+                    to.0 + usize::from(to.1 > 0),
+                    if *open { ", then opens it" } else { "" }
+                ),
+                Style::default().add_modifier(Modifier::DIM),
+            ),
+        ],
+        crate::app::Modal::CommitMessage { message, amend } => vec![
+            // Bodies of amended commits keep their newlines; show them inline.
+            Line::from(Span::raw(format!("Message: {}", message.replace('\n', " ⏎ ")))),
+            Line::styled(
+                if *amend { "Rewrites the last commit with the staged changes" } else { "Stages all changes in the vault" },
+                Style::default().add_modifier(Modifier::DIM),
+            ),
+        ],
+        crate::app::Modal::Batch { action, input, confirming } => {
+            let n = app.marked.len();
+            let files = format!("{} marked file{}", n, if n == 1 { "" } else { "s" });
+            let dim = Style::default().add_modifier(Modifier::DIM);
+            match (action, confirming) {
+                (crate::app::BatchAction::Move, false) => vec![
+                    Line::raw(format!("Folder: {}", input)),
+                    Line::styled("Relative to the notes folder; created when missing", dim),
+                ],
+                (crate::app::BatchAction::Tag, false) => vec![
+                    Line::raw(format!("Tag: {}", input)),
+                    Line::styled("Added to the frontmatter tags of the marked notes", dim),
+                ],
+                (crate::app::BatchAction::Delete, _) => vec![Line::raw(format!("Delete {}? (y/n)", files))],
+                (crate::app::BatchAction::Move, _) => vec![Line::raw(format!("Move {} to {}/? (y/n)", files, input.trim_matches('/')))],
+                (crate::app::BatchAction::Tag, _) => vec![Line::raw(format!("Tag {} with #{}? (y/n)", files, input.trim_start_matches('#')))],
+                (crate::app::BatchAction::Archive, _) => vec![Line::raw(format!("Move {} to archive/? (y/n)", files))],
+            }
+        }
+        crate::app::Modal::ImportFile { input, candidates, to_assets } => {
+            let dim = Style::default().add_modifier(Modifier::DIM);
+            let names: Vec<&str> = candidates.iter().map(|c| c.trim_end_matches('/').rsplit('/').next().unwrap_or(c)).collect();
+            vec![
+                Line::from(Span::raw(format!("Path: {}", input))),
+                Line::styled(
+                    if *to_assets { "Copies into the note's assets/ and links it (Ctrl+T: note's folder)" } else { "Copies next to the note and links it (Ctrl+T: assets/)" },
+                    dim,
+                ),
+                Line::styled(names.join("  "), dim),
+            ]
+        }
+        crate::app::Modal::Passphrase { path, input, sealing, .. } => vec![
+            Line::from(Span::raw(format!("Key: {}", "•".repeat(input.chars().count())))),
+            Line::styled(
+                format!(
+                    "{} {}; kept in memory until you quit",
+                    if *sealing { "Encrypts" } else { "Decrypts" },
+                    path.file_name().and_then(|s| s.to_str()).unwrap_or("")
+                ),
+                Style::default().add_modifier(Modifier::DIM),
+            ),
+        ],
+        crate::app::Modal::CommitForm { fields, field } => {
+            let label = |name: &'static str, active: bool| {
+                let style = if active { Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD) } else { Style::default() };
+                Span::styled(name, style)
+            };
+            let dim = Style::default().add_modifier(Modifier::DIM);
+            let template = app.commit_template.as_deref().unwrap_or_default();
+            vec![
+                Line::from(vec![label("Type:    ", *field == 0), Span::raw(fields[0].as_str()), Span::styled("  ↑↓ cycles", dim)]),
+                Line::from(vec![label("Scope:   ", *field == 1), Span::raw(fields[1].as_str())]),
+                Line::from(vec![label("Summary: ", *field == 2), Span::raw(fields[2].as_str())]),
+                Line::styled(crate::app::commit_message(template, fields), dim),
+            ]
+        }
+        crate::app::Modal::InsertLink { text, url, editing_url } => {
+            let label = |name: &'static str, active: bool| {
+                let style = if active { Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD) } else { Style::default() };
+                Span::styled(name, style)
+            };
+            vec![
+                Line::from(vec![label("Text: ", !editing_url), Span::raw(text.as_str())]),
+                Line::from(vec![label("URL:  ", *editing_url), Span::raw(url.as_str())]),
+            ]
+        }
+        crate::app::Modal::Find { .. } | crate::app::Modal::VaultSearch { .. } | crate::app::Modal::NotePicker { .. } | crate::app::Modal::Outline { .. } | crate::app::Modal::Marks { .. } | crate::app::Modal::MacroRegister { .. } | crate::app::Modal::Menu { .. } | crate::app::Modal::Log { .. } | crate::app::Modal::Errors | crate::app::Modal::History { .. } | crate::app::Modal::LocalHistory { .. } | crate::app::Modal::Diff { .. } | crate::app::Modal::CommitDiff { .. } | crate::app::Modal::Merge { .. } | crate::app::Modal::Stats(_) | crate::app::Modal::Yanks { .. } | crate::app::Modal::InsertDate { .. } | crate::app::Modal::GotoLine { .. } | crate::app::Modal::Replace { .. } | crate::app::Modal::LinkReport { .. } | crate::app::Modal::Remotes { .. } | crate::app::Modal::Stashes { .. } | crate::app::Modal::Attachments { .. } | crate::app::Modal::Present { .. } | crate::app::Modal::Deleted { .. } | crate::app::Modal::NoteInfo(_) => Vec::new(),
     };
     let para = Paragraph::new(Text::from(text)).alignment(Alignment::Left);
     let inner = Rect::new(rect.x + 1, rect.y + 1, rect.width.saturating_sub(2), rect.height.saturating_sub(2));
@@ -128,20 +460,899 @@ fn draw_modal(frame: &mut Frame, modal: &crate::app::Modal, _app: &App) {
 
 
 
+/// Floats over the top of Content, so the open note stays put underneath.
+fn draw_preview(frame: &mut Frame, area: Rect, app: &mut App) {
+    let (theme, icons, math_unicode) = (app.theme, app.icons, app.math_unicode);
+    let accent = theme.accent;
+    let Some((path, lines)) = app.sidebar_preview() else { return };
+    let rect = Rect::new(area.x + 2, area.y + 1, area.width.saturating_sub(4), (lines.len() as u16 + 2).min(area.height.saturating_sub(1)));
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let text: Vec<Line> = lines
+        .iter()
+        .zip(render_rows(lines, &theme, icons, math_unicode))
+        .map(|(l, callout)| callout.unwrap_or_else(|| Line::raw(l.as_str())))
+        .collect();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!("Preview: {} (p:Hide)", name))
+        .border_style(Style::default().fg(accent));
+    frame.render_widget(Clear, rect);
+    frame.render_widget(Paragraph::new(Text::from(text)).block(block), rect);
+}
+
+/// The rows of a read-only view that aren't drawn as written: highlighted
+/// code blocks, callouts and math.
+fn render_rows<'a>(rows: &'a [String], theme: &Theme, icons: IconSet, math_unicode: bool) -> Vec<Option<Line<'a>>> {
+    let in_code: Vec<bool> = {
+        let mut out = vec![false; rows.len()];
+        for block in crate::markdown::code_blocks(rows) {
+            out[block.start..(block.end + 1).min(rows.len())].fill(true);
+        }
+        out
+    };
+    let mut badges: Vec<Option<Line<'a>>> = vec![None; rows.len()];
+    for block in crate::markdown::code_blocks(rows).into_iter().filter(|b| b.lang == crate::diagram::MERMAID) {
+        let badge = Style::default().fg(theme.on_accent).bg(theme.accent).add_modifier(Modifier::BOLD);
+        badges[block.start] = Some(Line::from(vec![
+            Span::styled(" mermaid diagram ", badge),
+            Span::styled(" Ctrl+G t m renders it", Style::default().add_modifier(Modifier::DIM)),
+        ]));
+    }
+    crate::highlight::code_blocks_highlighted(rows, theme.light)
+        .into_iter()
+        .zip(badges)
+        .map(|(code, badge)| badge.or(code))
+        .zip(render_callouts(rows, theme, icons))
+        .zip(render_math(rows, theme, math_unicode))
+        .zip(in_code)
+        .map(|(((code, callout), math), in_code)| code.or(callout).or(if in_code { None } else { math }))
+        .collect()
+}
+
+/// `$...$` spans and `$$` blocks in italics and their own color, as
+/// Unicode when `unicode` is set; `None` for rows without math.
+fn render_math<'a>(rows: &'a [String], theme: &Theme, unicode: bool) -> Vec<Option<Line<'a>>> {
+    let style = Style::default().fg(theme.readable(Color::LightCyan)).add_modifier(Modifier::ITALIC);
+    let show = |tex: &'a str| -> Span<'a> {
+        if unicode { Span::styled(crate::math::to_unicode(tex), style) } else { Span::styled(tex, style) }
+    };
+    rows.iter()
+        .zip(crate::math::block_rows(rows))
+        .map(|(line, in_block)| {
+            if in_block {
+                let tex = line.trim();
+                let tex = if unicode { tex.trim_matches('$').trim() } else { tex };
+                return Some(Line::from(show(tex)));
+            }
+            let spans = crate::math::inline_spans(line);
+            if spans.is_empty() {
+                return None;
+            }
+            let mut out = Vec::new();
+            let mut at = 0;
+            for (start, end) in spans {
+                out.push(Span::raw(&line[at..start]));
+                out.push(show(if unicode { &line[start + 1..end - 1] } else { &line[start..end] }));
+                at = end;
+            }
+            out.push(Span::raw(&line[at..]));
+            Some(Line::from(out))
+        })
+        .collect()
+}
+
+/// Obsidian-style `> [!type]` callouts in `rows` drawn with a bar in the
+/// type's color and an icon before the title, by row; `None` for rows
+/// outside any callout.
+fn render_callouts<'a>(rows: &'a [String], theme: &Theme, icons: IconSet) -> Vec<Option<Line<'a>>> {
+    use crate::markdown::CalloutKind;
+    let mut out = Vec::with_capacity(rows.len());
+    let mut open: Option<Style> = None;
+    for line in rows {
+        if let Some((kind, title)) = crate::markdown::callout(line) {
+            let color = match kind {
+                CalloutKind::Note => Color::LightBlue,
+                CalloutKind::Tip => Color::LightCyan,
+                CalloutKind::Success => Color::LightGreen,
+                CalloutKind::Question | CalloutKind::Warning => Color::LightYellow,
+                CalloutKind::Danger => Color::LightRed,
+                CalloutKind::Example => Color::LightMagenta,
+                CalloutKind::Quote => Color::Gray,
+            };
+            let style = Style::default().fg(theme.readable(color));
+            out.push(Some(Line::from(vec![
+                Span::styled("┃ ", style),
+                Span::styled(format!("{}{}", icons.callout(kind), title), style.add_modifier(Modifier::BOLD)),
+            ])));
+            open = Some(style);
+            continue;
+        }
+        match (open, line.trim_start().strip_prefix('>')) {
+            (Some(style), Some(body)) => {
+                let body = body.strip_prefix(' ').unwrap_or(body);
+                out.push(Some(Line::from(vec![Span::styled("┃ ", style), Span::raw(body)])));
+            }
+            _ => {
+                open = None;
+                out.push(None);
+            }
+        }
+    }
+    out
+}
+
+fn centered_rect(area: Rect, width_pct: u16, height_pct: u16) -> Rect {
+    let w = area.width * width_pct / 100;
+    let h = area.height * height_pct / 100;
+    Rect::new(
+        area.x + (area.width.saturating_sub(w)) / 2,
+        area.y + (area.height.saturating_sub(h)) / 2,
+        w,
+        h,
+    )
+}
+
+fn draw_vault_search(frame: &mut Frame, query: &str, hits: &[crate::search::SearchHit], selected: usize, app: &App) {
+    let rect = centered_rect(frame.size(), 70, 60);
+    frame.render_widget(Clear, rect);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title("Search vault")
+        .border_style(Style::default().fg(app.theme.accent));
+    let inner = block.inner(rect);
+    frame.render_widget(block, rect);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(inner);
+    let options = app.searches.options;
+    let count = if crate::search::Matcher::new(query, options).is_err() {
+        "invalid regex".to_string()
+    } else {
+        format!("{} hits", hits.len())
+    };
+    let prompt = Line::from(vec![
+        Span::styled("> ", Style::default().fg(app.theme.accent)),
+        Span::styled(options.indicator(), Style::default().fg(app.theme.accent)),
+        Span::raw(query),
+        Span::styled(format!("  {}   Up:History  Ctrl+S:Save  Alt+R/C/W:Regex/Case/Word", count), Style::default().add_modifier(Modifier::DIM)),
+    ]);
+    frame.render_widget(Paragraph::new(prompt), rows[0]);
+
+    let items: Vec<ListItem> = hits
+        .iter()
+        .map(|h| {
+            let rel = crate::search::relative_to(&h.path, &app.notes_dir);
+            let line = h.line.trim_start();
+            let offset = h.line.len() - line.len();
+            let (start, end) = (h.start.saturating_sub(offset), h.end.saturating_sub(offset));
+            let mut spans = vec![Span::styled(
+                format!("{}:{}  ", rel.display(), h.row + 1),
+                Style::default().fg(app.theme.dir_icon),
+            )];
+            spans.extend(styled_line(line, &[(start, end, Style::default().fg(Color::Black).bg(Color::Yellow))]).spans);
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let mut state = ListState::default();
+    if !hits.is_empty() {
+        state.select(Some(selected));
+    }
+    let list = List::new(items).highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, rows[1], &mut state);
+}
+
+fn draw_note_picker(frame: &mut Frame, query: &str, matches: &[std::path::PathBuf], selected: usize, app: &App) {
+    let rect = centered_rect(frame.size(), 50, 50);
+    frame.render_widget(Clear, rect);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title("Pick note")
+        .border_style(Style::default().fg(app.theme.accent));
+    let inner = block.inner(rect);
+    frame.render_widget(block, rect);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(inner);
+    let prompt = Line::from(vec![Span::styled("> ", Style::default().fg(app.theme.accent)), Span::raw(query)]);
+    frame.render_widget(Paragraph::new(prompt), rows[0]);
+
+    let items: Vec<ListItem> = matches
+        .iter()
+        .map(|p| ListItem::new(crate::search::relative_to(p, &app.notes_dir).display().to_string()))
+        .collect();
+    let mut state = ListState::default();
+    if !matches.is_empty() {
+        state.select(Some(selected));
+    }
+    let list = List::new(items).highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, rows[1], &mut state);
+}
+
+fn draw_outline(frame: &mut Frame, selected: usize, app: &App) {
+    let headings = crate::markdown::headings(&app.lines);
+    let rect = centered_rect(frame.size(), 50, 60);
+    frame.render_widget(Clear, rect);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title("Outline")
+        .border_style(Style::default().fg(app.theme.accent));
+
+    let items: Vec<ListItem> = headings
+        .iter()
+        .map(|h| {
+            let indent = "  ".repeat(h.level - 1);
+            let style = if h.level == 1 { Style::default().add_modifier(Modifier::BOLD) } else { Style::default() };
+            ListItem::new(Line::styled(format!("{}{}", indent, h.title), style))
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, rect, &mut state);
+}
+
+fn draw_note_info(frame: &mut Frame, stats: &crate::stats::NoteStats, app: &App) {
+    let rect = centered_rect(frame.size(), 60, 60);
+    frame.render_widget(Clear, rect);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!("{} (Esc:Close)", if app.title.is_empty() { "Note info" } else { app.title.as_str() }))
+        .border_style(Style::default().fg(app.theme.accent));
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let dim = Style::default().add_modifier(Modifier::DIM);
+    let minutes = stats.reading_minutes();
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(format!("{}", stats.words), bold),
+            Span::raw(" words   "),
+            Span::styled(format!("{}", stats.chars), bold),
+            Span::raw(format!(" characters ({} without spaces)   ", stats.chars_no_spaces)),
+            Span::styled(format!("{}", stats.lines), bold),
+            Span::raw(" lines"),
+        ]),
+        Line::from(vec![
+            Span::raw("Reading time: "),
+            Span::styled(format!("about {} minute{}", minutes, if minutes == 1 { "" } else { "s" }), Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD)),
+        ]),
+        Line::default(),
+        Line::styled("Top keywords", bold),
+    ];
+    if stats.keywords.is_empty() {
+        lines.push(Line::styled("  none", dim));
+    }
+    let widest = stats.keywords.iter().map(|(w, _)| w.chars().count()).max().unwrap_or(0);
+    for (word, count) in &stats.keywords {
+        lines.push(Line::from(vec![Span::raw(format!("  {:<width$}  ", word, width = widest)), Span::styled(format!("{}", count), dim)]));
+    }
+    lines.push(Line::default());
+    lines.push(Line::styled("Letters", bold));
+    lines.push(Line::raw(format!(
+        "  {}",
+        stats.letters.iter().map(|(c, n)| format!("{} {}", c, n)).collect::<Vec<_>>().join("   ")
+    )));
+    frame.render_widget(Paragraph::new(Text::from(lines)).block(block).wrap(Wrap { trim: false }), rect);
+}
+
+fn draw_stats(frame: &mut Frame, stats: &crate::stats::Stats, app: &App) {
+    use time::Duration;
+
+    let rect = centered_rect(frame.size(), 90, 50);
+    frame.render_widget(Clear, rect);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title("Stats (Esc:Close)")
+        .border_style(Style::default().fg(app.theme.accent));
+    let inner = block.inner(rect);
+    frame.render_widget(block, rect);
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(format!("{}", stats.notes), Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(" notes   "),
+            Span::styled(format!("{}", stats.active_days()), Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(" active days in the last year"),
+        ]),
+        Line::from(vec![
+            Span::raw("Daily notes: "),
+            Span::styled(plural_days(stats.streak.current), Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD)),
+            Span::raw(" current streak, "),
+            Span::styled(plural_days(stats.streak.longest), Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(" longest"),
+        ]),
+        Line::styled(format!("Activity from {}", stats.source), Style::default().add_modifier(Modifier::DIM)),
+        Line::default(),
+    ];
+
+    // Columns are weeks ending with the current one, rows Sunday..Saturday;
+    // as many weeks as fit, newest on the right.
+    const SHADES: [Color; 5] = [Color::DarkGray, Color::Indexed(22), Color::Indexed(28), Color::Indexed(34), Color::Indexed(40)];
+    let weeks = ((inner.width.saturating_sub(4) / 2) as i64).clamp(1, crate::stats::HEATMAP_DAYS / 7);
+    let week_start = stats.today - Duration::days(stats.today.weekday().number_days_from_sunday() as i64);
+    let first = week_start - Duration::weeks(weeks - 1);
+    for row in 0..7i64 {
+        let label = match row {
+            1 => "Mon ",
+            3 => "Wed ",
+            5 => "Fri ",
+            _ => "    ",
+        };
+        let mut spans = vec![Span::styled(label, Style::default().add_modifier(Modifier::DIM))];
+        for week in 0..weeks {
+            let day = first + Duration::days(week * 7 + row);
+            if day > stats.today {
+                spans.push(Span::raw("  "));
+                continue;
+            }
+            let color = SHADES[stats.level(day)];
+            spans.push(Span::styled("■ ", Style::default().fg(color)));
+        }
+        lines.push(Line::from(spans));
+    }
+    let mut legend = vec![Span::styled("    Less ", Style::default().add_modifier(Modifier::DIM))];
+    legend.extend(SHADES.iter().map(|&c| Span::styled("■ ", Style::default().fg(c))));
+    legend.push(Span::styled("More", Style::default().add_modifier(Modifier::DIM)));
+    lines.push(Line::default());
+    lines.push(Line::from(legend));
+    frame.render_widget(Paragraph::new(Text::from(lines)), inner);
+}
+
+fn plural_days(n: usize) -> String {
+    if n == 1 { "1 day".to_string() } else { format!("{} days", n) }
+}
+
+fn draw_errors(frame: &mut Frame, app: &App) {
+    let rect = centered_rect(frame.size(), 80, 70);
+    frame.render_widget(Clear, rect);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title("Errors (c:Clear  Esc:Close)")
+        .border_style(Style::default().fg(app.theme.readable(Color::LightRed)));
+    let mut lines: Vec<Line> = Vec::new();
+    for report in app.errors.iter().rev() {
+        lines.push(Line::styled(report.summary.as_str(), Style::default().add_modifier(Modifier::BOLD)));
+        lines.extend(report.detail.lines().map(|l| Line::styled(l, Style::default().add_modifier(Modifier::DIM))));
+        lines.push(Line::default());
+    }
+    frame.render_widget(Paragraph::new(Text::from(lines)).block(block).wrap(Wrap { trim: false }), rect);
+}
+
+fn draw_log(frame: &mut Frame, scroll: usize, app: &App) {
+    let rect = centered_rect(frame.size(), 90, 80);
+    frame.render_widget(Clear, rect);
+    let title = match crate::logging::path() {
+        Some(path) => format!("Debug log: {}", path.display()),
+        None => "Debug log".to_string(),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .border_style(Style::default().fg(app.theme.accent));
+    let inner = block.inner(rect);
+    frame.render_widget(block, rect);
+
+    let lines = crate::logging::recent();
+    if lines.is_empty() {
+        let hint = "Logging is off. Start lazynotes with --debug or RUST_LOG=debug.";
+        frame.render_widget(Paragraph::new(Line::styled(hint, Style::default().add_modifier(Modifier::DIM))), inner);
+        return;
+    }
+    let height = inner.height as usize;
+    let end = lines.len() - scroll.min(lines.len() - 1);
+    let start = end.saturating_sub(height);
+    let text: Vec<Line> = lines[start..end].iter().map(|l| Line::raw(l.as_str())).collect();
+    frame.render_widget(Paragraph::new(Text::from(text)), inner);
+}
+
+/// Leader menu as a panel along the bottom edge, one row per entry.
+fn draw_menu(frame: &mut Frame, menu: &crate::menu::Menu, app: &App) {
+    let area = frame.size();
+    let h = (menu.entries.len() as u16 + 2).min(area.height);
+    let rect = Rect::new(area.x, area.bottom().saturating_sub(h), area.width, h);
+    frame.render_widget(Clear, rect);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!("{}  (Esc:Close  Backspace:Top)", menu.title))
+        .border_style(Style::default().fg(app.theme.accent));
+    let lines: Vec<Line> = menu
+        .entries
+        .iter()
+        .map(|e| {
+            let more = if matches!(e.action, crate::menu::MenuAction::Submenu(_)) { " ▸" } else { "" };
+            Line::from(vec![
+                Span::styled(format!(" {} ", e.key), Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD)),
+                Span::raw(format!(" {}{}", e.label, more)),
+            ])
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(Text::from(lines)).block(block), rect);
+}
+
+fn draw_marks(frame: &mut Frame, setting: bool, selected: usize, app: &App) {
+    let rect = centered_rect(frame.size(), 50, 40);
+    frame.render_widget(Clear, rect);
+    let title = if setting { "Set mark (a-z)" } else { "Jump to mark (a-z, Del removes)" };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .border_style(Style::default().fg(app.theme.accent));
+
+    let items: Vec<ListItem> = app
+        .marks
+        .iter()
+        .map(|(name, mark)| {
+            let row = app.mark_row(mark);
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", name), Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD)),
+                Span::styled(format!("{:>5}  ", row + 1), Style::default().add_modifier(Modifier::DIM)),
+                Span::raw(app.lines[row].trim()),
+            ]))
+        })
+        .collect();
+    let mut state = ListState::default();
+    if !app.marks.is_empty() {
+        state.select(Some(selected));
+    }
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, rect, &mut state);
+}
+
+fn draw_yanks(frame: &mut Frame, selected: usize, app: &App) {
+    let rect = centered_rect(frame.size(), 60, 50);
+    frame.render_widget(Clear, rect);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title("Paste from history (Enter:Paste  Del:Forget)")
+        .border_style(Style::default().fg(app.theme.accent));
+
+    let items: Vec<ListItem> = app
+        .clipboard
+        .history
+        .iter()
+        .map(|text| {
+            let first = text.lines().next().unwrap_or_default();
+            let more = text.lines().count().saturating_sub(1);
+            let mut spans = vec![Span::raw(first.to_string())];
+            if more > 0 {
+                spans.push(Span::styled(format!("  (+{} lines)", more), Style::default().add_modifier(Modifier::DIM)));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, rect, &mut state);
+}
+
+fn draw_merge(frame: &mut Frame, regions: &[crate::merge::Region], selected: usize, app: &App) {
+    use crate::merge::{Choice, Region};
+
+    let rect = centered_rect(frame.size(), 90, 80);
+    frame.render_widget(Clear, rect);
+    let count = crate::merge::conflicts(regions);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(format!(
+            "Changed on disk: hunk {}/{}  ↑↓:Hunk  m/t/b:Mine/Theirs/Both  Enter:Apply  Esc:Keep mine",
+            selected + 1,
+            count
+        ))
+        .border_style(Style::default().fg(app.theme.readable(Color::LightYellow)));
+    let inner = block.inner(rect);
+    frame.render_widget(block, rect);
+
+    let Some(Region::Conflict { base, mine, theirs, choice }) =
+        regions.iter().filter(|r| matches!(r, Region::Conflict { .. })).nth(selected)
+    else {
+        return;
+    };
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 3), Constraint::Ratio(1, 3), Constraint::Ratio(1, 3)])
+        .split(inner);
+    let panes = [
+        ("Base", base, false),
+        ("Mine (buffer)", mine, matches!(choice, Choice::Mine | Choice::Both)),
+        ("Theirs (disk)", theirs, matches!(choice, Choice::Theirs | Choice::Both)),
+    ];
+    for ((title, lines, chosen), area) in panes.into_iter().zip(columns.iter()) {
+        let style = if chosen { Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD) } else { Style::default() };
+        let title = if chosen { format!("{} ✓", title) } else { title.to_string() };
+        let text: Vec<Line> = if lines.is_empty() {
+            vec![Line::styled("(nothing)", Style::default().add_modifier(Modifier::DIM))]
+        } else {
+            lines.iter().map(|l| Line::raw(l.as_str())).collect()
+        };
+        let pane = Paragraph::new(Text::from(text))
+            .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title(title).border_style(style))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(pane, *area);
+    }
+}
+
+fn draw_diff(frame: &mut Frame, title: &str, rows: &[crate::diff::DiffLine], scroll: usize, app: &App) {
+    use crate::diff::DiffLine;
+
+    let rect = centered_rect(frame.size(), 80, 80);
+    frame.render_widget(Clear, rect);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .border_style(Style::default().fg(app.theme.accent));
+
+    let green = Style::default().fg(app.theme.readable(Color::LightGreen));
+    let red = Style::default().fg(app.theme.readable(Color::LightRed));
+    let text: Vec<Line> = rows
+        .iter()
+        .skip(scroll)
+        .map(|row| match row {
+            DiffLine::Same(l) => Line::raw(format!("  {}", l)),
+            DiffLine::Added(l) => Line::styled(format!("+ {}", l), green),
+            DiffLine::Removed(l) => Line::styled(format!("- {}", l), red),
+            DiffLine::Skipped(n) => Line::styled(
+                format!("⋯ {} unchanged line{}", n, if *n == 1 { "" } else { "s" }),
+                Style::default().add_modifier(Modifier::DIM),
+            ),
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(Text::from(text)).block(block), rect);
+}
+
+fn draw_link_report(frame: &mut Frame, issues: &[crate::links::LinkIssue], selected: usize, app: &App) {
+    let rect = centered_rect(frame.size(), 70, 60);
+    frame.render_widget(Clear, rect);
+    let broken = issues.iter().filter(|i| matches!(i, crate::links::LinkIssue::Broken { .. })).count();
+    let title = format!(
+        "{} broken links, {} orphaned notes (Enter:Jump  r:Refresh)",
+        broken,
+        issues.len() - broken
+    );
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .border_style(Style::default().fg(app.theme.accent));
+
+    let items: Vec<ListItem> = issues
+        .iter()
+        .map(|issue| {
+            let rel = crate::search::relative_to(issue.path(), &app.notes_dir);
+            let line = match issue {
+                crate::links::LinkIssue::Broken { row, target, .. } => Line::from(vec![
+                    Span::styled("broken  ", Style::default().fg(app.theme.readable(Color::LightRed))),
+                    Span::styled(format!("{}:{}  ", rel.display(), row + 1), Style::default().fg(app.theme.dir_icon)),
+                    Span::raw(target.clone()),
+                ]),
+                crate::links::LinkIssue::Orphan(_) => Line::from(vec![
+                    Span::styled("orphan  ", Style::default().add_modifier(Modifier::DIM)),
+                    Span::styled(rel.display().to_string(), Style::default().fg(app.theme.dir_icon)),
+                ]),
+            };
+            ListItem::new(line)
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, rect, &mut state);
+}
+
+fn draw_attachments(frame: &mut Frame, items: &[crate::attachments::Attachment], selected: usize, vault: bool, renaming: Option<&str>, app: &App) {
+    use crate::attachments::State;
+
+    let rect = centered_rect(frame.size(), 70, 60);
+    frame.render_widget(Clear, rect);
+    let title = match (renaming, vault) {
+        (Some(name), _) => format!("Rename to: {}  (Enter:Rename  Esc:Cancel)", name),
+        (None, true) => format!("{} orphaned attachments (Enter:Open  r:Rename  d:Delete)", items.len()),
+        (None, false) => "Attachments (Enter:Open  r:Rename  d:Delete unreferenced)".to_string(),
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .border_style(Style::default().fg(app.theme.accent));
+    let list_items: Vec<ListItem> = items
+        .iter()
+        .map(|a| {
+            let (tag, style) = match a.state {
+                State::Linked => ("linked  ", Style::default().fg(app.theme.readable(Color::LightGreen))),
+                State::Missing => ("missing ", Style::default().fg(app.theme.readable(Color::LightRed))),
+                State::Unreferenced => ("unused  ", Style::default().add_modifier(Modifier::DIM)),
+            };
+            let rel = crate::search::relative_to(&a.path, &app.notes_dir);
+            ListItem::new(Line::from(vec![Span::styled(tag, style), Span::raw(rel.display().to_string())]))
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    let list = List::new(list_items)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, rect, &mut state);
+}
+
+fn draw_deleted(frame: &mut Frame, selected: usize, app: &App) {
+    let rect = centered_rect(frame.size(), 60, 40);
+    frame.render_widget(Clear, rect);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title("Recently deleted (Enter:Restore)")
+        .border_style(Style::default().fg(app.theme.accent));
+    let items: Vec<ListItem> = app
+        .deleted
+        .iter()
+        .rev()
+        .map(|file| {
+            ListItem::new(Line::from(vec![
+                Span::raw(crate::search::relative_to(&file.path, &app.notes_dir).display().to_string()),
+                Span::styled(format!("  {}", app.deleted_ago(file)), Style::default().add_modifier(Modifier::DIM)),
+            ]))
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, rect, &mut state);
+}
+
+fn draw_stashes(frame: &mut Frame, stashes: &[(String, String)], selected: usize, app: &App) {
+    let rect = centered_rect(frame.size(), 60, 40);
+    frame.render_widget(Clear, rect);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title("Stashes (Enter:Pop  a:Apply and keep)")
+        .border_style(Style::default().fg(app.theme.accent));
+    let items: Vec<ListItem> = stashes
+        .iter()
+        .map(|(reference, description)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{}  ", reference), Style::default().fg(app.theme.dir_icon)),
+                Span::raw(description.as_str()),
+            ]))
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, rect, &mut state);
+}
+
+fn draw_remotes(frame: &mut Frame, remotes: &[(String, String)], selected: usize, form: Option<&(String, String, bool)>, app: &App) {
+    let rect = centered_rect(frame.size(), 60, 40);
+    frame.render_widget(Clear, rect);
+    let title = match form {
+        Some(_) => "Remote (Tab switches field, Enter saves)",
+        None => "Remotes (a:Add  e:Edit URL)",
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .border_style(Style::default().fg(app.theme.accent));
+
+    if let Some((name, url, editing_url)) = form {
+        let label = |text: &'static str, active: bool| {
+            let style = if active { Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD) } else { Style::default() };
+            Span::styled(text, style)
+        };
+        let mut lines = vec![
+            Line::from(vec![label("Name: ", !editing_url), Span::raw(name.as_str())]),
+            Line::from(vec![label("URL:  ", *editing_url), Span::raw(url.as_str())]),
+        ];
+        if remotes.is_empty() {
+            lines.push(Line::raw(""));
+            lines.push(Line::styled("The notes repo has no remotes yet", Style::default().add_modifier(Modifier::DIM)));
+        }
+        frame.render_widget(Paragraph::new(lines).block(block), rect);
+        return;
+    }
+
+    let width = remotes.iter().map(|(n, _)| n.chars().count()).max().unwrap_or(0);
+    let items: Vec<ListItem> = remotes
+        .iter()
+        .map(|(name, url)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:width$}  ", name, width = width), Style::default().fg(app.theme.dir_icon)),
+                Span::raw(url.as_str()),
+            ]))
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, rect, &mut state);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_replace(
+    frame: &mut Frame,
+    pattern: &str,
+    with: &str,
+    editing_with: bool,
+    matches: &[crate::replace::ReplaceMatch],
+    selected: usize,
+    previewing: bool,
+    commit: bool,
+    app: &App,
+) {
+    let rect = centered_rect(frame.size(), 80, 70);
+    frame.render_widget(Clear, rect);
+    let hint = if previewing {
+        "Replace in vault (Space:Toggle  a:All  c:Commit  Enter:Apply  Esc:Back)"
+    } else {
+        "Replace in vault (Tab:Switch field  Alt+R/C/W:Options  Enter:Preview)"
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(hint)
+        .border_style(Style::default().fg(app.theme.accent));
+    let inner = block.inner(rect);
+    frame.render_widget(block, rect);
+
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(1)])
+        .split(inner);
+    let label = |name: &'static str, active: bool| {
+        let style = if active && !previewing { Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD) } else { Style::default() };
+        Span::styled(name, style)
+    };
+    let accepted = matches.iter().filter(|m| m.accepted).count();
+    let summary = if previewing {
+        format!("{} of {} matches selected   auto-commit: {}", accepted, matches.len(), if commit { "on" } else { "off" })
+    } else {
+        String::new()
+    };
+    let header = vec![
+        Line::from(vec![
+            label("Find:    ", !editing_with),
+            Span::styled(app.searches.options.indicator(), Style::default().fg(app.theme.accent)),
+            Span::raw(pattern),
+        ]),
+        Line::from(vec![label("Replace: ", editing_with), Span::raw(with)]),
+        Line::styled(summary, Style::default().add_modifier(Modifier::DIM)),
+    ];
+    frame.render_widget(Paragraph::new(Text::from(header)), rows[0]);
+
+    let items: Vec<ListItem> = matches
+        .iter()
+        .map(|m| {
+            let rel = crate::search::relative_to(&m.path, &app.notes_dir);
+            let mark = if m.accepted { "[x] " } else { "[ ] " };
+            let preview = m.preview();
+            let end = m.start + m.replacement.len();
+            let mut spans = vec![
+                Span::raw(mark),
+                Span::styled(format!("{}:{}  ", rel.display(), m.row + 1), Style::default().fg(app.theme.dir_icon)),
+            ];
+            let style = if m.accepted { Style::default().fg(Color::Black).bg(Color::Green) } else { Style::default().add_modifier(Modifier::DIM) };
+            let shown = if m.accepted { preview } else { m.line.clone() };
+            let end = if m.accepted { end } else { m.end };
+            spans.push(Span::raw(shown[..m.start].to_string()));
+            spans.push(Span::styled(shown[m.start..end].to_string(), style));
+            spans.push(Span::raw(shown[end..].to_string()));
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let mut state = ListState::default();
+    if previewing && !matches.is_empty() {
+        state.select(Some(selected));
+    }
+    let list = List::new(items).highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, rows[1], &mut state);
+}
+
+fn draw_insert_date(frame: &mut Frame, selected: usize, app: &App) {
+    let rect = centered_rect(frame.size(), 50, 40);
+    frame.render_widget(Clear, rect);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title("Insert date/time")
+        .border_style(Style::default().fg(app.theme.accent));
+
+    let now = crate::datefmt::now();
+    let items: Vec<ListItem> = app
+        .date_formats
+        .iter()
+        .map(|fmt| {
+            ListItem::new(Line::from(vec![
+                Span::raw(crate::datefmt::strftime(now, fmt)),
+                Span::styled(format!("  {}", fmt), Style::default().add_modifier(Modifier::DIM)),
+            ]))
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, rect, &mut state);
+}
+
+/// Entries are `(headline, detail)` pairs.
+fn draw_history_list(frame: &mut Frame, area: Rect, title: &str, entries: &[(String, String)], selected: usize, app: &App) {
+    let items: Vec<ListItem> = entries
+        .iter()
+        .map(|(headline, detail)| {
+            ListItem::new(vec![
+                Line::from(headline.as_str()),
+                Line::styled(detail.as_str(), Style::default().add_modifier(Modifier::ITALIC)),
+            ])
+        })
+        .collect();
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .title_style(Style::default().add_modifier(Modifier::BOLD))
+        .border_style(Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD));
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().fg(app.theme.on_accent).bg(app.theme.accent).add_modifier(Modifier::BOLD))
+        .highlight_symbol("→ ");
+    let mut state = ListState::default();
+    state.select(Some(selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
 fn draw_commit_list(frame: &mut Frame, area: Rect, app: &mut App) {
     use ratatui::widgets::{List, ListItem, Block, Borders};
 
     let commits = &app.git_section.commits;
     let selected = app.git_section.selected;
-    let items: Vec<ListItem> = commits
+    // A straight line of history needs no graph.
+    let show_graph = app.git_section.graph.iter().any(|row| row.commit.len() > 1 || row.link.len() > 1);
+    let mut items: Vec<ListItem> = commits
         .iter()
-        .map(|c| {
-            let summary = format!("{} {}", &c.hash, &c.summary);
-            let line1 = Line::from(Span::raw(summary));
-            let line2 = Line::from(Span::styled(format!("{} • {}", &c.author, &c.date), Style::default().add_modifier(Modifier::ITALIC)));
+        .enumerate()
+        .map(|(i, c)| {
+            let row = app.git_section.graph.get(i).filter(|_| show_graph);
+            let mut line1 = Line::from(row.map(|r| graph_spans(&r.commit, app)).unwrap_or_default());
+            line1.spans.push(Span::raw(format!("{} {}", &c.hash, &c.summary)));
+            let mut line2 = Line::from(row.map(|r| graph_spans(&r.link, app)).unwrap_or_default());
+            line2.spans.push(Span::styled(format!("{} • {}", &c.author, app.commit_date(c)), Style::default().add_modifier(Modifier::ITALIC)));
             ListItem::new(vec![line1, line2])
         })
         .collect();
+    if items.is_empty() && app.git_section.is_loading() {
+        items.push(ListItem::new(Line::styled("Loading commits…", Style::default().add_modifier(Modifier::ITALIC))));
+    }
 
     let block = Block::default()
         .borders(Borders::ALL)
@@ -150,34 +1361,79 @@ fn draw_commit_list(frame: &mut Frame, area: Rect, app: &mut App) {
         .title_style(Style::default().add_modifier(Modifier::BOLD))
         .title_alignment(Alignment::Left)
         .border_style(if matches!(app.focus, Focus::Commits) {
-            Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)
+            Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD)
         } else {
             Style::default()
         });
 
     let list = List::new(items)
         .block(block)
-        .highlight_style(Style::default().fg(Color::Black).bg(Color::Green).add_modifier(Modifier::BOLD))
+        .highlight_style(Style::default().fg(app.theme.on_accent).bg(app.theme.accent).add_modifier(Modifier::BOLD))
         .highlight_symbol("→ ");
 
-    let mut state = ratatui::widgets::ListState::default();
-    if !commits.is_empty() {
-        state.select(Some(selected));
-    }
-    frame.render_stateful_widget(list, area, &mut state);
+    app.git_section.list_state.select((!commits.is_empty()).then_some(selected));
+    frame.render_stateful_widget(list, area, &mut app.git_section.list_state);
+}
+
+/// Graph cells padded to the widest row, each lane in its own color.
+fn graph_spans(cells: &[char], app: &App) -> Vec<Span<'static>> {
+    const LANE_COLORS: [Color; 5] = [Color::LightBlue, Color::LightMagenta, Color::LightGreen, Color::LightYellow, Color::LightCyan];
+    let width = app.git_section.graph.iter().map(|r| r.commit.len().max(r.link.len())).max().unwrap_or(0);
+    (0..width)
+        .map(|i| match cells.get(i) {
+            Some(&c) if c != ' ' => Span::styled(format!("{} ", c), Style::default().fg(app.theme.readable(LANE_COLORS[i % LANE_COLORS.len()]))),
+            _ => Span::raw("  "),
+        })
+        .collect()
 }
 
 fn draw_changed_files(frame: &mut Frame, area: Rect, app: &mut App) {
-    use ratatui::widgets::{Paragraph, Block, Borders};
+    use ratatui::widgets::{List, ListItem, Block, Borders};
     let files = app.git_section.selected_changed_files();
-    let file_items: Vec<Line> = if files.is_empty() {
-        vec![Line::raw("(no changed files)")]
+    let items: Vec<ListItem> = if files.is_empty() {
+        vec![ListItem::new(Line::raw("(no changed files)"))]
     } else {
-        files.iter().map(|f| Line::from(Span::raw(f.clone()))).collect()
+        files
+            .iter()
+            .map(|f| {
+                let color = match f.status {
+                    'A' => Color::LightGreen,
+                    'D' => Color::LightRed,
+                    'R' | 'C' => Color::LightBlue,
+                    _ => Color::Yellow,
+                };
+                let mut spans = vec![Span::styled(format!("{} ", f.status), Style::default().fg(app.theme.readable(color)).add_modifier(Modifier::BOLD))];
+                if let Some(from) = &f.from {
+                    spans.push(Span::styled(format!("{} → ", from), Style::default().add_modifier(Modifier::DIM)));
+                }
+                spans.push(Span::raw(f.path.clone()));
+                ListItem::new(Line::from(spans))
+            })
+            .collect()
+    };
+
+    let focused = matches!(app.focus, Focus::ChangedFiles);
+    // Position in long lists, which only show part of the files.
+    let title = match files.len() {
+        n if focused && n > 0 => format!("[5]Changed Files {}/{}", app.git_section.file_selected.min(n - 1) + 1, n),
+        0 => "[5]Changed Files".to_string(),
+        n => format!("[5]Changed Files ({})", n),
     };
-    let files_para = Paragraph::new(Text::from(file_items))
-        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Changed Files"));
-    frame.render_widget(files_para, area);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .title_style(Style::default().add_modifier(Modifier::BOLD))
+        .border_style(if focused { Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD) } else { Style::default() });
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().fg(app.theme.on_accent).bg(app.theme.accent).add_modifier(Modifier::BOLD));
+
+    // Only show the selection while the pane is in use.
+    let selected = (focused && !files.is_empty()).then(|| app.git_section.file_selected.min(files.len() - 1));
+    app.git_section.files_state.select(selected);
+    app.changed_files_area = area;
+    frame.render_stateful_widget(list, area, &mut app.git_section.files_state);
 }
 
 fn draw_right_panel(frame: &mut Frame, title_area: Rect, content_area: Rect, app: &mut App) {
@@ -186,41 +1442,117 @@ fn draw_right_panel(frame: &mut Frame, title_area: Rect, content_area: Rect, app
     } else {
         Style::default()
     };
+    let crumbs = app.breadcrumb();
+    let mut breadcrumb = Line::default();
+    for (i, part) in crumbs.iter().enumerate() {
+        if i > 0 {
+            breadcrumb.spans.push(Span::styled(" ▸ ", Style::default().fg(app.theme.accent)));
+        }
+        let style = if i + 1 == crumbs.len() { Style::default() } else { Style::default().add_modifier(Modifier::DIM) };
+        breadcrumb.spans.push(Span::styled(part.clone(), style));
+    }
+
     let title = Paragraph::new(app.title.as_str())
         .block(
                 Block::default()
                 .title(
-                    ratatui::widgets::block::Title::from("[2]Title")
+                    ratatui::widgets::block::Title::from(if app.scratch_active { "[2]Scratch" } else { "[2]Title" })
                         .alignment(Alignment::Left)
                 )
+                .title(
+                    ratatui::widgets::block::Title::from(breadcrumb)
+                        .alignment(Alignment::Right)
+                )
                 .title_style(title_style)
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
-                .border_style(if matches!(app.focus, Focus::Title) { Style::default().fg(Color::Green).add_modifier(Modifier::BOLD) } else { Style::default() }),
+                .border_style(if matches!(app.focus, Focus::Title) { Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD) } else { Style::default() }),
         )
         .wrap(Wrap { trim: false });
     frame.render_widget(title, title_area);
 
-    let text_lines: Vec<Line> = if app.lines.is_empty() {
-        vec![Line::raw("")]
-    } else {
-        app.lines.iter().map(|l| Line::raw(l.clone())).collect()
+    let revision = match &app.modal {
+        Some(crate::app::Modal::History { revisions, selected, lines, scroll }) => {
+            let rev = &revisions[*selected];
+            Some((format!("{} ({})", rev.hash, app.commit_date(rev)), lines, *scroll))
+        }
+        Some(crate::app::Modal::LocalHistory { snapshots, selected, lines, scroll }) => {
+            Some((format!("snapshot {}", snapshots[*selected].label()), lines, *scroll))
+        }
+        _ => None,
     };
+    if let Some((label, lines, scroll)) = revision {
+        let text: Vec<Line> = lines.iter().skip(scroll).map(|l| Line::raw(l.as_str())).collect();
+        let marker = Style::default().fg(app.theme.readable(Color::LightYellow)).add_modifier(Modifier::BOLD);
+        let paragraph = Paragraph::new(Text::from(text))
+            .block(
+                Block::default()
+                    .title(Span::styled(format!("[3]Content @ {} read-only", label), marker))
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(marker),
+            )
+            .wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, content_area);
+        return;
+    }
+
+    // Only the rows that can be on screen are turned into (borrowed) Lines,
+    // so large notes don't cost a full copy per frame.
+    let visible_rows = content_area.height.saturating_sub(2) as usize;
+    let folds = app.fold_ranges();
+    app.set_content_size(content_area.width.saturating_sub(2) as usize, visible_rows);
+    let text_lines: Vec<Line> = app
+        .lines
+        .iter()
+        .enumerate()
+        .skip(app.scroll_y)
+        .filter(|(row, _)| !folds.iter().any(|&f| crate::markdown::in_fold(f, *row)))
+        .take(visible_rows)
+        .map(|(row, l)| {
+            let mut line = content_line(app, row, l);
+            if let Some(&(_, end)) = folds.iter().find(|(start, _)| *start == row) {
+                line.spans.push(Span::styled(
+                    format!(" ▸ ({} lines)", end - row - 1),
+                    Style::default().fg(app.theme.accent).add_modifier(Modifier::DIM),
+                ));
+            }
+            line
+        })
+        .collect();
+
+    let headings = crate::markdown::headings(&app.lines);
+    let current_heading = crate::markdown::current_heading(&headings, app.cursor_row)
+        .map(|i| Span::styled(format!(" § {} ", headings[i].title), Style::default().add_modifier(Modifier::DIM)))
+        .unwrap_or_default();
 
     let paragraph = Paragraph::new(Text::from(text_lines))
         .block(
                 Block::default()
                 .title(
-                    ratatui::widgets::block::Title::from(if app.dirty { "[3]Content *" } else { "[3]Content" })
+                    ratatui::widgets::block::Title::from(if app.scratch_active {
+                        "[3]Scratch (not a note)"
+                    } else if app.dirty {
+                        "[3]Content *"
+                    } else {
+                        "[3]Content"
+                    })
                         .alignment(Alignment::Left)
                 )
+                .title(
+                    ratatui::widgets::block::Title::from(current_heading)
+                        .alignment(Alignment::Right)
+                )
                 .title_style(Style::default().add_modifier(Modifier::BOLD))
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
-                .border_style(if matches!(app.focus, Focus::Content) { Style::default().fg(Color::Green).add_modifier(Modifier::BOLD) } else { Style::default() }),
-        )
-        .wrap(Wrap { trim: false })
-        .scroll((app.scroll_y as u16, 0));
+                .border_style(if matches!(app.focus, Focus::Content) { Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD) } else { Style::default() }),
+        );
+    let paragraph = if app.wrap {
+        paragraph.wrap(Wrap { trim: false })
+    } else {
+        paragraph.scroll((0, app.scroll_x as u16))
+    };
     frame.render_widget(paragraph, content_area);
 
     match app.focus {
@@ -237,20 +1569,149 @@ fn draw_right_panel(frame: &mut Frame, title_area: Rect, content_area: Rect, app
     }
 }
 
-fn draw_footer(frame: &mut Frame, area: Rect, app: &mut App) {
+fn content_line<'a>(app: &App, row: usize, line: &'a str) -> Line<'a> {
+    let mut ranges: Vec<(usize, usize, Style)> = Vec::new();
+    for f in crate::markdown::footnotes(line) {
+        let style = Style::default().fg(app.theme.accent);
+        ranges.push((f.start, f.end, if f.definition { style.add_modifier(Modifier::BOLD) } else { style }));
+    }
+    for &(r, start, end) in &app.find_matches {
+        if r == row {
+            let current = (r, start) == (app.cursor_row, app.cursor_col);
+            let bg = if current { app.theme.accent } else { Color::Yellow };
+            ranges.push((start, end, Style::default().fg(Color::Black).bg(bg)));
+        }
+    }
+    let mut selection_spans_eol = false;
+    if let Some(((sr, sc), (er, ec))) = app.selection_range()
+        && (sr..=er).contains(&row)
+    {
+        let start = if row == sr { sc } else { 0 };
+        let end = if row == er { ec } else { line.len() };
+        ranges.push((start, end, Style::default().add_modifier(Modifier::REVERSED)));
+        selection_spans_eol = row != er;
+    }
+    if let Some((top, bottom, left, right)) = app.block_range()
+        && (top..=bottom).contains(&row)
+    {
+        let start = crate::app::char_to_byte(line, left);
+        if left == right {
+            // A zero-width block still shows which column it edits.
+            let end = crate::app::char_to_byte(line, left + 1);
+            ranges.push((start, end, Style::default().add_modifier(Modifier::UNDERLINED)));
+        } else {
+            ranges.push((start, crate::app::char_to_byte(line, right), Style::default().add_modifier(Modifier::REVERSED)));
+        }
+    }
+    let mut out = styled_line(line, &ranges);
+    // Keep the line break visible as a selected cell when the selection spans it.
+    if selection_spans_eol {
+        out.spans.push(Span::styled(" ", Style::default().add_modifier(Modifier::REVERSED)));
+    }
+    out
+}
+
+/// Splits `line` at every range boundary and patches each segment with the
+/// styles of all ranges covering it, so overlapping highlights compose.
+fn styled_line<'a>(line: &'a str, ranges: &[(usize, usize, Style)]) -> Line<'a> {
+    if ranges.is_empty() {
+        return Line::raw(line);
+    }
+    let mut cuts: Vec<usize> = vec![0, line.len()];
+    for &(start, end, _) in ranges {
+        cuts.push(clamp_to_char_boundary(line, start));
+        cuts.push(clamp_to_char_boundary(line, end));
+    }
+    cuts.sort_unstable();
+    cuts.dedup();
+    let spans = cuts
+        .windows(2)
+        .map(|w| {
+            let style = ranges
+                .iter()
+                .filter(|(s, e, _)| *s <= w[0] && w[1] <= *e)
+                .fold(Style::default(), |acc, (_, _, st)| acc.patch(*st));
+            Span::styled(&line[w[0]..w[1]], style)
+        })
+        .collect::<Vec<_>>();
+    Line::from(spans)
+}
+
+fn draw_footer(frame: &mut Frame, area: Rect, app: &mut App, compact: bool) {
     
     let help = Line::from(vec![
-    Span::styled("Ctrl+S", Style::default().fg(Color::LightMagenta)), Span::raw(":Save"), Span::raw("  "),
+    Span::styled("Ctrl+S", Style::default().fg(app.theme.readable(Color::LightMagenta))), Span::raw(":Save"), Span::raw("  "),
     Span::styled("Enter/Right", Style::default().fg(Color::Green)), Span::raw(":Open"), Span::raw("  "),
-    Span::styled("d", Style::default().fg(Color::LightRed)), Span::raw(":Delete"), Span::raw("  "),
+    Span::styled("d", Style::default().fg(app.theme.readable(Color::LightRed))), Span::raw(":Delete"), Span::raw("  "),
+    Span::styled("Ctrl+G", Style::default().fg(app.theme.readable(Color::LightBlue))), Span::raw(":Menu"), Span::raw("  "),
     
     ]);
     
     let mut footer_text = vec![help];
+    if app.unseen_errors > 0 {
+        let label = if app.unseen_errors == 1 { "error" } else { "errors" };
+        footer_text[0].spans.push(Span::styled(
+            format!("✗ {} {} (Alt+E)", app.unseen_errors, label),
+            Style::default().fg(app.theme.readable(Color::LightRed)).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some(n) = app.git_section.behind.filter(|n| *n > 0) {
+        let label = if n == 1 { "commit" } else { "commits" };
+        footer_text[0].spans.push(Span::styled(
+            format!("  ⇣ {} new remote {} (p in Commits pulls)", n, label),
+            Style::default().fg(app.theme.readable(Color::LightCyan)).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some((reg, _)) = &app.recording {
+        footer_text[0].spans.insert(0, Span::styled(format!("● REC @{}  ", reg), Style::default().fg(app.theme.readable(Color::LightRed)).add_modifier(Modifier::BOLD)));
+    }
+    if let Some(crate::app::Modal::MacroRegister { record, count }) = &app.modal {
+        let prompt = if *record { "Record macro into register (a-z): " } else { "Replay macro ([count]register): " };
+        footer_text = vec![Line::from(vec![
+            Span::styled(prompt, Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD)),
+            Span::raw(count.clone()),
+            Span::raw("   Esc:Cancel"),
+        ])];
+    }
+    if let Some(crate::app::Modal::Find { query, .. }) = &app.modal {
+        let options = app.searches.options;
+        let count = match (app.find_position(), app.find_matches.len()) {
+            _ if crate::search::Matcher::new(query, options).is_err() => " (invalid regex)".to_string(),
+            (_, 0) if !query.is_empty() => " (no matches)".to_string(),
+            (_, 0) => String::new(),
+            (Some(i), n) => format!(" ({}/{})", i, n),
+            (None, n) => format!(" ({})", n),
+        };
+        footer_text = vec![Line::from(vec![
+            Span::styled("Find: ", Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD)),
+            Span::styled(options.indicator(), Style::default().fg(app.theme.accent)),
+            Span::raw(query.clone()),
+            Span::styled(count, Style::default().add_modifier(Modifier::DIM)),
+            Span::raw("   Up/Down:Prev/Next  Alt+R/C/W:Regex/Case/Word  Enter:Accept  Esc:Cancel"),
+        ])];
+    }
+    if let Some(crate::app::Modal::GotoLine { input }) = &app.modal {
+        footer_text = vec![Line::from(vec![
+            Span::styled("Go to line: ", Style::default().fg(app.theme.accent).add_modifier(Modifier::BOLD)),
+            Span::raw(input.clone()),
+            Span::styled(format!(" (1-{}, or N%)", app.lines.len()), Style::default().add_modifier(Modifier::DIM)),
+            Span::raw("   Enter:Go  Esc:Cancel"),
+        ])];
+    }
     if let Some(msg) = &app.status_message {
         footer_text.push(Line::from(Span::raw(format!("  {}", msg))));
     }
 
+    if compact {
+        // One line: a message beats the key help.
+        let mut line = if app.modal.is_none() && footer_text.len() > 1 { footer_text.pop() } else { footer_text.into_iter().next() }.unwrap_or_default();
+        // The outer frame that normally says so is gone.
+        if app.read_only {
+            line.spans.insert(0, Span::styled("[read-only] ", Style::default().add_modifier(Modifier::BOLD)));
+        }
+        frame.render_widget(Paragraph::new(line).alignment(Alignment::Center), area);
+        return;
+    }
     let footer = Paragraph::new(Text::from(footer_text))
         .alignment(Alignment::Center)
     .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded));
@@ -265,8 +1726,9 @@ fn content_cursor_to_screen(area: Rect, app: &App) -> (u16, u16) {
         height: area.height.saturating_sub(2),
     };
 
-    let visible_row = app.cursor_row.saturating_sub(app.scroll_y);
+    let visible_row = app.screen_row(app.cursor_row);
     let y = inner.y + (visible_row as u16).min(inner.height.saturating_sub(1));
-    let x = inner.x + (app.cursor_col as u16).min(inner.width.saturating_sub(1));
+    let col = app.cursor_display_col().saturating_sub(app.scroll_x);
+    let x = inner.x + (col as u16).min(inner.width.saturating_sub(1));
     (x, y)
 }